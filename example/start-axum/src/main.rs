@@ -36,6 +36,9 @@ async fn main() {
         optimizer: ImageOptimizer::new("/cache/image", root, 1),
     };
 
+    // Pre-generate the images of every page in the background.
+    state.optimizer.warm_cache(App);
+
     // Build Router.
     let app = Router::new()
         .route("/api/*fn_name", post(handle_server_fns))
@@ -0,0 +1,152 @@
+use crate::image::background_style;
+use crate::optimizer::*;
+
+use leptos::*;
+use leptos_meta::Link;
+
+/// Renders a `<div>` with an optimized image as its `background-image`, wrapping its children.
+///
+/// For hero sections and other content laid over an image, which can't use an `<img>`.
+/// The image is resized like an [`crate::Image`] and sized to cover or fit the element following `fit`.
+/// With `blur`, the blur placeholder is drawn underneath the image until it has loaded.
+///
+/// ```
+/// use leptos::*;
+/// use leptos_image::*;
+///
+/// #[component]
+/// fn Hero() -> impl IntoView {
+///     view! {
+///         <BackgroundImage src="/hero.jpg" width=1920 height=1080 fit=ResizeMode::Cover blur=true class="hero">
+///             <h1>"Welcome"</h1>
+///         </BackgroundImage>
+///     }
+/// }
+/// ```
+#[component]
+pub fn BackgroundImage(
+    /// Path to a static image, relative to the site root.
+    #[prop(into)]
+    src: String,
+    /// Resize width, the aspect ratio is maintained.
+    width: u32,
+    /// Resize height, the aspect ratio is maintained.
+    height: u32,
+    /// How the image fits the element, and the `width` x `height` box it is resized to.
+    /// Defaults to [`crate::ResizeMode::Contain`], use [`crate::ResizeMode::Cover`] to fill the element.
+    #[prop(optional)]
+    fit: crate::ResizeMode,
+    /// Image quality. 0-100.
+    /// Defaults to the quality configured on the optimizer, 75 unless changed.
+    #[prop(optional)]
+    quality: Option<u8>,
+    /// Draws the blur placeholder underneath the image.
+    /// The placeholder is generated in the background the first time the image is rendered,
+    /// until then the element renders without one.
+    #[prop(default = false)]
+    blur: bool,
+    /// Will add preload link to head if true.
+    /// Background images are only discovered once styles apply, so this helps above the fold.
+    #[prop(default = false)]
+    priority: bool,
+    /// Style class for the `<div>`.
+    #[prop(into, optional)]
+    class: Option<AttributeValue>,
+    /// Inline style of the `<div>`, appended to the generated background styles so it takes precedence.
+    #[prop(into, optional)]
+    style: Option<String>,
+    /// Tenant the image belongs to. Defaults to the tenant provided with [`crate::provide_image_tenant`].
+    #[prop(into, optional)]
+    tenant: Option<String>,
+    /// Content rendered over the image.
+    #[prop(optional)]
+    children: Option<ChildrenFn>,
+) -> impl IntoView {
+    let tenant = tenant.or_else(crate::provider::use_image_tenant);
+    let resource = crate::use_image_cache_resource();
+
+    #[cfg(not(feature = "blur-placeholder"))]
+    let blur = {
+        if blur {
+            logging::debug_warn!("Blur placeholders require the `blur-placeholder` feature.");
+        }
+        false
+    };
+    #[cfg(feature = "blur-placeholder")]
+    if blur {
+        crate::provider::request_placeholder(
+            CachedImage::blur(src.clone()).scoped(tenant.as_deref()),
+        );
+    }
+    let preload = priority && crate::provider::claim_preload(&src);
+
+    let src = store_value(src);
+    let tenant = store_value(tenant);
+    let class = store_value(class.map(|c| c.into_attribute_boxed()));
+    let user_style = store_value(style);
+    let children = store_value(children);
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                resource
+                    .get()
+                    .map(|config| {
+                        let quality = quality.unwrap_or(config.default_quality);
+                        let format = config.formats.last().copied().unwrap_or_default();
+                        let image = tenant
+                            .with_value(|tenant| {
+                                CachedImage::resize(src.get_value(), width, height, quality)
+                                    .scoped(tenant.as_deref())
+                                    .with_fit(fit)
+                                    .with_format(format)
+                            });
+                        let url = config.url(&image);
+                        let (size, position) = background_fit(fit);
+                        let image_layer = (format!("url('{url}')"), size.to_string(), position);
+                        #[cfg(feature = "blur-placeholder")]
+                        let placeholder = blur
+                            .then(|| {
+                                let blur_image = tenant
+                                    .with_value(|tenant| {
+                                        CachedImage::blur(src.get_value()).scoped(tenant.as_deref())
+                                    });
+                                crate::image::cached_placeholder(&config, blur_image)
+                            })
+                            .flatten()
+                            .map(crate::image::placeholder_layers)
+                            .unwrap_or_default();
+                        #[cfg(not(feature = "blur-placeholder"))]
+                        let placeholder = {
+                            let _ = blur;
+                            Vec::new()
+                        };
+                        // The placeholder is drawn underneath the image, showing until it has loaded.
+                        let layers: Vec<_> = std::iter::once(image_layer).chain(placeholder).collect();
+                        let style = background_style(&layers) + &user_style.get_value().unwrap_or_default();
+                        view! {
+                            {preload
+                                .then(|| {
+                                    view! { <Link rel="preload" as_="image" href=url.clone()/> }
+                                })}
+                            <div class=class.get_value() style=style>
+                                {children.with_value(|children| children.as_ref().map(|children| children()))}
+                            </div>
+                        }
+                    })
+            }}
+
+        </Suspense>
+    }
+}
+
+// Background size and position drawing the image like an `<img>` resized with `fit`.
+fn background_fit(fit: ResizeMode) -> (&'static str, String) {
+    match fit {
+        ResizeMode::Contain => ("contain", "center".to_string()),
+        ResizeMode::Cover => ("cover", "center".to_string()),
+        ResizeMode::Fill => ("100% 100%", "center".to_string()),
+        ResizeMode::Crop(gravity) => ("cover", gravity.as_str().replace('-', " ")),
+        ResizeMode::Focus(focus) => ("cover", focus.css_position()),
+    }
+}
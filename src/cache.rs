@@ -0,0 +1,162 @@
+use crate::optimizer::{CachedImage, CreateImageError, ImageOptimizer};
+
+/// Creates the images of an app ahead of time, e.g. at startup, so its first visitors are served from cache.
+///
+/// Blur placeholders are also loaded in memory, so `<Image blur=true/>` inlines them on the first render.
+/// They are created in the placeholder mode configured on the optimizer.
+///
+/// A failing image doesn't stop the others, it is recorded in the returned [`CacheReport`].
+///
+/// ```no_run
+/// use leptos_image::*;
+///
+/// # async fn warm() {
+/// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+/// let report = cache_app_images(
+///     &optimizer,
+///     [
+///         CachedImage::resize("/cute_ferris.png", 750, 500, 85),
+///         CachedImage::blur("/cute_ferris.png"),
+///     ],
+/// )
+/// .await;
+/// assert!(report.failure_rate() < 0.05, "{report}");
+/// # }
+/// ```
+pub async fn cache_app_images(
+    optimizer: &ImageOptimizer,
+    images: impl IntoIterator<Item = CachedImage>,
+) -> CacheReport {
+    let start = std::time::Instant::now();
+    let placeholder = optimizer.config().placeholder;
+    let mut report = CacheReport::default();
+    for image in images {
+        report
+            .record(optimizer, &image.with_placeholder(placeholder))
+            .await;
+    }
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Outcome of creating many images at once, with [`cache_app_images`], [`ImageOptimizer::create_image_set`]
+/// or when warming the cache.
+///
+/// Failures are collected instead of stopping the run, so a CI job can fail only above an error threshold.
+#[derive(Debug, Default)]
+pub struct CacheReport {
+    /// Images that were found, whatever their outcome.
+    pub discovered: usize,
+    /// Images that were created.
+    pub created: usize,
+    /// Images skipped because they were already cached.
+    pub skipped: usize,
+    /// Images that failed, with the error for each.
+    pub failed: Vec<(CachedImage, CreateImageError)>,
+    /// Size in bytes of the created images.
+    pub bytes: u64,
+    /// Time the whole run took.
+    pub elapsed: std::time::Duration,
+}
+
+impl CacheReport {
+    /// Share of the discovered images that failed, from 0 to 1.
+    pub fn failure_rate(&self) -> f64 {
+        if self.discovered == 0 {
+            0.0
+        } else {
+            self.failed.len() as f64 / self.discovered as f64
+        }
+    }
+
+    /// Returns `true` if no image failed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    // Creates the image, loading blur placeholders in memory, and counts the outcome.
+    // Created images are allowed in strict mode, see `OptimizerConfig::strict`.
+    pub(crate) async fn record(&mut self, optimizer: &ImageOptimizer, image: &CachedImage) {
+        self.discovered += 1;
+        optimizer.allow_image(image);
+        match optimizer.create_image(image).await {
+            Ok(true) => {
+                tracing::info!("Created Image: {}", image);
+                self.created += 1;
+                if let Ok(path) = optimizer.get_file_path_from_root(image) {
+                    if let Ok(metadata) = tokio::fs::metadata(path).await {
+                        self.bytes += metadata.len();
+                    }
+                }
+            }
+            Ok(false) => self.skipped += 1,
+            Err(e) => {
+                tracing::error!("Failed to create image {}: {:?}", image, e);
+                self.failed.push((image.clone(), e));
+                return;
+            }
+        }
+        optimizer.add_file_to_cache(image).await;
+    }
+}
+
+impl std::fmt::Display for CacheReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} images: {} created, {} cached, {} failed ({} bytes in {:.1?})",
+            self.discovered,
+            self.created,
+            self.skipped,
+            self.failed.len(),
+            self.bytes,
+            self.elapsed
+        )
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    #[test]
+    fn caches_app_images() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _ = std::fs::remove_dir_all("cache/image/app");
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("app");
+        let src = "./example/start-axum/public/cute_ferris.png";
+        let images = [CachedImage::resize(src, 40, 40, 75), CachedImage::blur(src)];
+
+        let report = runtime.block_on(cache_app_images(&optimizer, images.clone()));
+        assert_eq!(report.created, 2);
+        assert!(report.bytes > 0);
+        assert!(optimizer.cache.contains_key(
+            &CachedImage::blur(src).with_placeholder(optimizer.config().placeholder)
+        ));
+
+        let report = runtime.block_on(cache_app_images(&optimizer, images));
+        assert_eq!((report.created, report.skipped), (0, 2));
+    }
+
+    #[test]
+    fn reports_failures() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("app-failures");
+        let src = "./example/start-axum/public/cute_ferris.png";
+        let images = [
+            CachedImage::resize("/missing.png", 40, 40, 75),
+            CachedImage::resize(src, 40, 40, 75),
+        ];
+
+        let report = runtime.block_on(cache_app_images(&optimizer, images));
+        assert_eq!(report.discovered, 2);
+        assert_eq!(report.created + report.skipped, 1);
+        assert!(matches!(
+            report.failed.as_slice(),
+            [(_, CreateImageError::SourceNotFound(_))]
+        ));
+        assert_eq!(report.failure_rate(), 0.5);
+        assert!(!report.is_success());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::provider::SharedImageConfig;
+use crate::ImageSrc;
+
+use leptos::*;
+
+/// An image of an [`ImageGallery`].
+///
+/// Plain sources convert into an image without alt text, so `images=vec!["/a.jpg", "/b.jpg"]` works.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GalleryImage {
+    /// Image source. Static sources should be a path relative to root.
+    pub src: ImageSrc,
+    /// Image alt text.
+    pub alt: String,
+}
+
+impl GalleryImage {
+    /// Creates an image with alt text.
+    pub fn new(src: impl Into<ImageSrc>, alt: impl Into<String>) -> Self {
+        Self {
+            src: src.into(),
+            alt: alt.into(),
+        }
+    }
+}
+
+impl From<&str> for GalleryImage {
+    fn from(src: &str) -> Self {
+        Self::new(src, "")
+    }
+}
+
+impl From<String> for GalleryImage {
+    fn from(src: String) -> Self {
+        Self::new(src, "")
+    }
+}
+
+/// Renders a list of [`crate::Image`]s sharing the same size and settings, e.g. the thumbnails of a photo grid.
+///
+/// The image config is loaded once for the whole list, in a single `<Suspense/>`, and cached placeholders
+/// are looked up in one pass instead of once per image. The images are rendered without a wrapper,
+/// put the gallery in the container that lays them out.
+///
+/// ```
+/// use leptos::*;
+/// use leptos_image::*;
+///
+/// #[component]
+/// fn Photos() -> impl IntoView {
+///     view! {
+///         <div class="grid">
+///             <ImageGallery
+///                 images=vec![GalleryImage::new("/beach.jpg", "Beach"), GalleryImage::new("/forest.jpg", "Forest")]
+///                 width=300
+///                 height=300
+///                 fit=ResizeMode::Cover
+///                 blur=true
+///             />
+///         </div>
+///     }
+/// }
+/// ```
+#[component]
+pub fn ImageGallery<I, T>(
+    /// The images, [`GalleryImage`]s or plain sources.
+    images: I,
+    /// Resize image height of every image, the aspect ratio is maintained.
+    height: u32,
+    /// Resize image width of every image, the aspect ratio is maintained.
+    width: u32,
+    /// How the images fit the `width` x `height` box. Defaults to [`crate::ResizeMode::Contain`].
+    #[prop(optional)]
+    fit: crate::ResizeMode,
+    /// Image quality. 0-100.
+    /// Defaults to the quality configured on the optimizer, 75 unless changed.
+    #[prop(optional)]
+    quality: Option<u8>,
+    /// Renders blur placeholders, see [`crate::Image`].
+    #[prop(default = false)]
+    blur: bool,
+    /// Lazy load the images.
+    #[prop(default = true)]
+    lazy: bool,
+    /// Only sets the source of each image once it scrolls near the viewport, see [`crate::Image`].
+    #[prop(default = false)]
+    visible_only: bool,
+    /// The `sizes` attribute of the images, either a string or a [`crate::Layout`] to infer it from.
+    #[prop(into, optional)]
+    sizes: Option<crate::Sizes>,
+    /// Style class of every `<img>`.
+    #[prop(into, optional)]
+    class: Option<String>,
+) -> impl IntoView
+where
+    I: IntoIterator<Item = T>,
+    T: Into<GalleryImage>,
+{
+    let images = images
+        .into_iter()
+        .map(Into::into)
+        .collect::<Vec<GalleryImage>>();
+    // The images render once the config has loaded, too late to add their placeholders
+    // and source dimensions to the page.
+    let tenant = crate::provider::use_image_tenant();
+    for image in &images {
+        crate::provider::request_source_dimensions(image.src.as_str(), tenant.as_deref());
+    }
+    #[cfg(feature = "blur-placeholder")]
+    if blur {
+        for image in &images {
+            let blur_image = crate::CachedImage::blur(image.src.as_str()).scoped(tenant.as_deref());
+            crate::provider::request_placeholder(blur_image);
+        }
+    }
+    let images = store_value(images);
+    let sizes = store_value(sizes);
+    let class = store_value(class);
+    let resource = crate::use_image_cache_resource();
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                resource
+                    .get()
+                    .map(|config| {
+                        let quality = quality.unwrap_or(config.default_quality);
+                        // Read by every `<Image/>` below instead of the resource.
+                        provide_context(SharedImageConfig::new(config));
+                        images
+                            .get_value()
+                            .into_iter()
+                            .map(|GalleryImage { src, alt }| match sizes.get_value() {
+                                Some(sizes) => {
+                                    view! {
+                                        <crate::Image
+                                            src
+                                            alt
+                                            width
+                                            height
+                                            fit
+                                            quality
+                                            blur
+                                            lazy
+                                            visible_only
+                                            sizes
+                                            class=class.get_value()
+                                        />
+                                    }
+                                }
+                                None => {
+                                    view! {
+                                        <crate::Image
+                                            src
+                                            alt
+                                            width
+                                            height
+                                            fit
+                                            quality
+                                            blur
+                                            lazy
+                                            visible_only
+                                            class=class.get_value()
+                                        />
+                                    }
+                                }
+                            })
+                            .collect_view()
+                    })
+            }}
+
+        </Suspense>
+    }
+}
@@ -1,175 +1,1089 @@
 use crate::optimizer::*;
+use crate::ImageSet;
 
 use leptos::*;
 use leptos_meta::Link;
 
-/**
- */
+/// The source of an image rendered by [`Image`].
+///
+/// Plain strings convert into the matching variant, so `src="/hero.png"` keeps working.
+/// Construct a variant directly to make the intended behavior explicit.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageSrc {
+    /// Path to a static image, relative to the site root. Optimized and cached.
+    Static(String),
+    /// Absolute `http(s)` URL of a remote image.
+    /// Optimized if its domain is in `OptimizerConfig::remote_domains`, rendered as-is otherwise.
+    Remote(String),
+    /// Inline `data:` URL. Passed through untouched.
+    DataUrl(String),
+    /// A responsive [`ImageSet`]. Every width is optimized and emitted in the `srcset`.
+    Responsive(ImageSet),
+}
+
+impl ImageSrc {
+    /// Returns the underlying path or URL.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ImageSrc::Static(s) | ImageSrc::Remote(s) | ImageSrc::DataUrl(s) => s,
+            ImageSrc::Responsive(set) => set.src(),
+        }
+    }
+}
+
+impl From<String> for ImageSrc {
+    fn from(src: String) -> Self {
+        if src.starts_with("data:") {
+            ImageSrc::DataUrl(src)
+        } else if src.starts_with("http://") || src.starts_with("https://") {
+            ImageSrc::Remote(src)
+        } else {
+            ImageSrc::Static(src)
+        }
+    }
+}
+
+impl From<&str> for ImageSrc {
+    fn from(src: &str) -> Self {
+        ImageSrc::from(src.to_string())
+    }
+}
+
+impl From<ImageSet> for ImageSrc {
+    fn from(set: ImageSet) -> Self {
+        ImageSrc::Responsive(set)
+    }
+}
+
+impl std::fmt::Display for ImageSrc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Hint for how early the browser fetches an image, the `fetchpriority` attribute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FetchPriority {
+    /// Fetch before other images, e.g. for the Largest Contentful Paint image.
+    High,
+    /// Fetch after other images, e.g. for images in a carousel that aren't visible yet.
+    Low,
+    /// Let the browser decide.
+    #[default]
+    Auto,
+}
+
+impl FetchPriority {
+    /// The attribute value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FetchPriority::High => "high",
+            FetchPriority::Low => "low",
+            FetchPriority::Auto => "auto",
+        }
+    }
+}
+
+/// How the browser decodes an image relative to rendering other content, the `decoding` attribute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Decoding {
+    /// Decode before presenting other content, so the image appears with it.
+    Sync,
+    /// Present other content first, and the image once it is decoded.
+    #[default]
+    Async,
+    /// Let the browser decide.
+    Auto,
+}
+
+impl Decoding {
+    /// The attribute value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decoding::Sync => "sync",
+            Decoding::Async => "async",
+            Decoding::Auto => "auto",
+        }
+    }
+}
 
 /// Image component for rendering optimized static images.
-/// Images MUST be static. Will not work with dynamic images.
+/// Static sources, and remote sources from allowed domains, are optimized. Other sources, and SVGs, are rendered as-is.
 #[component]
 pub fn Image(
-    /// Image source. Should be path relative to root.
+    /// Image source. Static sources should be a path relative to root.
     #[prop(into)]
-    src: String,
+    src: ImageSrc,
     /// Resize image height, but will still maintain the same aspect ratio.
     height: u32,
     /// Resize image width, but will still maintain the same aspect ratio.
     width: u32,
+    /// How the image fits the `width` x `height` box. Defaults to [`crate::ResizeMode::Contain`],
+    /// use [`crate::ResizeMode::Cover`] for exact-dimension thumbnails, and [`crate::ResizeMode::Focus`]
+    /// to keep the subject of a cropped photo in view.
+    #[prop(optional)]
+    fit: crate::ResizeMode,
+    /// Fills the parent element instead of rendering at a fixed size, for images sized by their container like heroes.
+    ///
+    /// The image is absolutely positioned over its parent, which must be positioned (e.g. `position:relative`),
+    /// and covers it following `fit`. No `width` and `height` attributes are rendered, they only set the aspect ratio
+    /// of the generated variants. Without `widths` or an [`crate::ImageSet`], a default set of widths is generated with `sizes="100vw"`.
+    #[prop(default = false)]
+    fill: bool,
+    /// Filter used to resize the image, trading speed for sharpness.
+    /// Defaults to the filter configured on the optimizer, [`crate::ResizeFilter::CatmullRom`] unless changed.
+    #[prop(optional)]
+    filter: Option<crate::ResizeFilter>,
+    /// Sharpens the image after resizing, in percent. Strong downscales look soft, 30 to 80 brings back detail.
+    /// Defaults to the amount configured on the optimizer, which doesn't sharpen unless changed.
+    #[prop(optional)]
+    sharpen: Option<u8>,
+    /// Keeps the EXIF metadata and ICC color profile of the source in the optimized image.
+    /// Defaults to the setting of the optimizer, which strips them unless changed.
+    /// Only applies once the server rendered the image, see [`crate::CachedImage::with_preserve_metadata`].
+    #[prop(optional)]
+    preserve_metadata: Option<bool>,
+    /// Serves the source's resolution instead of scaling it up when `width` and `height` are larger.
+    /// Defaults to the setting of the optimizer, which scales up unless changed.
+    #[prop(optional)]
+    no_upscale: Option<bool>,
+    /// Encodes the image losslessly, ignoring `quality`.
+    /// For diagrams, logos and screenshots, which look poor with lossy compression at any quality.
+    #[prop(optional)]
+    lossless: bool,
+    /// Draws the watermark configured on the optimizer. Set to `false` to opt the image out.
+    /// Only applies once the server rendered the image, see [`crate::CachedImage::with_watermark`].
+    #[prop(default = true)]
+    watermark: bool,
+    /// Rotates, flips or adjusts the colors of the source before resizing, in order, at most [`crate::MAX_TRANSFORMS`].
+    #[prop(optional)]
+    transforms: Vec<crate::Transform>,
     /// Image quality. 0-100.
-    #[prop(default = 75_u8)]
-    quality: u8,
+    /// Defaults to the quality configured on the optimizer, 75 unless changed.
+    #[prop(optional)]
+    quality: Option<u8>,
     /// Will add blur image to head if true.
+    /// The placeholder is generated in the background the first time the image is rendered,
+    /// until then the image renders without one. Images rendered after the page, e.g. inside a `<Suspense/>`,
+    /// only get a placeholder from the server render, see [`crate::provide_image_context`].
     #[prop(default = false)]
     blur: bool,
     /// Will add preload link to head if true.
+    /// Also sets `fetchpriority="high"`. Beyond the page's preload budget, only `fetchpriority` is set.
     #[prop(default = false)]
     priority: bool,
+    /// The `fetchpriority` attribute of the `<img>`. Defaults to `high` with `priority`, and no attribute otherwise.
+    #[prop(optional)]
+    fetchpriority: Option<FetchPriority>,
+    /// The `decoding` attribute of the `<img>`. Defaults to [`Decoding::Async`].
+    #[prop(optional)]
+    decoding: Decoding,
     /// Lazy load image.
     #[prop(default = true)]
     lazy: bool,
-    /// Image alt text.
+    /// Only sets the source of the image once it scrolls near the viewport, with an `IntersectionObserver`.
+    /// For very long galleries, where even lazy loading hundreds of images is costly.
+    /// Images rendered on the server keep their source and load with `loading="lazy"`.
+    #[prop(default = false)]
+    visible_only: bool,
+    /// Image alt text. Accepts a signal so translations can update it in place.
     #[prop(into, optional)]
-    alt: String,
+    alt: MaybeSignal<String>,
+    /// Image title text. Accepts a signal so translations can update it in place.
+    #[prop(into, optional)]
+    title: MaybeProp<String>,
     /// Style class for image.
     #[prop(into, optional)]
     class: Option<AttributeValue>,
+    /// Inline style of the image, appended to the generated placeholder and `fill` styles so it takes precedence.
+    #[prop(into, optional)]
+    style: Option<String>,
+    /// Tenant the image belongs to. Defaults to the tenant provided with [`crate::provide_image_tenant`].
+    #[prop(into, optional)]
+    tenant: Option<String>,
+    /// The `sizes` attribute of responsive images, either a string or a [`crate::Layout`] to infer it from.
+    /// Overrides the sizes of the [`crate::ImageSet`].
+    #[prop(into, optional)]
+    sizes: Option<crate::Sizes>,
+    /// Widths to generate for a static source, emitted in the `srcset` so browsers pick the smallest fitting variant.
+    /// Shorthand for an [`crate::ImageSet`] with these widths, e.g. `widths=vec![480, 960, 1920]`.
+    /// Ignored when `src` is an [`crate::ImageSet`], which lists its own widths.
+    #[prop(into, optional)]
+    widths: Option<Vec<u32>>,
+    /// Pixel densities generated for high density screens, emitted in the `srcset` with `1x`, `2x` descriptors.
+    /// Each variant multiplies `width` and `height`, and lowers the quality following `OptimizerConfig::dpr_quality`.
+    /// Defaults to `[1, 2]`. Ignored with `widths`, `fill` or an [`crate::ImageSet`], whose `srcset` lists widths instead.
+    #[prop(into, optional)]
+    densities: Option<Vec<u32>>,
+    /// Overrides the `Cache-Control` header the optimized image is served with,
+    /// e.g. `"public, max-age=300"` for an image that is replaced often.
+    #[prop(into, optional)]
+    cache_control: Option<String>,
+    /// Loads a tiny version of the image first, then swaps to the full size image once it has loaded.
+    /// Shows real image content almost immediately on slow connections.
+    #[prop(default = false)]
+    progressive: bool,
+    /// When the visitor prefers reduced data, only a tiny low-res image is rendered,
+    /// along with a button to load the full image.
+    /// Honors the `Sec-CH-Prefers-Reduced-Data` and `Save-Data` headers of the page request, read on the server
+    /// so hydration renders the same image. Browsers only send the client hint after your server responds with
+    /// `Accept-CH: Sec-CH-Prefers-Reduced-Data`.
+    #[prop(default = false)]
+    reduced_data: bool,
+    /// View rendered until the optimized image has finished loading, e.g. a spinner.
+    /// It is rendered right before the `<img>`, so position it as needed.
+    #[prop(into, optional)]
+    placeholder_view: Option<ViewFn>,
+    /// Called once the optimized image has loaded, e.g. to fade it in.
+    /// In progressive mode, only once the full size image has loaded.
+    #[prop(into, optional)]
+    on_load: Option<Callback<()>>,
+    /// Called when the optimized image fails to load, e.g. to swap to a fallback asset.
+    #[prop(into, optional)]
+    on_error: Option<Callback<ev::ErrorEvent>>,
+    /// Extra attributes set on the `<img>`, e.g. `attr:id="hero"`, `attr:data-index=3`,
+    /// `attr:referrerpolicy="no-referrer"`, `attr:crossorigin="anonymous"` or `attr:aria-describedby="caption"`.
+    #[prop(attrs)]
+    attributes: Vec<(&'static str, Attribute)>,
 ) -> impl IntoView {
-    if src.starts_with("http") {
-        logging::debug_warn!("Image component only supports static images.");
+    // Client side rendered apps have no server to optimize the image, so it is rendered from its source.
+    if cfg!(feature = "csr") {
         let loading = if lazy { "lazy" } else { "eager" };
-        return view! { <img src=src alt=alt class=class loading=loading/> }.into_view();
+        let fetchpriority = fetchpriority
+            .or(priority.then_some(FetchPriority::High))
+            .map(|priority| priority.as_str());
+        return view! {
+            <img
+                {..attributes}
+                src=src.to_string()
+                alt=alt
+                title=title
+                class=class
+                width=(!fill).then_some(width)
+                height=(!fill).then_some(height)
+                style=merge_styles(fill.then(|| fill_style(fit)), style)
+                decoding=decoding.as_str()
+                loading=loading
+                fetchpriority=fetchpriority
+                on:load=move |_| {
+                    if let Some(on_load) = on_load {
+                        on_load.call(());
+                    }
+                }
+                on:error=move |event| {
+                    if let Some(on_error) = on_error {
+                        on_error.call(event);
+                    }
+                }
+            />
+        }
+        .into_view();
     }
 
-    let blur_image = {
-        CachedImage {
-            src: src.clone(),
-            option: CachedImageOption::Blur(Blur {
-                width: 20,
-                height: 20,
-                svg_width: 100,
-                svg_height: 100,
-                sigma: 15,
-            }),
+    // SVGs are vector images, they are rendered as-is instead of rasterized.
+    if matches!(&src, ImageSrc::Static(path) | ImageSrc::Remote(path) if is_svg(path)) {
+        let loading = if lazy { "lazy" } else { "eager" };
+        let resource = crate::use_image_cache_resource();
+        let src = store_value(src.to_string());
+        let attributes = store_value(attributes);
+        let alt = store_value(alt);
+        let title = store_value(title);
+        let class = store_value(class.map(|c| c.into_attribute_boxed()));
+        let style = store_value(merge_styles(fill.then(|| fill_style(fit)), style));
+        let svg = move |config: crate::provider::ImageConfig| {
+            view! {
+                <img
+                    {..attributes.get_value()}
+                    src=src.with_value(|src| config.static_url(src))
+                    alt=alt.get_value()
+                    title=title.get_value()
+                    class=class.get_value()
+                    width=(!fill).then_some(width)
+                    height=(!fill).then_some(height)
+                    style=style.get_value()
+                    loading=loading
+                />
+            }
+        };
+        return view! { <Suspense fallback=|| ()>{move || resource.get().map(svg)}</Suspense> }
+            .into_view();
+    }
+
+    let (src, image_set, remote) = match src {
+        ImageSrc::Static(path) => match widths {
+            Some(widths) if !widths.is_empty() => {
+                (path.clone(), Some(ImageSet::new(path, widths)), false)
+            }
+            _ if fill => (
+                path.clone(),
+                Some(ImageSet::new(path, FILL_WIDTHS).sizes("100vw")),
+                false,
+            ),
+            _ => (path, None, false),
+        },
+        ImageSrc::Responsive(set) => {
+            if widths.is_some() {
+                logging::debug_warn!(
+                    "The `widths` of <Image/> are ignored for the image set of {}, set them on the set.",
+                    set.src
+                );
+            }
+            (set.src.clone(), Some(set), false)
+        }
+        // Optimized like static sources if the domain is allowed, see `OptimizerConfig::remote_domains`.
+        ImageSrc::Remote(url) => (url, None, true),
+        ImageSrc::DataUrl(url) => {
+            let loading = if lazy { "lazy" } else { "eager" };
+            return view! {
+                <img
+                    {..attributes}
+                    src=url
+                    alt=alt
+                    title=title
+                    class=class
+                    style=style
+                    loading=loading
+                />
+            }
+            .into_view();
         }
     };
 
-    let opt_image = {
-        CachedImage {
-            src: src.clone(),
-            option: CachedImageOption::Resize(Resize {
-                quality,
-                width,
-                height,
-            }),
+    let tenant = tenant.or_else(crate::provider::use_image_tenant);
+    // Without the image context, the image degrades to rendering without a placeholder.
+    #[cfg(feature = "blur-placeholder")]
+    let blur = blur && crate::provider::has_image_context();
+    #[cfg(feature = "blur-placeholder")]
+    let blur_image = CachedImage::blur(src.clone()).scoped(tenant.as_deref());
+    #[cfg(feature = "blur-placeholder")]
+    if blur {
+        crate::provider::request_placeholder(blur_image.clone());
+    }
+    // The dimensions of the source size the image, see `ImageConfig::fitted_dimensions`.
+    if !fill && (fit == ResizeMode::Contain || no_upscale != Some(false)) {
+        crate::provider::request_source_dimensions(&src, tenant.as_deref());
+    }
+
+    #[cfg(not(feature = "blur-placeholder"))]
+    let blur = {
+        if blur {
+            logging::debug_warn!("Blur placeholders require the `blur-placeholder` feature.");
         }
+        false
     };
 
+    // Set once the visitor asks for the full image, when data is reduced.
+    let load_full = create_rw_signal(false);
+
+    let preload = priority && crate::provider::claim_preload(&src);
+    let fetchpriority = fetchpriority
+        .or(priority.then_some(FetchPriority::High))
+        .map(|priority| priority.as_str());
+    let decoding = decoding.as_str();
+
     // Retrieve value from Cache if it exists. Doing this per-image to allow image introspection.
     let resource = crate::use_image_cache_resource();
 
+    #[cfg(feature = "blur-placeholder")]
     let blur_image = store_value(blur_image);
-    let opt_image = store_value(opt_image);
+    let src = store_value(src);
+    let image_set = store_value(image_set);
+    let tenant = store_value(tenant);
+    let cache_control = store_value(cache_control);
+    let transforms = store_value(transforms);
+    let densities = store_value(densities.unwrap_or_else(|| vec![1, 2]));
+    let sizes = store_value(sizes.map(|sizes| sizes.0));
+    // Applies the per-image options that every generated variant shares.
+    let scope = move |image: CachedImage| {
+        let image = tenant.with_value(|tenant| image.scoped(tenant.as_deref()).with_fit(fit));
+        let image = match filter {
+            Some(filter) => image.with_filter(filter),
+            None => image,
+        };
+        let image = match sharpen {
+            Some(amount) => image.with_sharpen(amount),
+            None => image,
+        };
+        let image = match preserve_metadata {
+            Some(preserve) => image.with_preserve_metadata(preserve),
+            None => image,
+        };
+        let image = match no_upscale {
+            Some(enabled) => image.with_no_upscale(enabled),
+            None => image,
+        };
+        let image = image.with_lossless(lossless);
+        let image = match watermark {
+            true => image,
+            false => image.with_watermark(false),
+        };
+        let image = transforms.with_value(|transforms| image.with_transforms(transforms.clone()));
+        match cache_control.get_value() {
+            Some(cache_control) => image.with_cache_control(cache_control),
+            None => image,
+        }
+    };
     let alt = store_value(alt);
+    let title = store_value(title);
     let class = store_value(class.map(|c| c.into_attribute_boxed()));
+    let attributes = store_value(attributes);
+    let user_style = store_value(style);
 
-    view! {
-        <Suspense fallback=|| ()>
-            {move || {
-                resource
-                    .get()
-                    .map(|config| {
-                        let images = config.cache;
-                        let handler_path = config.api_handler_path;
-                        let opt_image = opt_image.get_value().get_url_encoded(&handler_path);
-                        if blur {
-                            let placeholder_svg = images
-                                .iter()
-                                .find(|(c, _)| blur_image.with_value(|b| b == c))
-                                .map(|c| c.1.clone());
-                            let svg = {
-                                if let Some(svg_data) = placeholder_svg {
-                                    SvgImage::InMemory(svg_data)
-                                } else {
-                                    SvgImage::Request(
-                                        blur_image.get_value().get_url_encoded(&handler_path),
-                                    )
-                                }
-                            };
-                            let class = class.get_value();
-                            let alt = alt.get_value();
-                            view! { <CacheImage lazy svg opt_image alt class=class priority/> }
-                                .into_view()
-                        } else {
-                            let loading = if lazy { "lazy" } else { "eager" };
-                            view! {
-                                <img
-                                    alt=alt.get_value()
-                                    class=class.get_value()
-                                    decoding="async"
-                                    loading=loading
-                                    src=opt_image
-                                />
-                            }
-                                .into_view()
-                        }
+    // Images rendered in the browser wait for the observer, the others load lazily, see `visible_only`.
+    let lazy = lazy || visible_only;
+    let visible = create_rw_signal(
+        !visible_only || cfg!(feature = "ssr") || leptos_dom::HydrationCtx::is_hydrating(),
+    );
+    let loaded = create_rw_signal(false);
+    // Whether the full size image has loaded, not only the preview of progressive mode.
+    let loaded_full = create_rw_signal(false);
+    // Progressive mode, also used to swap in the image over a `Placeholder::CssBlur`.
+    let swaps_preview = store_value(progressive);
+    let node_ref = create_node_ref::<html::Img>();
+    let user_on_load = on_load;
+    let on_load = Callback::new(move |_| {
+        // In progressive mode, the first load is the tiny preview.
+        let full = !swaps_preview.get_value() || loaded.get_untracked();
+        loaded.set(true);
+        if full {
+            loaded_full.set(true);
+        }
+        if let Some(on_load) = user_on_load.filter(|_| full) {
+            on_load.call(());
+        }
+    });
+    let on_error = Callback::new(move |event| {
+        if let Some(on_error) = on_error {
+            on_error.call(event);
+        }
+    });
+    // The image may finish loading before hydration attaches the load listener.
+    create_effect(move |_| {
+        if let Some(img) = node_ref.get().filter(|img| img.complete()) {
+            // A broken image is complete too, but has no size.
+            if img.natural_width() > 0 {
+                on_load.call(());
+            } else {
+                loaded.set(true);
+            }
+        }
+    });
+    #[cfg(feature = "hydrate")]
+    observe_visibility(node_ref, visible);
+    let placeholder = move || {
+        placeholder_view
+            .as_ref()
+            .filter(|_| !loaded.get())
+            .map(|view| view.run())
+    };
+
+    let render = move |config: &crate::provider::ImageConfig| {
+        if remote && !src.with_value(|src| config.optimizes_remote(src)) {
+            let loading = if lazy { "lazy" } else { "eager" };
+            return view! {
+                <img
+                    {..attributes.get_value()}
+                    src=src.get_value()
+                    alt=alt.get_value()
+                    title=title.get_value()
+                    class=class.get_value()
+                    style=user_style.get_value()
+                    loading=loading
+                />
+            }
+            .into_view();
+        }
+        let quality = quality.unwrap_or(config.default_quality);
+        let no_upscale = no_upscale.unwrap_or(config.no_upscale);
+        // Lets the browser reserve the space of the image before it loads.
+        let (img_width, img_height) = if fill {
+            (None, None)
+        } else {
+            let (img_width, img_height) = tenant.with_value(|tenant| {
+                let tenant = tenant.as_deref();
+                src.with_value(|src| {
+                    transforms.with_value(|transforms| {
+                        config.fitted_dimensions(
+                            src,
+                            tenant,
+                            (width, height),
+                            fit,
+                            transforms,
+                            no_upscale,
+                        )
                     })
-            }}
+                })
+            });
+            (Some(img_width), Some(img_height))
+        };
+        let style = merge_styles(fill.then(|| fill_style(fit)), user_style.get_value());
+        #[cfg(feature = "blur-placeholder")]
+        let svg = blur
+            .then(|| cached_placeholder(config, blur_image.get_value()))
+            .flatten();
+        #[cfg(not(feature = "blur-placeholder"))]
+        let svg = None;
+        // A CSS blur placeholder is the first source of the `<img>`, like the preview of progressive mode.
+        let css_blur = css_blur_uri(&svg);
+        let progressive = progressive || css_blur.is_some();
+        swaps_preview.set_value(progressive);
+        let variant =
+            move |image: CachedImage, format: OutputFormat| scope(image).with_format(format);
+        // The `<img>` uses the last format, the preferred ones become `<source>`s.
+        let fallback = config.formats.last().copied().unwrap_or_default();
+        let preferred = match config.formats.split_last() {
+            // Progressive mode swaps the `<img>` source, which a `<source>` would override.
+            Some((_, preferred)) if !progressive => preferred.to_vec(),
+            _ => Vec::new(),
+        };
+        let scope = move |image: CachedImage| variant(image, fallback);
+        let opt_image = scope(CachedImage::resize(src.get_value(), width, height, quality));
+        let opt_image = config.url(&opt_image);
+        let format_srcset = |format: OutputFormat| {
+            let scope = move |image: CachedImage| variant(image, format);
+            image_set.with_value(|set| match set {
+                Some(set) => Some(set.srcset(width, height, quality, scope, config)),
+                None => densities.with_value(|densities| {
+                    density_srcset(
+                        src.get_value(),
+                        (width, height),
+                        quality,
+                        densities,
+                        scope,
+                        config,
+                    )
+                }),
+            })
+        };
+        let srcset = format_srcset(fallback);
+        let sizes = sizes
+            .get_value()
+            .or_else(|| image_set.with_value(|set| set.as_ref().and_then(|set| set.sizes.clone())));
+        let sources = preferred
+            .iter()
+            .map(|&format| {
+                let srcset = format_srcset(format).unwrap_or_else(|| {
+                    config.url(&variant(
+                        CachedImage::resize(src.get_value(), width, height, quality),
+                        format,
+                    ))
+                });
+                view! {
+                    <source
+                        type=format.mime_type()
+                        srcset=move || visible.get().then(|| srcset.clone())
+                        sizes=sizes.clone()
+                    />
+                }
+            })
+            .collect_view();
+        // Preload the format the browser is most likely to pick, with its candidates so the
+        // browser preloads the one matching `sizes`.
+        let (preload_href, preload_type, preload_srcset) = match preferred.first() {
+            Some(&format) => {
+                let image = variant(
+                    CachedImage::resize(src.get_value(), width, height, quality),
+                    format,
+                );
+                (
+                    config.url(&image),
+                    Some(format.mime_type()),
+                    format_srcset(format),
+                )
+            }
+            None => (opt_image.clone(), None, srcset.clone()),
+        };
+        let small = move || {
+            scope(CachedImage::resize(
+                src.get_value(),
+                PROGRESSIVE_WIDTH,
+                crate::image_set::scale_height(PROGRESSIVE_WIDTH, width, height),
+                quality,
+            ))
+        };
+        // Decided from the page request on the server, the browser can't tell.
+        let reduce_data = reduced_data && config.reduced_data;
+        if reduce_data && !load_full.get() {
+            return view! {
+                <img
+                    {..attributes.get_value()}
+                    alt=alt.get_value()
+                    title=title.get_value()
+                    class=class.get_value()
+                    src=config.url(&small())
+                    width=img_width
+                    height=img_height
+                    style=style
+                />
+                <button
+                    type="button"
+                    class="leptos-image-load"
+                    on:click=move |_| load_full.set(true)
+                >
+                    "Load image"
+                </button>
+            }
+            .into_view();
+        }
+        let (img_src, srcset) = if progressive {
+            let small = css_blur.clone().unwrap_or_else(|| config.url(&small()));
+            let full = opt_image.clone();
+            (
+                MaybeSignal::derive(move || {
+                    if loaded.get() {
+                        full.clone()
+                    } else {
+                        small.clone()
+                    }
+                }),
+                MaybeSignal::derive(move || srcset.clone().filter(|_| loaded.get())),
+            )
+        } else {
+            (
+                MaybeSignal::Static(opt_image.clone()),
+                MaybeSignal::Static(srcset),
+            )
+        };
+        let img_src = MaybeSignal::derive(move || visible.get().then(|| img_src.get()));
+        let srcset = MaybeSignal::derive(move || srcset.get().filter(|_| visible.get()));
+        let preload_link = (preload && !reduce_data)
+            .then(|| preload_link(preload_href, preload_type, preload_srcset, sizes.clone()));
+        let image = if blur {
+            let style = match css_blur {
+                Some(_) => MaybeSignal::derive(move || {
+                    let filter = (!loaded_full.get()).then(|| CSS_BLUR_STYLE.to_string());
+                    merge_styles(filter, style.clone())
+                }),
+                None => MaybeSignal::Static(style),
+            };
+            let class = class.get_value();
+            let alt = alt.get_value();
+            let title = title.get_value();
+            view! {
+                <CacheImage
+                    lazy
+                    svg
+                    src=img_src
+                    srcset
+                    sizes
+                    alt
+                    title
+                    class=class
+                    width=img_width
+                    height=img_height
+                    style
+                    fetchpriority
+                    decoding
+                    node_ref
+                    on_load
+                    on_error
+                    attributes=attributes.get_value()
+                />
+            }
+            .into_view()
+        } else {
+            let loading = if lazy { "lazy" } else { "eager" };
+            view! {
+                <img
+                    {..attributes.get_value()}
+                    alt=alt.get_value()
+                    title=title.get_value()
+                    class=class.get_value()
+                    width=img_width
+                    height=img_height
+                    style=style
+                    decoding=decoding
+                    fetchpriority=fetchpriority
+                    loading=loading
+                    src=img_src
+                    srcset=srcset
+                    sizes=sizes
+                    node_ref=node_ref
+                    on:load=move |_| on_load.call(())
+                    on:error=move |event| on_error.call(event)
+                />
+            }
+            .into_view()
+        };
+        let image = if preferred.is_empty() {
+            image
+        } else {
+            view! { <picture>{sources} {image}</picture> }.into_view()
+        };
+        view! { {preload_link} {image} }.into_view()
+    };
+
+    view! {
+        {placeholder}
+        {match use_context::<crate::provider::SharedImageConfig>() {
+            // Images of an `ImageGallery` render with the config it resolved for all of them.
+            Some(shared) => (move || shared.config.with_value(render)).into_view(),
+            None => {
+                view! {
+                    <Suspense fallback=|| ()>
+                        {move || resource.with(render)}
+                    </Suspense>
+                }
+                    .into_view()
+            }
+        }}
+    }
+    .into_view()
+}
+
+// Sets `visible` once the image is near the viewport. Without `IntersectionObserver` support,
+// right away, so the image falls back to `loading="lazy"`.
+#[cfg(feature = "hydrate")]
+fn observe_visibility(node_ref: NodeRef<html::Img>, visible: RwSignal<bool>) {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    create_effect(move |_| {
+        let Some(img) = node_ref.get() else {
+            return;
+        };
+        if visible.get_untracked() {
+            return;
+        }
+        let callback =
+            Closure::<dyn FnMut(web_sys::js_sys::Array, web_sys::IntersectionObserver)>::new(
+                move |entries: web_sys::js_sys::Array, observer: web_sys::IntersectionObserver| {
+                    let near = entries.iter().any(|entry| {
+                        entry
+                            .unchecked_into::<web_sys::IntersectionObserverEntry>()
+                            .is_intersecting()
+                    });
+                    if near {
+                        visible.set(true);
+                        observer.disconnect();
+                    }
+                },
+            );
+        let options = web_sys::IntersectionObserverInit::new();
+        options.set_root_margin(VISIBLE_MARGIN);
+        match web_sys::IntersectionObserver::new_with_options(
+            callback.as_ref().unchecked_ref(),
+            &options,
+        ) {
+            Ok(observer) => {
+                observer.observe(&img);
+                on_cleanup(move || {
+                    observer.disconnect();
+                    drop(callback);
+                });
+            }
+            Err(_) => visible.set(true),
+        }
+    });
+}
+
+// How far from the viewport `visible_only` images start loading.
+#[cfg(feature = "hydrate")]
+const VISIBLE_MARGIN: &str = "200px";
+
+// Whether the visitor asked to save data, from the headers of the request being rendered.
+#[cfg(feature = "ssr")]
+pub(crate) fn prefers_reduced_data() -> bool {
+    #[cfg(feature = "axum-handler")]
+    {
+        use_context::<axum::http::request::Parts>().is_some_and(|parts| {
+            let header = |name: &str| {
+                parts
+                    .headers
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.trim().to_ascii_lowercase())
+            };
+            header("sec-ch-prefers-reduced-data").as_deref() == Some("reduce")
+                || header("save-data").as_deref() == Some("on")
+        })
+    }
+    #[cfg(not(feature = "axum-handler"))]
+    {
+        false
+    }
+}
+
+// A layer of a CSS background, as its image, size and position.
+pub(crate) type BackgroundLayer = (String, String, String);
+
+// Style drawing the layers, the first one on top.
+pub(crate) fn background_style(layers: &[BackgroundLayer]) -> String {
+    let join =
+        |part: fn(&BackgroundLayer) -> &str| layers.iter().map(part).collect::<Vec<_>>().join(",");
+    format!(
+        "background-repeat:no-repeat;background-size:{};background-position:{};background-image:{};",
+        join(|layer| &layer.1),
+        join(|layer| &layer.2),
+        join(|layer| &layer.0),
+    )
+}
+
+// Background layers drawing a placeholder, empty if it can't be drawn.
+pub(crate) fn placeholder_layers(svg: SvgImage) -> Vec<BackgroundLayer> {
+    match svg {
+        #[cfg(feature = "blur-placeholder")]
+        SvgImage::InMemory(svg_data) => {
+            use base64::{engine::general_purpose, Engine as _};
+            let svg_encoded = general_purpose::STANDARD.encode(svg_data.as_bytes());
+            vec![(
+                format!("url('data:image/svg+xml;base64,{svg_encoded}')"),
+                "cover".to_string(),
+                "50% 50%".to_string(),
+            )]
+        }
+        #[cfg(feature = "blur-placeholder")]
+        SvgImage::BlurHash(hash) => blurhash_layers(&hash),
+        #[cfg(feature = "blur-placeholder")]
+        SvgImage::CssBlur(uri) => vec![(
+            format!("url('{uri}')"),
+            "cover".to_string(),
+            "50% 50%".to_string(),
+        )],
+    }
+}
+
+// Data URI of a `Placeholder::CssBlur`, which `<Image/>` renders as the first source of the `<img>`.
+fn css_blur_uri(svg: &Option<SvgImage>) -> Option<String> {
+    match svg {
+        #[cfg(feature = "blur-placeholder")]
+        Some(SvgImage::CssBlur(uri)) => Some(uri.clone()),
+        _ => None,
+    }
+}
+
+// Blurs the preview of a `Placeholder::CssBlur`, scaled up so its blurred edges don't show.
+const CSS_BLUR_STYLE: &str = "filter:blur(20px);transform:scale(1.1)";
 
-        </Suspense>
+// The generated placeholder of an image, if any.
+// Generating a blur blocks on decoding the full source image,
+// so a cold placeholder is created off the request path for later renders.
+#[cfg(feature = "blur-placeholder")]
+pub(crate) fn cached_placeholder(
+    config: &crate::provider::ImageConfig,
+    blur_image: CachedImage,
+) -> Option<SvgImage> {
+    let blur_image = blur_image.with_placeholder(config.placeholder);
+    // On the server the optimizer is read directly, so images rendered after the page's placeholders
+    // were collected still inline theirs. Only a style changes, which hydration leaves as rendered.
+    // A CSS blur also changes the rendered elements, so it must come from the config the client has too.
+    #[cfg(feature = "ssr")]
+    if config.placeholder != Placeholder::CssBlur {
+        if let Some(optimizer) = use_context::<crate::ImageOptimizer>() {
+            let cached = optimizer.cache.get(&blur_image).map(|value| value.clone());
+            if cached.is_none() {
+                optimizer.enqueue_blur(blur_image);
+            }
+            return cached.map(|value| match config.placeholder {
+                Placeholder::BlurHash => SvgImage::BlurHash(value),
+                _ => SvgImage::InMemory(value),
+            });
+        }
+    }
+    let cached = match use_context::<crate::provider::SharedImageConfig>() {
+        Some(shared) => shared
+            .cache
+            .with_value(|cache| cache.get(&blur_image).cloned()),
+        None => config
+            .cache
+            .iter()
+            .find(|(c, _)| *c == blur_image)
+            .map(|(_, value)| value.clone()),
+    };
+    let svg = cached.map(|value| match config.placeholder {
+        Placeholder::Svg => SvgImage::InMemory(value),
+        Placeholder::BlurHash => SvgImage::BlurHash(value),
+        Placeholder::CssBlur => SvgImage::CssBlur(value),
+    });
+    #[cfg(feature = "ssr")]
+    if svg.is_none() {
+        if let Some(optimizer) = use_context::<crate::ImageOptimizer>() {
+            optimizer.enqueue_blur(blur_image);
+        }
+    }
+    svg
+}
+
+// Decodes a BlurHash into a 4x3 grid, drawn as one horizontal gradient per row.
+#[cfg(feature = "blurhash")]
+fn blurhash_layers(hash: &str) -> Vec<BackgroundLayer> {
+    const COLUMNS: usize = 4;
+    const ROWS: usize = 3;
+    let Ok(pixels) = blurhash::decode(hash, COLUMNS as u32, ROWS as u32, 1.0) else {
+        return Vec::new();
+    };
+    pixels
+        .chunks_exact(COLUMNS * 4)
+        .enumerate()
+        .map(|(row, pixels)| {
+            let colors = pixels
+                .chunks_exact(4)
+                .map(|p| format!("rgb({},{},{})", p[0], p[1], p[2]))
+                .collect::<Vec<_>>()
+                .join(",");
+            (
+                format!("linear-gradient(90deg,{colors})"),
+                format!("100% {}%", 100 / ROWS + 1),
+                format!("0 {}%", row * 100 / (ROWS - 1)),
+            )
+        })
+        .collect()
+}
+
+#[cfg(all(feature = "blur-placeholder", not(feature = "blurhash")))]
+fn blurhash_layers(_hash: &str) -> Vec<BackgroundLayer> {
+    Vec::new()
+}
+
+// `srcset` of the variants at each pixel density, or none with only the 1x variant.
+fn density_srcset(
+    src: String,
+    (width, height): (u32, u32),
+    quality: u8,
+    densities: &[u32],
+    scope: impl Fn(CachedImage) -> CachedImage,
+    config: &crate::provider::ImageConfig,
+) -> Option<String> {
+    let mut densities: Vec<u32> = densities.iter().copied().filter(|&d| d > 0).collect();
+    densities.sort_unstable();
+    densities.dedup();
+    if densities.iter().all(|&density| density == 1) {
+        return None;
     }
+    let srcset = densities
+        .into_iter()
+        .map(|density| {
+            let quality = match density {
+                1 => quality,
+                _ => config.dpr_quality.quality(quality, density as f32),
+            };
+            let image = CachedImage::resize(&src, width * density, height * density, quality);
+            format!("{} {density}x", config.url(&scope(image)))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(srcset)
 }
 
-enum SvgImage {
+// Widths generated for `<Image fill=true/>`, covering common device widths at 1x to 2x density.
+const FILL_WIDTHS: [u32; 8] = [640, 750, 828, 1080, 1200, 1920, 2048, 3840];
+
+// Positions a `fill` image over its parent, cropped or letterboxed like the resized variants.
+fn fill_style(fit: ResizeMode) -> String {
+    let (object_fit, position) = match fit {
+        ResizeMode::Contain => ("contain", "center".to_string()),
+        ResizeMode::Cover => ("cover", "center".to_string()),
+        ResizeMode::Fill => ("fill", "center".to_string()),
+        ResizeMode::Crop(gravity) => ("cover", gravity.as_str().replace('-', " ")),
+        ResizeMode::Focus(focus) => ("cover", focus.css_position()),
+    };
+    format!(
+        "position:absolute;inset:0;width:100%;height:100%;object-fit:{object_fit};object-position:{position};"
+    )
+}
+
+// Joins inline styles, later declarations override earlier ones.
+fn merge_styles(base: Option<String>, extra: Option<String>) -> Option<String> {
+    match (base, extra) {
+        (Some(base), Some(extra)) if base.trim_end().ends_with(';') => Some(base + &extra),
+        (Some(base), Some(extra)) => Some(format!("{base};{extra}")),
+        (base, extra) => base.or(extra),
+    }
+}
+
+// Width of the first image loaded in progressive mode.
+const PROGRESSIVE_WIDTH: u32 = 32;
+
+pub(crate) enum SvgImage {
+    #[cfg(feature = "blur-placeholder")]
     InMemory(String),
-    Request(String),
+    // Placeholders created with `Placeholder::BlurHash`.
+    #[cfg(feature = "blur-placeholder")]
+    BlurHash(String),
+    // Data URI of a tiny WebP, created with `Placeholder::CssBlur`.
+    #[cfg(feature = "blur-placeholder")]
+    CssBlur(String),
+}
+
+// Preload link for priority images. With `imagesrcset` and `imagesizes`, the browser preloads the
+// candidate it will pick for the `<img>`, not the largest one.
+fn preload_link(
+    href: String,
+    type_: Option<&'static str>,
+    srcset: Option<String>,
+    sizes: Option<String>,
+) -> View {
+    let sizes = sizes.filter(|_| srcset.is_some());
+    match type_ {
+        Some(type_) => view! {
+            <Link
+                rel="preload"
+                as_="image"
+                href=href
+                type_=type_
+                attr:imagesrcset=srcset
+                attr:imagesizes=sizes
+            />
+        },
+        None => view! {
+            <Link rel="preload" as_="image" href=href attr:imagesrcset=srcset attr:imagesizes=sizes/>
+        },
+    }
+    .into_view()
 }
 
+// `node_ref` is dropped by the view macro when rendering on the server.
+#[allow(unused_variables)]
 #[component]
 fn CacheImage(
-    svg: SvgImage,
-    #[prop(into)] opt_image: String,
-    #[prop(into, optional)] alt: String,
+    svg: Option<SvgImage>,
+    src: MaybeSignal<Option<String>>,
+    srcset: MaybeSignal<Option<String>>,
+    sizes: Option<String>,
+    alt: MaybeSignal<String>,
+    title: MaybeProp<String>,
     class: Option<Attribute>,
-    priority: bool,
+    width: Option<u32>,
+    height: Option<u32>,
+    style: MaybeSignal<Option<String>>,
+    fetchpriority: Option<&'static str>,
+    decoding: &'static str,
     lazy: bool,
+    node_ref: NodeRef<html::Img>,
+    on_load: Callback<()>,
+    on_error: Callback<ev::ErrorEvent>,
+    attributes: Vec<(&'static str, Attribute)>,
 ) -> impl IntoView {
-    use base64::{engine::general_purpose, Engine as _};
-
-    let style = {
-        let background_image = match svg {
-            SvgImage::InMemory(svg_data) => {
-                let svg_encoded = general_purpose::STANDARD.encode(svg_data.as_bytes());
-                format!("url('data:image/svg+xml;base64,{svg_encoded}')")
-            }
-            SvgImage::Request(svg_url) => {
-                format!("url('{}')", svg_url)
-            }
-        };
-        let style= format!(
-        "color:transparent;background-size:cover;background-position:50% 50%;background-repeat:no-repeat;background-image:{background_image};",
-        );
-
-        style
+    // Without a placeholder yet, the image renders like a plain `<img>`.
+    let placeholder = svg
+        .map(placeholder_layers)
+        .filter(|layers| !layers.is_empty())
+        .map(|layers| format!("color:transparent;{}", background_style(&layers)));
+    // A reactive style is patched while hydrating, which would drop a placeholder only the server had.
+    let style = match style {
+        MaybeSignal::Static(style) => merge_styles(placeholder, style).into_attribute(),
+        style => (move || merge_styles(placeholder.clone(), style.get())).into_attribute(),
     };
 
     let loading = if lazy { "lazy" } else { "eager" };
 
     view! {
-        {if priority {
-            view! { <Link rel="preload" as_="image" href=opt_image.clone()/> }.into_view()
-        } else {
-            ().into_view()
-        }}
-
         <img
-            alt=alt.clone()
+            {..attributes}
+            alt=alt
+            title=title
             class=class
-            decoding="async"
+            width=width
+            height=height
+            decoding=decoding
             loading=loading
-            src=opt_image
+            fetchpriority=fetchpriority
+            src=src
+            srcset=srcset
+            sizes=sizes
             style=style
+            node_ref=node_ref
+            on:load=move |_| on_load.call(())
+            on:error=move |event| on_error.call(event)
         />
     }
 }
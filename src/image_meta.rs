@@ -0,0 +1,90 @@
+use crate::optimizer::CachedImage;
+use leptos::*;
+use leptos_meta::Meta;
+
+/// Adds `og:image` and `twitter:image` meta tags pointing to an optimized image.
+///
+/// Crawlers need absolute URLs, so the optimizer must be given the site's origin
+/// with `ImageOptimizerBuilder::base_url`.
+///
+/// ```
+/// use leptos::*;
+/// use leptos_image::*;
+///
+/// #[component]
+/// fn Article() -> impl IntoView {
+///     view! {
+///         <ImageMeta src="/cover.jpg" width=1200 height=630 alt="Article cover"/>
+///     }
+/// }
+/// ```
+#[component]
+pub fn ImageMeta(
+    /// Image source. Should be a path relative to root.
+    #[prop(into)]
+    src: String,
+    /// Resize image width, but will still maintain the same aspect ratio.
+    width: u32,
+    /// Resize image height, but will still maintain the same aspect ratio.
+    height: u32,
+    /// Image quality. 0-100. Defaults to the quality configured on the optimizer.
+    #[prop(optional)]
+    quality: Option<u8>,
+    /// Image alt text, for `og:image:alt` and `twitter:image:alt`.
+    #[prop(into, optional)]
+    alt: Option<String>,
+) -> impl IntoView {
+    let url = use_absolute_image_url(src, width, height, quality);
+    let alt = store_value(alt);
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                url.get()
+                    .map(|url| {
+                        view! {
+                            <Meta property="og:image" content=url.clone()/>
+                            <Meta property="og:image:width" content=width.to_string()/>
+                            <Meta property="og:image:height" content=height.to_string()/>
+                            <Meta name="twitter:card" content="summary_large_image"/>
+                            <Meta name="twitter:image" content=url/>
+                            {alt
+                                .get_value()
+                                .map(|alt| {
+                                    view! {
+                                        <Meta property="og:image:alt" content=alt.clone()/>
+                                        <Meta name="twitter:image:alt" content=alt/>
+                                    }
+                                })}
+                        }
+                    })
+            }}
+
+        </Suspense>
+    }
+}
+
+/// Absolute URL of an optimized image, for meta tags and JSON-LD.
+///
+/// Resolves once the image config is loaded, see [`ImageMeta`] for the required setup.
+pub fn use_absolute_image_url(
+    src: impl Into<String>,
+    width: u32,
+    height: u32,
+    quality: Option<u8>,
+) -> Signal<Option<String>> {
+    let resource = crate::use_image_cache_resource();
+    let src = store_value(src.into());
+    let tenant = store_value(crate::provider::use_image_tenant());
+
+    Signal::derive(move || {
+        resource.get().map(|config| {
+            let quality = quality.unwrap_or(config.default_quality);
+            let format = config.formats.last().copied().unwrap_or_default();
+            let image = CachedImage::resize(src.get_value(), width, height, quality)
+                .with_format(format)
+                .scoped(tenant.get_value().as_deref());
+            config.absolute_url(&image)
+        })
+    })
+}
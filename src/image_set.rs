@@ -0,0 +1,121 @@
+use crate::optimizer::CachedImage;
+
+/// A reusable responsive image definition.
+///
+/// Define the set once and pass it as the `src` of [`crate::Image`], or to
+/// [`crate::ImageOptimizer::create_image_set`] to pre-generate every variant.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let hero = image_set! {
+///     src: "/hero.jpg",
+///     widths: [480, 960, 1920],
+///     sizes: "100vw",
+///     quality: 80,
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageSet {
+    pub(crate) src: String,
+    pub(crate) widths: Vec<u32>,
+    pub(crate) sizes: Option<String>,
+    pub(crate) quality: Option<u8>,
+}
+
+impl ImageSet {
+    /// Creates a new image set for a static source and the widths to generate.
+    pub fn new(src: impl Into<String>, widths: impl IntoIterator<Item = u32>) -> Self {
+        let mut widths: Vec<u32> = widths.into_iter().collect();
+        widths.sort_unstable();
+        widths.dedup();
+        Self {
+            src: src.into(),
+            widths,
+            sizes: None,
+            quality: None,
+        }
+    }
+
+    /// Sets the `sizes` attribute emitted alongside the `srcset`.
+    pub fn sizes(mut self, sizes: impl Into<String>) -> Self {
+        self.sizes = Some(sizes.into());
+        self
+    }
+
+    /// Sets the quality of every variant, overriding the `quality` prop of `<Image/>`.
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// The static source of the set.
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// The widths that are generated for the set, in ascending order.
+    pub fn widths(&self) -> &[u32] {
+        &self.widths
+    }
+
+    // Every variant keeps the aspect ratio of the `width` x `height` box given to `<Image/>`.
+    pub(crate) fn variants(&self, width: u32, height: u32, quality: u8) -> Vec<CachedImage> {
+        let quality = self.quality.unwrap_or(quality);
+        self.widths
+            .iter()
+            .map(|&w| CachedImage::resize(&self.src, w, scale_height(w, width, height), quality))
+            .collect()
+    }
+
+    pub(crate) fn srcset(
+        &self,
+        width: u32,
+        height: u32,
+        quality: u8,
+        scope: impl Fn(CachedImage) -> CachedImage,
+        config: &crate::ImageConfig,
+    ) -> String {
+        self.variants(width, height, quality)
+            .into_iter()
+            .map(scope)
+            .zip(self.widths.iter())
+            .map(|(image, w)| format!("{} {w}w", config.url(&image)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub(crate) fn scale_height(target_width: u32, width: u32, height: u32) -> u32 {
+    if width == 0 {
+        return height;
+    }
+    let scaled = (target_width as u64 * height as u64) / width as u64;
+    scaled.max(1) as u32
+}
+
+/// Declares an [`ImageSet`].
+///
+/// `src` and `widths` are required, `sizes` and `quality` are optional but must follow in that order.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let thumbnails = image_set! { src: "/cute_ferris.png", widths: [64, 128] };
+/// assert_eq!(thumbnails.widths(), &[64, 128]);
+/// ```
+#[macro_export]
+macro_rules! image_set {
+    (
+        src: $src:expr,
+        widths: [$($width:expr),* $(,)?]
+        $(, sizes: $sizes:expr)?
+        $(, quality: $quality:expr)?
+        $(,)?
+    ) => {{
+        let set = $crate::ImageSet::new($src, [$($width),*]);
+        $(let set = set.sizes($sizes);)?
+        $(let set = set.quality($quality);)?
+        set
+    }};
+}
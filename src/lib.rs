@@ -15,6 +15,25 @@
 //! - **Low-Quality Image Placeholders (LQIP)**: Embeds SVG placeholders extracted from original images directly into your server-side rendered HTML, improving perceived performance by displaying content while the full-quality image loads.
 //! - **Faster Page Load**: Prioritizes key images that impact the Largest Contentful Paint (LCP) with the `priority` prop, injecting a preload `<link>` into the document head to accelerate load times.
 //!
+//! ## Cargo Features
+//!
+//! - `ssr` / `hydrate`: Enable alongside the matching Leptos features.
+//! - `csr`: For client side rendered apps without a server, e.g. built with Trunk. `<Image/>` renders its source as-is.
+//! - `blur-placeholder` (default): Blur placeholders for `<Image blur=true/>`.
+//! - `blurhash`: BlurHash placeholders, see [`Placeholder::BlurHash`].
+//! - `webp` (default): Lossy WebP encoding via libwebp. When disabled, images are encoded as lossless WebP in pure Rust.
+//! - `pure-rust`: Never encodes with libwebp, even when `webp` is enabled. For targets where C dependencies don't build, like musl or Windows cross compiles.
+//! - `axum-handler` (default): The Axum route serving cached images, see [`ImageCacheRoute`].
+//! - `actix`: The Actix Web handler serving cached images, see `ImageCacheService`.
+//! - `viz` / `rocket`: The Viz and Rocket handlers serving cached images, see `ImageCacheVizRoute` and `ImageCacheMount`.
+//! - `avif`: AVIF encoding, for [`OutputFormat::Avif`] in `OptimizerConfig::formats`.
+//! - `smartcrop`: Crops [`ResizeMode::Cover`] images around their most detailed part instead of the center.
+//! - `jxl`: Lossless JPEG XL encoding, for [`OutputFormat::Jxl`] and lossless images in browsers that accept it.
+//! - `fetch`: Warm the cache from page URLs and sitemaps with `ImageOptimizer::warm_from_urls`, and optimize remote images from `OptimizerConfig::remote_domains`.
+//! - `metrics`: Cache, encode and request metrics through the `metrics` crate, for exporters like Prometheus.
+//!
+//! Server only dependencies are never compiled for `wasm32`, so the defaults are safe for client builds.
+//!
 //! ## Getting Started
 //!
 //! The crate focuses on creating optimized images for static content in Leptos projects, a full-stack web framework in Rust.
@@ -101,15 +120,68 @@
 //! ```
 //!
 
+mod background;
+#[cfg(feature = "ssr")]
+mod cache;
+mod gallery;
 mod image;
+mod image_meta;
+mod image_set;
+#[cfg(feature = "ssr")]
+mod metadata;
 mod optimizer;
+mod picture;
 mod provider;
+#[cfg(all(feature = "ssr", feature = "axum-handler"))]
+mod request_log;
 #[cfg(feature = "ssr")]
 mod routes;
+#[cfg(feature = "actix")]
+mod routes_actix;
+#[cfg(all(feature = "ssr", feature = "axum-handler"))]
+mod routes_axum;
+#[cfg(feature = "rocket")]
+mod routes_rocket;
+#[cfg(feature = "viz")]
+mod routes_viz;
+mod sizes;
+#[cfg(feature = "ssr")]
+mod storage;
+#[cfg(feature = "ssr")]
+mod telemetry;
+#[cfg(feature = "ssr")]
+mod warm;
 
+pub use background::*;
+#[cfg(feature = "ssr")]
+pub use cache::*;
+pub use gallery::*;
 pub use image::*;
+pub use image_meta::*;
+pub use image_set::*;
 #[cfg(feature = "ssr")]
-pub use optimizer::ImageOptimizer;
+pub use optimizer::{
+    BlurTemplate, CreateImageError, ImageOptimizer, ImageOptimizerBuilder, OptimizerConfig,
+    Parallelism, SourceInfo, Watermark,
+};
+pub use optimizer::{
+    CachedImage, DprQualityCurve, FocalPoint, Gravity, OutputFormat, Placeholder, ResizeFilter,
+    ResizeMode, Transform, UrlScheme, MAX_TRANSFORMS,
+};
+pub use picture::*;
 pub use provider::*;
+#[cfg(all(feature = "ssr", feature = "axum-handler"))]
+pub use request_log::{ImageLog, ImageLogLayer};
 #[cfg(feature = "ssr")]
 pub use routes::*;
+#[cfg(feature = "actix")]
+pub use routes_actix::*;
+#[cfg(all(feature = "ssr", feature = "axum-handler"))]
+pub use routes_axum::*;
+#[cfg(feature = "rocket")]
+pub use routes_rocket::*;
+#[cfg(feature = "viz")]
+pub use routes_viz::*;
+pub use sizes::*;
+#[cfg(feature = "ssr")]
+pub use storage::*;
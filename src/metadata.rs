@@ -0,0 +1,171 @@
+// Copies EXIF and ICC metadata from a source image into WebP output.
+// Encoders only see decoded pixels, so metadata is stripped unless it is copied explicitly.
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SourceMetadata {
+    pub(crate) exif: Option<Vec<u8>>,
+    pub(crate) icc: Option<Vec<u8>>,
+}
+
+impl SourceMetadata {
+    // Reads the metadata of a JPEG, PNG or WebP source. Other formats have none.
+    pub(crate) fn read(bytes: &[u8]) -> Self {
+        use image::ImageDecoder;
+        let cursor = std::io::Cursor::new(bytes);
+        match image::guess_format(bytes) {
+            Ok(image::ImageFormat::Jpeg) => Self {
+                exif: jpeg_exif(bytes),
+                icc: image::codecs::jpeg::JpegDecoder::new(cursor)
+                    .ok()
+                    .and_then(|mut decoder| decoder.icc_profile()),
+            },
+            Ok(image::ImageFormat::Png) => Self {
+                exif: png_exif(bytes),
+                icc: image::codecs::png::PngDecoder::new(cursor)
+                    .ok()
+                    .and_then(|mut decoder| decoder.icc_profile()),
+            },
+            Ok(image::ImageFormat::WebP) => Self {
+                exif: riff_chunks(bytes)
+                    .find(|(fourcc, _)| fourcc == b"EXIF")
+                    .map(|(_, data)| data.to_vec()),
+                icc: riff_chunks(bytes)
+                    .find(|(fourcc, _)| fourcc == b"ICCP")
+                    .map(|(_, data)| data.to_vec()),
+            },
+            _ => Self::default(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc.is_none()
+    }
+}
+
+// EXIF of a JPEG, from the APP1 segment starting with `Exif\0\0`.
+fn jpeg_exif(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = bytes.strip_prefix(&[0xFF, 0xD8])?;
+    while let [0xFF, marker, high, low, tail @ ..] = rest {
+        // Metadata segments all come before the start of scan.
+        if *marker == 0xDA {
+            break;
+        }
+        let length = (u16::from_be_bytes([*high, *low]) as usize).checked_sub(2)?;
+        let segment = tail.get(..length)?;
+        if *marker == 0xE1 {
+            if let Some(exif) = segment.strip_prefix(b"Exif\0\0") {
+                return Some(exif.to_vec());
+            }
+        }
+        rest = &tail[length..];
+    }
+    None
+}
+
+// EXIF of a PNG, from its `eXIf` chunk.
+fn png_exif(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = bytes.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    while rest.len() >= 8 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + length)?;
+        if kind == b"eXIf" {
+            return Some(data.to_vec());
+        }
+        if kind == b"IDAT" || kind == b"IEND" {
+            break;
+        }
+        // Chunk data is followed by a 4 byte CRC.
+        rest = rest.get(8 + length + 4..)?;
+    }
+    None
+}
+
+// Chunks of a RIFF (WebP) file, as `(fourcc, data)` pairs.
+fn riff_chunks(bytes: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut rest = match bytes.get(..12) {
+        Some(header) if &header[..4] == b"RIFF" && &header[8..] == b"WEBP" => &bytes[12..],
+        _ => &[][..],
+    };
+    std::iter::from_fn(move || {
+        let fourcc: [u8; 4] = rest.get(..4)?.try_into().ok()?;
+        let length = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+        let data = rest.get(8..8 + length)?;
+        // Chunks are padded to an even size.
+        rest = rest.get(8 + length + (length & 1)..).unwrap_or_default();
+        Some((fourcc, data))
+    })
+}
+
+fn push_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+// Rewrites a simple WebP file in the extended format, with an ICCP chunk before the image and an EXIF chunk after it.
+pub(crate) fn embed_webp_metadata(
+    webp: Vec<u8>,
+    width: u32,
+    height: u32,
+    metadata: &SourceMetadata,
+) -> Vec<u8> {
+    const ICC: u8 = 0x20;
+    const ALPHA: u8 = 0x10;
+    const EXIF: u8 = 0x08;
+
+    if metadata.is_empty() {
+        return webp;
+    }
+    let chunks: Vec<_> = riff_chunks(&webp).collect();
+    if chunks.is_empty() {
+        return webp;
+    }
+
+    let mut flags = 0;
+    if metadata.icc.is_some() {
+        flags |= ICC;
+    }
+    if metadata.exif.is_some() {
+        flags |= EXIF;
+    }
+    let alpha = chunks.iter().any(|(fourcc, data)| match fourcc {
+        b"ALPH" => true,
+        // The VP8L header stores whether alpha is used in bit 28, after the signature byte.
+        b"VP8L" => data
+            .get(1..5)
+            .is_some_and(|header| u32::from_le_bytes(header.try_into().unwrap()) >> 28 & 1 == 1),
+        b"VP8X" => data.first().is_some_and(|f| f & ALPHA != 0),
+        _ => false,
+    });
+    if alpha {
+        flags |= ALPHA;
+    }
+
+    let mut vp8x = vec![flags, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    let mut body = b"WEBP".to_vec();
+    push_chunk(&mut body, b"VP8X", &vp8x);
+    if let Some(icc) = &metadata.icc {
+        push_chunk(&mut body, b"ICCP", icc);
+    }
+    for (fourcc, data) in chunks
+        .iter()
+        .filter(|(fourcc, _)| !matches!(fourcc, b"VP8X" | b"ICCP" | b"EXIF"))
+    {
+        push_chunk(&mut body, fourcc, data);
+    }
+    if let Some(exif) = &metadata.exif {
+        push_chunk(&mut body, b"EXIF", exif);
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
@@ -8,6 +8,1073 @@ pub struct ImageOptimizer {
     pub(crate) root_file_path: String,
     pub(crate) semaphore: std::sync::Arc<tokio::sync::Semaphore>,
     pub(crate) cache: std::sync::Arc<dashmap::DashMap<CachedImage, String>>,
+    pub(crate) blur_template: BlurTemplate,
+    pub(crate) warmup_token: Option<std::sync::Arc<str>>,
+    pub(crate) signing_key: Option<std::sync::Arc<[u8]>>,
+    pub(crate) tenants: std::sync::Arc<std::collections::HashMap<String, Tenant>>,
+    pub(crate) url_scheme: UrlScheme,
+    pub(crate) base_url: Option<String>,
+    pub(crate) base_path: String,
+    pub(crate) cache_dir: String,
+    pub(crate) cache_namespace: String,
+    pub(crate) config: std::sync::Arc<std::sync::RwLock<OptimizerConfig>>,
+    // Downloads remote sources, see `remote_client`.
+    #[cfg(feature = "fetch")]
+    pub(crate) http: reqwest::Client,
+    #[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+    pub(crate) pending_blurs: std::sync::Arc<dashmap::DashSet<CachedImage>>,
+    #[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+    pub(crate) background: std::sync::Arc<tokio::sync::Semaphore>,
+    pub(crate) auto_tune: Option<std::sync::Arc<std::sync::Mutex<AutoTune>>>,
+    pub(crate) in_flight: std::sync::Arc<InFlight>,
+    pub(crate) encoder_pool: Option<std::sync::Arc<EncoderPool>>,
+    // Serializes manifest writes, so an older snapshot never overwrites a newer one.
+    pub(crate) manifest_lock: std::sync::Arc<tokio::sync::Mutex<()>>,
+    pub(crate) cache_control: Option<String>,
+    pub(crate) sweeper: std::sync::Arc<CacheSweeper>,
+    // Files being created or downloaded, so concurrent requests for the same one are handled once.
+    pub(crate) creating: std::sync::Arc<
+        dashmap::DashMap<std::path::PathBuf, std::sync::Arc<tokio::sync::Mutex<()>>>,
+    >,
+    // Sources probed by `ImageOptimizer::probe` or rendered by `<Image/>`, keyed by tenant and source.
+    pub(crate) source_dimensions: std::sync::Arc<dashmap::DashMap<SourceKey, SourceInfo>>,
+    // Sources recently found missing, so requests for them don't hit the disk again.
+    pub(crate) missing_sources:
+        std::sync::Arc<dashmap::DashMap<std::path::PathBuf, std::time::Instant>>,
+    // Versions of the sources cached files were created from, keyed by cached file.
+    pub(crate) source_versions: std::sync::Arc<dashmap::DashMap<std::path::PathBuf, SourceVersion>>,
+    // Stale files being created again in the background, see `OptimizerConfig::stale_while_revalidate`.
+    pub(crate) revalidating: std::sync::Arc<dashmap::DashSet<std::path::PathBuf>>,
+    // Images the app rendered or created ahead of time, see `OptimizerConfig::strict`.
+    pub(crate) known_images: std::sync::Arc<dashmap::DashSet<CachedImage>>,
+    // Originals rendered on the server, the only ones served in strict mode.
+    pub(crate) known_originals: std::sync::Arc<dashmap::DashSet<OriginalImage>>,
+    // Canonical paths of the roots, resolved once instead of on every request.
+    canonical_roots: std::sync::Arc<dashmap::DashMap<String, std::path::PathBuf>>,
+    // Images rendered with settings their URLs don't carry, keyed by the image of their URL.
+    pub(crate) server_overrides: std::sync::Arc<dashmap::DashMap<CachedImage, CachedImage>>,
+    // Durable store of created images, see `ImageOptimizerBuilder::cache_backend`.
+    pub(crate) storage: Option<std::sync::Arc<dyn crate::CacheBackend>>,
+    // Where sources are loaded from, see `ImageOptimizerBuilder::image_source`.
+    pub(crate) sources: Option<std::sync::Arc<dyn crate::ImageSource>>,
+}
+
+/// Settings of an [`ImageOptimizer`] that can be changed while the server is running,
+/// see [`ImageOptimizer::update_config`].
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizerConfig {
+    /// Quality used by `<Image/>` when no `quality` prop is given. Applies to pages rendered after the change.
+    pub default_quality: u8,
+    /// Number of images that can be created at once. Adjusted automatically with [`Parallelism::Auto`].
+    pub parallelism: usize,
+    /// Path prefixes of the sources that may be optimized, e.g. `/assets/`. Empty allows every source.
+    pub allowed_sources: Vec<String>,
+    /// How quality is lowered for high pixel density variants.
+    pub dpr_quality: DprQualityCurve,
+    /// Formats `<Image/>` is served in, most preferred first. The last one is the `<img>` fallback.
+    ///
+    /// With more than one format, `<Image/>` renders a `<picture>` with a `<source>` per preferred format,
+    /// e.g. `vec![OutputFormat::Avif, OutputFormat::WebP]`. Defaults to WebP only.
+    pub formats: Vec<OutputFormat>,
+    /// Produces byte-identical images across runs and machines, so they can be content-addressed
+    /// and verified with [`ImageOptimizer::verify_sync`]. Encoding may be slower.
+    ///
+    /// WebP and blur placeholders are always deterministic for a given encoder version,
+    /// this pins AVIF encoding to a single thread.
+    pub deterministic: bool,
+    /// Filter used to resize images. Defaults to [`ResizeFilter::CatmullRom`].
+    pub resize_filter: ResizeFilter,
+    /// Filters for smaller variants, as `(max_width, filter)` pairs.
+    /// An image uses the filter of the smallest `max_width` it fits in, e.g. `vec![(128, ResizeFilter::Triangle)]`
+    /// for fast thumbnails, and [`OptimizerConfig::resize_filter`] when it fits in none.
+    pub resize_filter_overrides: Vec<(u32, ResizeFilter)>,
+    /// Strength of the unsharp mask applied after resizing, in percent. Defaults to 0, which doesn't sharpen.
+    ///
+    /// Strong downscales look soft, and 30 to 80 brings back the detail of thumbnails.
+    /// Images can override it with [`CachedImage::with_sharpen`].
+    pub sharpen: u8,
+    /// Highest pixel density clients may request with a `dpr` query parameter, e.g. `?dpr=2`.
+    ///
+    /// Lets a small client script pick the density of an image the server rendered at 1x.
+    /// The width and height are multiplied by the density and the quality follows [`OptimizerConfig::dpr_quality`].
+    /// Only whole densities up to this limit are accepted, so each image has a bounded number of variants.
+    /// Defaults to 1, which ignores the parameter.
+    pub max_client_dpr: u8,
+    /// Domains remote `http(s)` images may be downloaded from and optimized, like local sources.
+    /// A leading `*.` matches any subdomain, e.g. `*.example.com`.
+    ///
+    /// Requires the `fetch` feature. Remote images from other domains are rendered as-is.
+    /// Downloads only follow redirects to these domains, and are limited to 32 MiB and 30 seconds.
+    /// Empty by default, so no remote image is optimized.
+    pub remote_domains: Vec<String>,
+    /// How `<Image blur=true/>` renders placeholders. Defaults to [`Placeholder::Svg`].
+    pub placeholder: Placeholder,
+    /// Largest `(width, height)` an image may be resized to, including client requested densities and blur placeholders.
+    /// Larger requests fail with [`CreateImageError::TooLarge`], so URLs can't be crafted to exhaust memory.
+    /// Unlimited by default.
+    pub max_dimensions: Option<(u32, u32)>,
+    /// Qualities images may be encoded at. Others fail with [`CreateImageError::QualityOutOfRange`].
+    /// Defaults to `0..=100`.
+    pub quality_range: std::ops::RangeInclusive<u8>,
+    /// Clamps requested image URLs into [`OptimizerConfig::max_dimensions`] and [`OptimizerConfig::quality_range`]
+    /// instead of rejecting them. Sizes are scaled down with their aspect ratio.
+    ///
+    /// Off by default, so a page asking for more than allowed fails loudly instead of getting a smaller image.
+    pub clamp_requests: bool,
+    /// Size the cache directory may grow to, across namespaces. Unlimited by default.
+    ///
+    /// Once exceeded, the least recently served images are deleted after new ones are created,
+    /// see [`ImageOptimizer::sweep_cache`]. Deleted images are created again when requested.
+    pub max_cache_size_bytes: Option<u64>,
+    /// Copies the EXIF metadata and ICC color profile of sources into optimized WebP images.
+    ///
+    /// Off by default: metadata is stripped, which removes private data like the GPS coordinates
+    /// of user photos and makes images smaller. AVIF images are always stripped.
+    /// Images rendered on the server can override it with [`CachedImage::with_preserve_metadata`].
+    pub preserve_metadata: bool,
+    /// Never scales sources up: requests larger than the source get an image at the source's resolution,
+    /// with the requested aspect ratio, instead of a blurry upscaled file that's bigger than the source.
+    ///
+    /// Off by default. Images can override it with [`CachedImage::with_no_upscale`].
+    pub no_upscale: bool,
+    /// Composites a watermark onto resized images, so the sources are never served unwatermarked.
+    ///
+    /// Images rendered on the server can opt out with [`CachedImage::with_watermark`], clients can't.
+    /// The original image route is disabled while a watermark is set.
+    pub watermark: Option<Watermark>,
+    /// Serves the cached image right away when its source changed, and creates it again in the background.
+    ///
+    /// Off by default: requests wait for the new image, which may take seconds for large images.
+    /// Later requests get the new image once it is written.
+    pub stale_while_revalidate: bool,
+    /// Only creates images the app rendered on the server, or created ahead of time with [`crate::cache_app_images`],
+    /// [`ImageOptimizer::create_image_set`] or the cache warm-up. Other URLs get a 404 unless their file already exists.
+    ///
+    /// Closes the denial of service of requesting arbitrary sizes, without signing URLs.
+    /// Known images are kept in memory, so with several instances each must render or warm them.
+    /// Off by default.
+    pub strict: bool,
+    /// Picks the format of WebP image requests from their `Accept` header: AVIF or JPEG XL for browsers that list them
+    /// (with the `avif` or `jxl` feature), WebP for the ones that list it, and JPEG or PNG for the others.
+    /// Requests without an `Accept` header get WebP.
+    ///
+    /// Each format is created on its first request and cached separately, and responses carry `Vary: Accept`.
+    /// On by default. Turn it off when a CDN in front of the server ignores `Vary`.
+    pub negotiate_format: bool,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        Self {
+            default_quality: 75,
+            parallelism: 1,
+            allowed_sources: Vec::new(),
+            dpr_quality: DprQualityCurve::default(),
+            formats: vec![OutputFormat::WebP],
+            deterministic: false,
+            resize_filter: ResizeFilter::default(),
+            resize_filter_overrides: Vec::new(),
+            sharpen: 0,
+            max_client_dpr: 1,
+            remote_domains: Vec::new(),
+            placeholder: Placeholder::default(),
+            max_dimensions: None,
+            quality_range: 0..=100,
+            clamp_requests: false,
+            max_cache_size_bytes: None,
+            preserve_metadata: false,
+            no_upscale: false,
+            watermark: None,
+            stale_while_revalidate: false,
+            strict: false,
+            negotiate_format: true,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl OptimizerConfig {
+    pub(crate) fn resize_filter_for(&self, width: u32) -> ResizeFilter {
+        self.resize_filter_overrides
+            .iter()
+            .filter(|(max_width, _)| width <= *max_width)
+            .min_by_key(|(max_width, _)| *max_width)
+            .map_or(self.resize_filter, |(_, filter)| *filter)
+    }
+
+    // Digest of the settings resized images are encoded with beyond their spec, mixed into the names
+    // of their files so changing a setting creates new files. Empty with the defaults.
+    pub(crate) fn encode_digest(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut settings = Vec::new();
+        if self.resize_filter != ResizeFilter::default() {
+            settings.push(format!("rf={:?}", self.resize_filter));
+        }
+        if !self.resize_filter_overrides.is_empty() {
+            settings.push(format!("rfo={:?}", self.resize_filter_overrides));
+        }
+        if self.sharpen != 0 {
+            settings.push(format!("sh={}", self.sharpen));
+        }
+        if self.preserve_metadata {
+            settings.push("md".to_string());
+        }
+        if self.no_upscale {
+            settings.push("nu".to_string());
+        }
+        if let Some(watermark) = &self.watermark {
+            settings.push(format!("wm={watermark:?}"));
+        }
+        if settings.is_empty() {
+            return String::new();
+        }
+        crate::storage::hex(&Sha256::digest(settings.join("&").as_bytes())[..8])
+    }
+
+    // The first size of the option above `max_dimensions`. Blurs are bounded too,
+    // their sample is resized to the size the URL asks for.
+    fn oversized(&self, option: &CachedImageOption) -> Option<(u32, u32)> {
+        let (max_width, max_height) = self.max_dimensions?;
+        let sizes = match option {
+            CachedImageOption::Resize(resize) => vec![(resize.width, resize.height)],
+            CachedImageOption::Blur(blur) => {
+                vec![(blur.width, blur.height), (blur.svg_width, blur.svg_height)]
+            }
+        };
+        sizes
+            .into_iter()
+            .find(|&(width, height)| width > max_width || height > max_height)
+    }
+
+    // Scales sizes down into `max_dimensions` and a resize's quality into `quality_range`.
+    fn clamp(&self, option: &mut CachedImageOption) {
+        match option {
+            CachedImageOption::Resize(resize) => {
+                self.clamp_size(&mut resize.width, &mut resize.height);
+                resize.quality = resize
+                    .quality
+                    .clamp(*self.quality_range.start(), *self.quality_range.end());
+            }
+            CachedImageOption::Blur(blur) => {
+                self.clamp_size(&mut blur.width, &mut blur.height);
+                self.clamp_size(&mut blur.svg_width, &mut blur.svg_height);
+            }
+        }
+    }
+
+    // Scales a size down into `max_dimensions`, keeping its aspect ratio.
+    fn clamp_size(&self, width: &mut u32, height: &mut u32) {
+        let Some((max_width, max_height)) = self.max_dimensions else {
+            return;
+        };
+        if *width > max_width || *height > max_height {
+            let scale = f64::min(
+                max_width as f64 / *width as f64,
+                max_height as f64 / *height as f64,
+            );
+            *width = ((*width as f64 * scale) as u32).clamp(1, max_width);
+            *height = ((*height as f64 * scale) as u32).clamp(1, max_height);
+        }
+    }
+
+    pub(crate) fn is_allowed(&self, src: &str) -> bool {
+        if is_remote(src) {
+            return remote_domain_allowed(&self.remote_domains, src);
+        }
+        let src = src.trim_start_matches('/');
+        self.allowed_sources.is_empty()
+            || self
+                .allowed_sources
+                .iter()
+                .any(|prefix| src.starts_with(prefix.trim_start_matches('/')))
+    }
+}
+
+// A cached image file, ready to be served by a framework handler.
+#[cfg(feature = "ssr")]
+#[cfg_attr(
+    not(any(feature = "axum-handler", feature = "actix")),
+    allow(dead_code)
+)]
+pub(crate) struct CachedFile {
+    // Path of the file, relative to the root.
+    pub(crate) path: String,
+    // Root the file is served from.
+    pub(crate) root: String,
+    pub(crate) image: CachedImage,
+    // Whether the format was picked from the `Accept` header, so responses must vary on it.
+    pub(crate) negotiated: bool,
+    // How long it took to create the file, if it was created for this request. Used for request logs.
+    pub(crate) generation: Option<std::time::Duration>,
+}
+
+/// A watermark composited onto optimized images, see [`OptimizerConfig::watermark`].
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let watermark = Watermark {
+///     position: Gravity::BottomLeft,
+///     opacity: 30,
+///     ..Watermark::new("/watermark.png")
+/// };
+/// let optimizer = ImageOptimizer::builder().root("./target/site").watermark(watermark).build();
+/// ```
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    /// Path of the watermark image relative to the root, usually a PNG with transparency. Drawn at its own size.
+    pub src: String,
+    /// Edge or corner the watermark is placed at. Defaults to the bottom right corner.
+    pub position: Gravity,
+    /// Opacity in percent. Defaults to 50.
+    pub opacity: u8,
+    /// Space between the watermark and the edges of the image, in pixels. Defaults to 16.
+    pub margin: u32,
+    /// Images narrower than this, like thumbnails, aren't watermarked. Defaults to 400.
+    pub min_width: u32,
+}
+
+#[cfg(feature = "ssr")]
+impl Watermark {
+    /// A watermark with the default placement.
+    pub fn new(src: impl Into<String>) -> Self {
+        Self {
+            src: src.into(),
+            position: Gravity::BottomRight,
+            opacity: 50,
+            margin: 16,
+            min_width: 400,
+        }
+    }
+}
+
+/// Filter used when resizing images, from fastest to sharpest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResizeFilter {
+    /// Nearest neighbor. Blocky, only suited to tiny placeholders.
+    #[serde(rename = "nearest")]
+    Nearest,
+    /// Linear. Fast and good enough for thumbnails.
+    #[serde(rename = "triangle")]
+    Triangle,
+    /// Cubic.
+    #[default]
+    #[serde(rename = "catmullrom")]
+    CatmullRom,
+    /// Gaussian. Soft.
+    #[serde(rename = "gaussian")]
+    Gaussian,
+    /// Lanczos with a window of 3. Sharpest, and slowest.
+    #[serde(rename = "lanczos3")]
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    #[cfg(feature = "ssr")]
+    const ALL: [ResizeFilter; 5] = [
+        ResizeFilter::Nearest,
+        ResizeFilter::Triangle,
+        ResizeFilter::CatmullRom,
+        ResizeFilter::Gaussian,
+        ResizeFilter::Lanczos3,
+    ];
+
+    // Name used in path encoded URLs.
+    fn as_str(self) -> &'static str {
+        match self {
+            ResizeFilter::Nearest => "nearest",
+            ResizeFilter::Triangle => "triangle",
+            ResizeFilter::CatmullRom => "catmullrom",
+            ResizeFilter::Gaussian => "gaussian",
+            ResizeFilter::Lanczos3 => "lanczos3",
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|filter| filter.as_str() == name)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl ResizeFilter {
+    fn filter_type(self) -> image::imageops::FilterType {
+        use image::imageops::FilterType;
+        match self {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// How many images an [`ImageOptimizer`] creates at once.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parallelism {
+    /// A fixed number of images.
+    Fixed(usize),
+    /// One image per CPU core, as long as the estimated decode memory fits in `memory_budget` bytes.
+    /// Lowered while encode latencies spike, and raised back once they recover.
+    Auto {
+        /// Memory available for decoding images, in bytes.
+        memory_budget: u64,
+    },
+}
+
+#[cfg(feature = "ssr")]
+impl Parallelism {
+    // Rough peak memory of a single encode: a 24 megapixel RGBA source plus the resized copy.
+    const MEMORY_PER_IMAGE: u64 = 128 * 1024 * 1024;
+
+    /// Auto-tuned parallelism with a 1 GiB memory budget.
+    pub fn auto() -> Self {
+        Parallelism::Auto {
+            memory_budget: 1024 * 1024 * 1024,
+        }
+    }
+
+    fn auto_limit(memory_budget: u64) -> usize {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let memory = (memory_budget / Self::MEMORY_PER_IMAGE) as usize;
+        cores.min(memory).max(1)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<usize> for Parallelism {
+    fn from(parallelism: usize) -> Self {
+        Parallelism::Fixed(parallelism)
+    }
+}
+
+// Adapts parallelism to encode latency, normalized by output size.
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+pub(crate) struct AutoTune {
+    max: usize,
+    current: usize,
+    // Moving average of nanoseconds per output pixel while not under pressure.
+    baseline: Option<f64>,
+    // Encodes within the baseline since the last change.
+    healthy: usize,
+}
+
+#[cfg(feature = "ssr")]
+impl AutoTune {
+    // An encode this many times slower than the baseline counts as a spike.
+    const SPIKE: f64 = 3.0;
+
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            current: max,
+            baseline: None,
+            healthy: 0,
+        }
+    }
+
+    // Records an encode, returning the new parallelism if it should change.
+    fn record(&mut self, elapsed: std::time::Duration, pixels: u64) -> Option<usize> {
+        let cost = elapsed.as_nanos() as f64 / pixels.max(1) as f64;
+        let Some(baseline) = self.baseline else {
+            self.baseline = Some(cost);
+            return None;
+        };
+
+        if cost > baseline * Self::SPIKE {
+            self.healthy = 0;
+            if self.current > 1 {
+                self.current -= 1;
+                return Some(self.current);
+            }
+            return None;
+        }
+
+        self.baseline = Some(baseline * 0.9 + cost * 0.1);
+        self.healthy += 1;
+        if self.current < self.max && self.healthy >= self.current * 4 {
+            self.healthy = 0;
+            self.current += 1;
+            return Some(self.current);
+        }
+        None
+    }
+}
+
+#[cfg(feature = "ssr")]
+type EncodeJob = Box<dyn FnOnce() + Send>;
+
+// Pre-spawned encoder threads, see `ImageOptimizerBuilder::encoder_threads`.
+// The threads exit once every optimizer clone is dropped.
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+pub(crate) struct EncoderPool {
+    sender: std::sync::Mutex<std::sync::mpsc::Sender<EncodeJob>>,
+}
+
+#[cfg(feature = "ssr")]
+impl EncoderPool {
+    fn new(threads: usize) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel::<EncodeJob>();
+        let receiver = std::sync::Arc::new(std::sync::Mutex::new(receiver));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("leptos-image-encoder-{i}"))
+                .spawn(move || {
+                    // Runs the encoder's one-time initialization ahead of the first request.
+                    let pixel = image::DynamicImage::new_rgba8(1, 1);
+                    let _ = encode_webp(&pixel, 75, false);
+
+                    loop {
+                        let job = receiver.lock().expect("Encoder queue poisoned").recv();
+                        let Ok(job) = job else {
+                            break;
+                        };
+                        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                    }
+                })
+                .expect("Failed to spawn encoder thread");
+        }
+        Self {
+            sender: std::sync::Mutex::new(sender),
+        }
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, CreateImageError> {
+        let (result, receiver) = tokio::sync::oneshot::channel();
+        let job: EncodeJob = Box::new(move || {
+            let _ = result.send(job());
+        });
+        let failed = || std::io::Error::other("Encoder thread failed");
+        self.sender
+            .lock()
+            .expect("Encoder queue poisoned")
+            .send(job)
+            .map_err(|_| failed())?;
+        Ok(receiver.await.map_err(|_| failed())?)
+    }
+}
+
+// Tracks the encodes that are running, for `ImageOptimizer::shutdown`.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    closed: std::sync::atomic::AtomicBool,
+    count: std::sync::atomic::AtomicUsize,
+    drained: tokio::sync::Notify,
+}
+
+#[cfg(feature = "ssr")]
+impl InFlight {
+    fn start(self: &std::sync::Arc<Self>) -> Result<InFlightGuard, CreateImageError> {
+        use std::sync::atomic::Ordering;
+        crate::telemetry::in_flight(self.count.fetch_add(1, Ordering::SeqCst) + 1);
+        let guard = InFlightGuard(self.clone());
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(CreateImageError::ShuttingDown);
+        }
+        Ok(guard)
+    }
+}
+
+#[cfg(feature = "ssr")]
+struct InFlightGuard(std::sync::Arc<InFlight>);
+
+#[cfg(feature = "ssr")]
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let count = self
+            .0
+            .count
+            .fetch_sub(1, std::sync::atomic::Ordering::SeqCst)
+            - 1;
+        crate::telemetry::in_flight(count);
+        if count == 0 {
+            self.0.drained.notify_waiters();
+        }
+    }
+}
+
+// Evicts the least recently served files once the cache exceeds `OptimizerConfig::max_cache_size_bytes`.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+pub(crate) struct CacheSweeper {
+    running: std::sync::atomic::AtomicBool,
+    // Files served since startup. Others fall back to their modification time.
+    last_served: dashmap::DashMap<std::path::PathBuf, std::time::SystemTime>,
+}
+
+#[cfg(feature = "ssr")]
+impl CacheSweeper {
+    fn served(&self, path: std::path::PathBuf) {
+        self.last_served.insert(path, std::time::SystemTime::now());
+    }
+
+    // Deletes files under `dir` until it fits the budget, returning the bytes freed.
+    fn sweep(
+        &self,
+        dir: &std::path::Path,
+        budget: u64,
+        cache: &dashmap::DashMap<CachedImage, String>,
+    ) -> std::io::Result<u64> {
+        let mut files = Vec::new();
+        let mut total = 0;
+        collect_cache_files(dir, &mut files, &mut total)?;
+        if total <= budget {
+            return Ok(0);
+        }
+
+        let last_used = |(path, _, modified): &(std::path::PathBuf, u64, std::time::SystemTime)| {
+            self.last_served
+                .get(path)
+                .map_or(*modified, |served| *served)
+        };
+        files.sort_by_key(last_used);
+
+        let mut freed = 0;
+        for (path, size, _) in files {
+            if total - freed <= budget {
+                break;
+            }
+            let sidecar = sidecar_path(&path);
+            if let Some(image) = CachedImage::from_file_path(&path.to_string_lossy()) {
+                cache.remove(&image);
+            }
+            std::fs::remove_file(&path)?;
+            freed += size;
+            if let Ok(metadata) = std::fs::metadata(&sidecar) {
+                std::fs::remove_file(&sidecar)?;
+                freed += metadata.len();
+            }
+            self.last_served.remove(&path);
+        }
+        Ok(freed)
+    }
+}
+
+// Cached images under `dir` with their size and modification time.
+// Sidecars and manifests count towards the total, but are never evicted on their own.
+#[cfg(feature = "ssr")]
+fn collect_cache_files(
+    dir: &std::path::Path,
+    files: &mut Vec<(std::path::PathBuf, u64, std::time::SystemTime)>,
+    total: &mut u64,
+) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+        if metadata.is_dir() {
+            collect_cache_files(&path, files, total)?;
+            continue;
+        }
+        *total += metadata.len();
+        let evictable = !matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("json" | "partial")
+        );
+        if evictable {
+            files.push((path, metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(())
+}
+
+// A named cache namespace with its own source root and parallelism.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub(crate) struct Tenant {
+    pub(crate) root_file_path: String,
+    pub(crate) semaphore: std::sync::Arc<tokio::sync::Semaphore>,
+}
+
+#[cfg(feature = "ssr")]
+impl Tenant {
+    fn new(root_file_path: String, parallelism: usize) -> Self {
+        Self {
+            root_file_path,
+            semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(parallelism)),
+        }
+    }
+}
+
+/// Builds an [`ImageOptimizer`], see [`ImageOptimizer::builder`].
+///
+/// Runtime settings given here are the initial [`OptimizerConfig`], which can still be changed
+/// with [`ImageOptimizer::update_config`].
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct ImageOptimizerBuilder {
+    api_handler_path: String,
+    root_file_path: String,
+    parallelism: Parallelism,
+    cache_dir: String,
+    blur_template: BlurTemplate,
+    config: OptimizerConfig,
+    storage: Option<std::sync::Arc<dyn crate::CacheBackend>>,
+    sources: Option<std::sync::Arc<dyn crate::ImageSource>>,
+    url_scheme: UrlScheme,
+    base_path: String,
+    base_url: Option<String>,
+    cache_namespace: String,
+    tenants: std::collections::HashMap<String, Tenant>,
+    encoder_threads: Option<usize>,
+    cache_control: Option<String>,
+    warmup_token: Option<std::sync::Arc<str>>,
+    signing_key: Option<std::sync::Arc<[u8]>>,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for ImageOptimizerBuilder {
+    fn default() -> Self {
+        Self {
+            api_handler_path: "/__cache/image".to_string(),
+            root_file_path: "./target/site".to_string(),
+            parallelism: Parallelism::Fixed(1),
+            cache_dir: "cache/image".to_string(),
+            blur_template: BlurTemplate::default(),
+            config: OptimizerConfig::default(),
+            storage: None,
+            sources: None,
+            url_scheme: UrlScheme::default(),
+            base_path: String::new(),
+            base_url: None,
+            cache_namespace: default_cache_namespace(),
+            tenants: Default::default(),
+            encoder_threads: None,
+            cache_control: None,
+            warmup_token: None,
+            signing_key: None,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl ImageOptimizerBuilder {
+    /// Path where the image handler is located in the server router. Defaults to `/__cache/image`.
+    pub fn api_handler_path(mut self, api_handler_path: impl Into<String>) -> Self {
+        self.api_handler_path = api_handler_path.into();
+        self
+    }
+
+    /// Directory sources are read from and images are cached in, usually the Leptos site root.
+    /// Defaults to `./target/site`.
+    pub fn root(mut self, root_file_path: impl Into<String>) -> Self {
+        self.root_file_path = root_file_path.into();
+        self
+    }
+
+    /// Number of images that can be created at once, or [`Parallelism::auto`]. Defaults to 1.
+    pub fn parallelism(mut self, parallelism: impl Into<Parallelism>) -> Self {
+        self.parallelism = parallelism.into();
+        self
+    }
+
+    /// Directory, relative to the root, optimized images and placeholders are written to.
+    /// Defaults to `cache/image`. Must be inside the root, which the handlers serve files from.
+    pub fn cache_dir(mut self, cache_dir: impl Into<String>) -> Self {
+        self.cache_dir = cache_dir.into().trim_matches('/').to_string();
+        self
+    }
+
+    /// See [`OptimizerConfig::default_quality`].
+    pub fn default_quality(mut self, quality: u8) -> Self {
+        self.config.default_quality = quality;
+        self
+    }
+
+    /// Serves images in a single format, see [`OptimizerConfig::formats`].
+    pub fn default_format(mut self, format: OutputFormat) -> Self {
+        self.config.formats = vec![format];
+        self
+    }
+
+    /// See [`OptimizerConfig::formats`].
+    pub fn formats(mut self, formats: impl IntoIterator<Item = OutputFormat>) -> Self {
+        self.config.formats = formats.into_iter().collect();
+        self
+    }
+
+    /// See [`OptimizerConfig::placeholder`].
+    pub fn placeholder(mut self, placeholder: Placeholder) -> Self {
+        self.config.placeholder = placeholder;
+        self
+    }
+
+    /// Overrides the SVG used to wrap blur placeholders.
+    /// See [`BlurTemplate`] for the available placeholders.
+    ///
+    /// Placeholders that were already written to the cache directory are not regenerated.
+    pub fn blur_template(mut self, template: BlurTemplate) -> Self {
+        self.blur_template = template;
+        self
+    }
+
+    /// Sets how `<Image/>` encodes optimized image URLs. Defaults to [`UrlScheme::Query`].
+    /// The handler always accepts both schemes.
+    pub fn url_scheme(mut self, url_scheme: UrlScheme) -> Self {
+        self.url_scheme = url_scheme;
+        self
+    }
+
+    /// Serves the app under a path prefix, e.g. `/app` when deployed behind a reverse proxy at `/app/`.
+    ///
+    /// Image URLs, preload links and the routes added by `ImageCacheRoute` are prefixed with it,
+    /// so the `api_handler_path` stays relative to the app.
+    /// SVG sources, which `<Image/>` renders as-is, are prefixed too.
+    pub fn base_path(mut self, base_path: impl Into<String>) -> Self {
+        self.base_path = normalize_base_path(&base_path.into());
+        self
+    }
+
+    /// Sets the canonical origin of the site, e.g. `https://example.com`.
+    /// Used for absolute image URLs in meta tags, see [`crate::ImageMeta`].
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Isolates the cached files of this optimizer under `<cache dir>/<namespace>/`, `cache/image/<namespace>/` by default,
+    /// so environments sharing a volume or bucket never serve each other's artifacts.
+    ///
+    /// Use the environment name or a git SHA. Defaults to the Leptos environment from `LEPTOS_ENV`, `dev` or `prod`.
+    pub fn cache_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.cache_namespace = normalize_cache_namespace(&namespace.into());
+        self
+    }
+
+    /// Registers a named tenant with its own source root and parallelism.
+    ///
+    /// Images rendered under [`crate::provide_image_tenant`] (or with the `tenant` prop) are read from
+    /// and cached under the tenant's root, so tenants can't access each other's assets.
+    /// The parallelism limits how many images of this tenant are created at once,
+    /// independently of other tenants. All tenants share the same handler.
+    pub fn tenant(
+        mut self,
+        name: impl Into<String>,
+        root_file_path: impl Into<String>,
+        parallelism: usize,
+    ) -> Self {
+        let tenant = Tenant::new(root_file_path.into(), parallelism);
+        self.tenants.insert(name.into(), tenant);
+        self
+    }
+
+    /// Encodes images on `threads` dedicated threads, spawned and initialized right away,
+    /// so the first image after startup doesn't pay for spawning a thread and initializing the encoder.
+    ///
+    /// Without it, images are encoded on the Tokio blocking pool.
+    pub fn encoder_threads(mut self, threads: usize) -> Self {
+        self.encoder_threads = Some(threads);
+        self
+    }
+
+    /// Sets the `Cache-Control` header the handlers serve optimized images with,
+    /// e.g. `"public, max-age=31536000, immutable"`. No header is sent by default.
+    ///
+    /// Image URLs change with their size, quality and format, but not with the contents of the source.
+    /// Only use a long `max-age` if replaced sources get a new path.
+    /// Images rendered with a `cache_control` prop keep their own header.
+    pub fn cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.cache_control = Some(cache_control.into());
+        self
+    }
+
+    /// Sets the bearer token required by the cache warm-up endpoint.
+    /// The endpoint is only mounted when a token is configured, see `ImageCacheRoute::image_cache_warm_route`.
+    pub fn warmup_token(mut self, token: impl Into<String>) -> Self {
+        self.warmup_token = Some(token.into().into());
+        self
+    }
+
+    /// Signs the image URLs rendered on the server with an HMAC of the image spec, and rejects tampered ones.
+    ///
+    /// Without signatures, anyone can request arbitrary sizes and qualities, filling the disk and the CPU.
+    /// Unsigned URLs, like the ones `<Image/>` renders in the browser after a client side navigation,
+    /// are only served if the image was already created, e.g. by a server render or [`crate::cache_app_images`].
+    /// Use a random secret of at least 32 bytes, shared by every instance serving the same cache.
+    pub fn signing_key(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.signing_key = Some(key.as_ref().into());
+        self
+    }
+
+    /// See [`OptimizerConfig::max_dimensions`].
+    pub fn max_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.config.max_dimensions = Some((width, height));
+        self
+    }
+
+    /// See [`OptimizerConfig::quality_range`].
+    pub fn quality_range(mut self, range: std::ops::RangeInclusive<u8>) -> Self {
+        self.config.quality_range = range;
+        self
+    }
+
+    /// See [`OptimizerConfig::clamp_requests`].
+    pub fn clamp_requests(mut self, enabled: bool) -> Self {
+        self.config.clamp_requests = enabled;
+        self
+    }
+
+    /// See [`OptimizerConfig::max_cache_size_bytes`].
+    pub fn max_cache_size_bytes(mut self, bytes: u64) -> Self {
+        self.config.max_cache_size_bytes = Some(bytes);
+        self
+    }
+
+    /// See [`OptimizerConfig::sharpen`].
+    pub fn sharpen(mut self, amount: u8) -> Self {
+        self.config.sharpen = amount;
+        self
+    }
+
+    /// See [`OptimizerConfig::preserve_metadata`].
+    pub fn preserve_metadata(mut self, preserve: bool) -> Self {
+        self.config.preserve_metadata = preserve;
+        self
+    }
+
+    /// See [`OptimizerConfig::no_upscale`].
+    pub fn no_upscale(mut self, enabled: bool) -> Self {
+        self.config.no_upscale = enabled;
+        self
+    }
+
+    /// See [`OptimizerConfig::watermark`].
+    pub fn watermark(mut self, watermark: Watermark) -> Self {
+        self.config.watermark = Some(watermark);
+        self
+    }
+
+    /// Keeps created images in a [`crate::CacheBackend`] too, so instances without a persistent disk
+    /// download them instead of encoding them again. Only the local cache directory is swept,
+    /// and images created with [`ImageOptimizer::optimize_sync`] are only written locally.
+    pub fn cache_backend(mut self, backend: impl crate::CacheBackend + 'static) -> Self {
+        self.storage = Some(std::sync::Arc::new(backend));
+        self
+    }
+
+    /// Loads sources from an [`crate::ImageSource`] instead of the root, e.g. originals kept in object storage.
+    ///
+    /// Each source is loaded once and kept in a `sources` folder of the cache directory, then optimized like a file
+    /// under the root. To replace a source, give it a new path. Remote `http(s)` sources are still fetched directly,
+    /// and [`ImageOptimizer::optimize_sync`] only uses sources that were already loaded.
+    pub fn image_source(mut self, source: impl crate::ImageSource + 'static) -> Self {
+        self.sources = Some(std::sync::Arc::new(source));
+        self
+    }
+
+    /// See [`OptimizerConfig::stale_while_revalidate`].
+    pub fn stale_while_revalidate(mut self, enabled: bool) -> Self {
+        self.config.stale_while_revalidate = enabled;
+        self
+    }
+
+    /// See [`OptimizerConfig::strict`].
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.config.strict = enabled;
+        self
+    }
+
+    /// See [`OptimizerConfig::negotiate_format`].
+    pub fn negotiate_format(mut self, enabled: bool) -> Self {
+        self.config.negotiate_format = enabled;
+        self
+    }
+
+    /// See [`OptimizerConfig::allowed_sources`].
+    pub fn allowed_sources(mut self, sources: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.config.allowed_sources = sources.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Creates the optimizer, loading placeholders cached by a previous run.
+    pub fn build(self) -> ImageOptimizer {
+        let (parallelism, auto_tune) = match self.parallelism {
+            Parallelism::Fixed(parallelism) => (parallelism, None),
+            Parallelism::Auto { memory_budget } => {
+                let max = Parallelism::auto_limit(memory_budget);
+                (
+                    max,
+                    Some(std::sync::Arc::new(std::sync::Mutex::new(AutoTune::new(
+                        max,
+                    )))),
+                )
+            }
+        };
+        let semaphore = tokio::sync::Semaphore::new(parallelism);
+        let semaphore = std::sync::Arc::new(semaphore);
+        let config = std::sync::Arc::new(std::sync::RwLock::new(OptimizerConfig {
+            parallelism,
+            ..self.config
+        }));
+        let optimizer = ImageOptimizer {
+            api_handler_path: self.api_handler_path,
+            root_file_path: self.root_file_path,
+            semaphore,
+            cache: std::sync::Arc::new(dashmap::DashMap::new()),
+            blur_template: self.blur_template,
+            warmup_token: self.warmup_token,
+            signing_key: self.signing_key,
+            tenants: std::sync::Arc::new(self.tenants),
+            url_scheme: self.url_scheme,
+            base_url: self.base_url,
+            base_path: self.base_path,
+            cache_dir: self.cache_dir,
+            cache_namespace: self.cache_namespace,
+            #[cfg(feature = "fetch")]
+            http: remote_client(config.clone()),
+            config,
+            pending_blurs: Default::default(),
+            // Background work runs one image at a time, so it never starves requests.
+            background: std::sync::Arc::new(tokio::sync::Semaphore::new(1)),
+            auto_tune,
+            in_flight: Default::default(),
+            encoder_pool: self
+                .encoder_threads
+                .map(|threads| std::sync::Arc::new(EncoderPool::new(threads.max(1)))),
+            manifest_lock: Default::default(),
+            cache_control: self.cache_control,
+            sweeper: Default::default(),
+            creating: Default::default(),
+            source_dimensions: Default::default(),
+            missing_sources: Default::default(),
+            source_versions: Default::default(),
+            revalidating: Default::default(),
+            known_images: Default::default(),
+            known_originals: Default::default(),
+            canonical_roots: Default::default(),
+            server_overrides: Default::default(),
+            storage: self.storage,
+            sources: self.sources,
+        };
+        for root in std::iter::once(&optimizer.root_file_path).chain(
+            optimizer
+                .tenants
+                .values()
+                .map(|tenant| &tenant.root_file_path),
+        ) {
+            optimizer.resolve_root_sync(root);
+        }
+        optimizer.load_manifest();
+        optimizer
+    }
 }
 
 #[cfg(feature = "ssr")]
@@ -16,19 +1083,417 @@ impl ImageOptimizer {
     /// api_handler_path is the path where the image handler is located in the server router.
     /// Parallelism denotes the number of images that can be created at once.
     /// Useful to limit to prevent overloading the server.
+    /// Pass a number, or [`Parallelism::auto`] to size it from the machine.
+    ///
+    /// See [`ImageOptimizer::builder`] for more settings.
     pub fn new(
         api_handler_path: impl Into<String>,
         root_file_path: impl Into<String>,
+        parallelism: impl Into<Parallelism>,
+    ) -> Self {
+        Self::builder()
+            .api_handler_path(api_handler_path)
+            .root(root_file_path)
+            .parallelism(parallelism)
+            .build()
+    }
+
+    /// Starts configuring an ImageOptimizer.
+    ///
+    /// ```
+    /// use leptos_image::*;
+    ///
+    /// let optimizer = ImageOptimizer::builder()
+    ///     .api_handler_path("/__cache/image")
+    ///     .root("./target/site")
+    ///     .parallelism(4)
+    ///     .default_quality(80)
+    ///     .max_dimensions(4096, 4096)
+    ///     .allowed_sources(["/assets/"])
+    ///     .build();
+    /// assert_eq!(optimizer.config().default_quality, 80);
+    /// ```
+    pub fn builder() -> ImageOptimizerBuilder {
+        ImageOptimizerBuilder::default()
+    }
+
+    /// Returns a snapshot of the current runtime settings.
+    pub fn config(&self) -> OptimizerConfig {
+        self.config
+            .read()
+            .expect("Optimizer config poisoned")
+            .clone()
+    }
+
+    /// Changes runtime settings without restarting the server,
+    /// e.g. to lower quality or concurrency during load spikes.
+    ///
+    /// Lowering the parallelism takes effect as soon as in-flight images finish.
+    /// Changing how images are encoded, like the sharpening or the watermark, creates new files for them
+    /// instead of serving the cached ones.
+    ///
+    /// ```
+    /// use leptos_image::*;
+    ///
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 4);
+    /// optimizer.update_config(|config| {
+    ///     config.default_quality = 60;
+    ///     config.parallelism = 1;
+    /// });
+    /// assert_eq!(optimizer.config().parallelism, 1);
+    /// ```
+    pub fn update_config(&self, update: impl FnOnce(&mut OptimizerConfig)) {
+        let mut config = self.config.write().expect("Optimizer config poisoned");
+        let previous = config.parallelism;
+        update(&mut config);
+        let current = config.parallelism;
+        drop(config);
+
+        if current > previous {
+            self.semaphore.add_permits(current - previous);
+        } else if current < previous {
+            let excess = previous - current;
+            let remaining = excess - self.semaphore.forget_permits(excess);
+            // Permits that are in use are forgotten once released.
+            if remaining > 0 {
+                if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                    let semaphore = self.semaphore.clone();
+                    runtime.spawn(async move {
+                        if let Ok(permits) = semaphore.acquire_many_owned(remaining as u32).await {
+                            permits.forget();
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    pub(crate) fn check_allowed(&self, src: &str) -> Result<(), CreateImageError> {
+        // `src` comes from the URL, it must not climb out of the root with `..`.
+        if !is_remote(src)
+            && std::path::Path::new(src)
+                .components()
+                .any(|component| component == std::path::Component::ParentDir)
+        {
+            return Err(CreateImageError::ForbiddenPath(src.to_string()));
+        }
+        if self
+            .config
+            .read()
+            .expect("Optimizer config poisoned")
+            .is_allowed(src)
+        {
+            Ok(())
+        } else {
+            Err(CreateImageError::SourceNotAllowed(src.to_string()))
+        }
+    }
+
+    fn check_limits(&self, cache_image: &CachedImage) -> Result<(), CreateImageError> {
+        let config = self.config.read().expect("Optimizer config poisoned");
+        if let Some((width, height)) = config.oversized(&cache_image.option) {
+            return Err(CreateImageError::TooLarge(width, height));
+        }
+        match &cache_image.option {
+            CachedImageOption::Resize(resize)
+                if !config.quality_range.contains(&resize.quality) =>
+            {
+                Err(CreateImageError::QualityOutOfRange(resize.quality))
+            }
+            // Each transform is a pass over the whole source.
+            CachedImageOption::Resize(resize) if resize.transforms.len() > MAX_TRANSFORMS => {
+                Err(CreateImageError::TooManyTransforms(resize.transforms.len()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Same as [`ImageOptimizerBuilder::url_scheme`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().url_scheme(..)` instead.")]
+    pub fn with_url_scheme(mut self, url_scheme: UrlScheme) -> Self {
+        self.url_scheme = url_scheme;
+        self
+    }
+
+    /// Same as [`ImageOptimizerBuilder::base_path`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().base_path(..)` instead.")]
+    pub fn with_base_path(mut self, base_path: impl Into<String>) -> Self {
+        self.base_path = normalize_base_path(&base_path.into());
+        self
+    }
+
+    /// Same as [`ImageOptimizerBuilder::cache_namespace`], on an optimizer that's already built.
+    pub fn with_cache_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.cache_namespace = normalize_cache_namespace(&namespace.into());
+        // Placeholders of the previous namespace point to files this optimizer no longer serves.
+        // Clones of the optimizer still serve them, so they keep their own.
+        self.cache = Default::default();
+        self.pending_blurs = Default::default();
+        self.load_manifest();
+        self
+    }
+
+    // Path the image handler is served at, including the base path.
+    pub(crate) fn handler_path(&self) -> String {
+        format!("{}{}", self.base_path, self.api_handler_path)
+    }
+
+    /// Same as [`ImageOptimizerBuilder::base_url`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().base_url(..)` instead.")]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    // Parses an optimized image URL in either scheme.
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
+    pub(crate) fn parse_image_url(&self, url: &str) -> Option<CachedImage> {
+        let (url, _) = split_query_param(url, "sig");
+        let (url, dpr) = split_client_dpr(&url);
+        let image = self.parse_unscaled_image_url(&url)?;
+        let mut image = match dpr {
+            Some(dpr) => self.apply_client_dpr(image, dpr)?,
+            None => image,
+        };
+        let config = self.config.read().expect("Optimizer config poisoned");
+        if config.clamp_requests {
+            config.clamp(&mut image.option);
+        }
+        Some(image)
+    }
+
+    // Parses an image URL without client requested density or signature.
+    fn parse_unscaled_image_url(&self, url: &str) -> Option<CachedImage> {
+        let image = if url.contains('?') {
+            CachedImage::from_url_encoded(url).ok()?
+        } else {
+            let path = url.strip_prefix(self.handler_path().trim_end_matches('/'))?;
+            CachedImage::from_path_encoded(path)?
+        };
+        Some(match self.server_overrides.get(&image) {
+            Some(rendered) => rendered.clone(),
+            None => image,
+        })
+    }
+
+    // Scales a resize to a client requested density, rejecting densities outside the configured range.
+    fn apply_client_dpr(&self, mut image: CachedImage, dpr: u8) -> Option<CachedImage> {
+        let config = self.config.read().expect("Optimizer config poisoned");
+        if dpr == 0 || dpr > config.max_client_dpr {
+            return None;
+        }
+        if let CachedImageOption::Resize(resize) = &mut image.option {
+            let scale = dpr as u32;
+            resize.width = resize.width.checked_mul(scale)?;
+            resize.height = resize.height.checked_mul(scale)?;
+            resize.quality = config.dpr_quality.quality(resize.quality, dpr as f32);
+        }
+        Some(image)
+    }
+
+    /// Same as [`ImageOptimizerBuilder::tenant`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().tenant(..)` instead.")]
+    pub fn with_tenant(
+        mut self,
+        name: impl Into<String>,
+        root_file_path: impl Into<String>,
         parallelism: usize,
     ) -> Self {
-        let semaphore = tokio::sync::Semaphore::new(parallelism);
-        let semaphore = std::sync::Arc::new(semaphore);
-        Self {
-            api_handler_path: api_handler_path.into(),
-            root_file_path: root_file_path.into(),
-            semaphore,
-            cache: std::sync::Arc::new(dashmap::DashMap::new()),
+        let tenant = Tenant::new(root_file_path.into(), parallelism);
+        self.resolve_root_sync(&tenant.root_file_path);
+        std::sync::Arc::make_mut(&mut self.tenants).insert(name.into(), tenant);
+        self
+    }
+
+    // Resolves the source root and semaphore of the image's tenant.
+    pub(crate) fn namespace(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<(&str, &std::sync::Arc<tokio::sync::Semaphore>), CreateImageError> {
+        self.tenant_namespace(cache_image.tenant.as_deref())
+    }
+
+    pub(crate) fn tenant_namespace(
+        &self,
+        tenant: Option<&str>,
+    ) -> Result<(&str, &std::sync::Arc<tokio::sync::Semaphore>), CreateImageError> {
+        match tenant {
+            None => Ok((&self.root_file_path, &self.semaphore)),
+            Some(name) => self
+                .tenants
+                .get(name)
+                .map(|t| (t.root_file_path.as_str(), &t.semaphore))
+                .ok_or_else(|| CreateImageError::UnknownTenant(name.to_string())),
+        }
+    }
+
+    /// Same as [`ImageOptimizerBuilder::encoder_threads`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().encoder_threads(..)` instead.")]
+    pub fn with_encoder_threads(mut self, threads: usize) -> Self {
+        self.encoder_pool = Some(std::sync::Arc::new(EncoderPool::new(threads.max(1))));
+        self
+    }
+
+    /// Same as [`ImageOptimizerBuilder::cache_control`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().cache_control(..)` instead.")]
+    pub fn with_cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.cache_control = Some(cache_control.into());
+        self
+    }
+
+    // The `Cache-Control` header of an image, its own override or the optimizer's default.
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
+    pub(crate) fn cache_control_for<'a>(&'a self, image: &'a CachedImage) -> Option<&'a str> {
+        image
+            .cache_control
+            .as_deref()
+            .or(self.cache_control.as_deref())
+    }
+
+    /// Same as [`ImageOptimizerBuilder::warmup_token`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().warmup_token(..)` instead.")]
+    pub fn with_warmup_token(mut self, token: impl Into<String>) -> Self {
+        self.warmup_token = Some(token.into().into());
+        self
+    }
+
+    /// Same as [`ImageOptimizerBuilder::signing_key`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().signing_key(..)` instead.")]
+    pub fn with_signing_key(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.signing_key = Some(key.as_ref().into());
+        self
+    }
+
+    // Allows creating an image in strict mode, see `OptimizerConfig::strict`.
+    // Settings its URL doesn't carry are remembered, so requests for the image get them.
+    pub(crate) fn allow_image(&self, image: &CachedImage) {
+        let requested = image.without_server_overrides();
+        if requested != *image {
+            self.server_overrides.insert(requested, image.clone());
+        }
+        if self
+            .config
+            .read()
+            .expect("Optimizer config poisoned")
+            .strict
+        {
+            self.known_images.insert(image.clone());
+        }
+    }
+
+    // URL of an image rendered on the server, allowed in strict mode and signed if a signing key is configured.
+    pub(crate) fn rendered_url(&self, url: String, image: &CachedImage) -> String {
+        self.allow_image(image);
+        match &self.signing_key {
+            Some(key) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{url}{separator}sig={}", url_signature(key, image))
+            }
+            None => url,
+        }
+    }
+
+    // URL of an original rendered on the server, allowed in strict mode and signed if a signing key is configured.
+    pub(crate) fn rendered_original_url(&self, url: String, original: &OriginalImage) -> String {
+        let original = original.without_download();
+        let signature = self
+            .signing_key
+            .as_ref()
+            .map(|key| original_signature(key, &original));
+        if self
+            .config
+            .read()
+            .expect("Optimizer config poisoned")
+            .strict
+        {
+            self.known_originals.insert(original);
+        }
+        match signature {
+            Some(signature) => format!("{url}&sig={signature}"),
+            None => url,
+        }
+    }
+
+    // The source file an original URL serves, `None` if the URL isn't one.
+    // Checked like optimized images: allowed sources, signature and strict mode.
+    #[cfg_attr(not(feature = "axum-handler"), allow(dead_code))]
+    pub(crate) async fn original_file(
+        &self,
+        url: &str,
+    ) -> Result<Option<(OriginalImage, crate::ImageBody)>, CreateImageError> {
+        let (url, signature) = split_query_param(url, "sig");
+        let Some(original) = url
+            .split_once('?')
+            .and_then(|(_, query)| serde_qs::from_str::<OriginalImage>(query).ok())
+        else {
+            return Ok(None);
+        };
+        if is_remote(&original.src) || image::ImageFormat::from_path(&original.src).is_err() {
+            return Ok(None);
         }
+        self.check_allowed(&original.src)?;
+        let config = self.config();
+        // Originals would expose the sources without the watermark.
+        if config.watermark.is_some() {
+            return Err(CreateImageError::SourceNotAllowed(original.src));
+        }
+        let key = original.without_download();
+        if let Some(signing_key) = &self.signing_key {
+            let expected = original_signature(signing_key, &key);
+            if !signature
+                .is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes()))
+            {
+                return Err(CreateImageError::InvalidSignature);
+            }
+        }
+        if config.strict && !self.known_originals.contains(&key) {
+            return Err(CreateImageError::UnknownImage(original.src));
+        }
+
+        let (root, _) = self.tenant_namespace(original.tenant.as_deref())?;
+        let path = self.source_path(root, &original.src);
+        self.check_contained(root, &path, &original.src).await?;
+        self.load_source(&original.src, original.tenant.as_deref(), &path)
+            .await?;
+        if !self.source_exists(&path).await {
+            return Err(CreateImageError::SourceNotFound(original.src));
+        }
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let body = crate::ImageBody::File {
+            root: root.to_string(),
+            path: relative.to_string_lossy().to_string(),
+        };
+        Ok(Some((original, body)))
+    }
+
+    // Whether the signature of an image URL is valid, `None` if it isn't signed.
+    fn signature_valid(&self, key: &[u8], url: &str) -> Option<bool> {
+        let (_, signature) = split_query_param(url, "sig");
+        let signature = signature?;
+        let valid = self.rendered_image(url).is_some_and(|image| {
+            constant_time_eq(url_signature(key, &image).as_bytes(), signature.as_bytes())
+        });
+        Some(valid)
+    }
+
+    // The image of a URL as rendered, without client requested density or signature.
+    fn rendered_image(&self, url: &str) -> Option<CachedImage> {
+        let (url, _) = split_query_param(url, "sig");
+        let (url, _) = split_client_dpr(&url);
+        self.parse_unscaled_image_url(&url)
+    }
+
+    /// Same as [`ImageOptimizerBuilder::blur_template`], on an optimizer that's already built.
+    #[deprecated(note = "Use `ImageOptimizer::builder().blur_template(..)` instead.")]
+    pub fn with_blur_template(mut self, template: BlurTemplate) -> Self {
+        self.blur_template = template;
+        self
     }
 
     /// Creates a context function to provide the optimizer.
@@ -77,125 +1542,1862 @@ impl ImageOptimizer {
         }
     }
 
-    pub(crate) async fn create_image(
+    /// Creates every variant of an [`crate::ImageSet`] ahead of time, so the first visitor is served from cache.
+    /// `width` and `height` must match the props given to `<Image/>` for the generated files to be reused.
+    pub async fn create_image_set(
+        &self,
+        set: &crate::ImageSet,
+        width: u32,
+        height: u32,
+        quality: u8,
+    ) -> crate::CacheReport {
+        let start = std::time::Instant::now();
+        let mut report = crate::CacheReport::default();
+        for image in set.variants(width, height, quality) {
+            report.record(self, &image).await;
+        }
+        report.elapsed = start.elapsed();
+        report
+    }
+
+    /// Creates an optimized image on the calling thread, without requiring an async runtime.
+    /// Useful in build scripts, CLIs and non-async servers.
+    ///
+    /// Returns `true` if the image was created and `false` if it already existed in the cache.
+    ///
+    /// ```no_run
+    /// use leptos_image::*;
+    ///
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+    /// let image = CachedImage::resize("/cute_ferris.png", 750, 500, 85);
+    /// optimizer.optimize_sync(&image).expect("Failed to optimize image");
+    /// ```
+    pub fn optimize_sync(&self, cache_image: &CachedImage) -> Result<bool, CreateImageError> {
+        self.check_allowed(&cache_image.src)?;
+        if is_svg(&cache_image.src) {
+            return Ok(false);
+        }
+        let (source_path, save_path) = self.source_and_save_paths_sync(cache_image)?;
+
+        let complete = std::fs::metadata(&save_path).is_ok_and(|m| m.len() > 0);
+        if (complete && self.source_unchanged_sync(cache_image, &source_path, &save_path))
+            || self.migrate_legacy_file_sync(cache_image, &save_path)
+        {
+            return Ok(false);
+        }
+        let _guard = self.in_flight.start()?;
+        let version = SourceVersion::of(cache_image, &source_path);
+        create_optimized_image(
+            cache_image.option.clone(),
+            source_path,
+            save_path.clone(),
+            &self.encode_settings(cache_image),
+        )?;
+        std::fs::write(
+            sidecar_path(&save_path),
+            sidecar(cache_image, version.as_ref()),
+        )?;
+        self.record_source_version(&save_path, version);
+
+        if let CachedImageOption::Blur(_) = cache_image.option {
+            let svg = std::fs::read_to_string(save_path)?;
+            self.cache.insert(cache_image.without_cache_control(), svg);
+        }
+        Ok(true)
+    }
+
+    /// Re-encodes an image and checks that it matches the file in the cache, e.g. in CI to verify
+    /// that pre-built images are up to date with their sources. Requires [`OptimizerConfig::deterministic`].
+    ///
+    /// Returns `false` if the cached file differs, and an error if it doesn't exist.
+    pub fn verify_sync(&self, cache_image: &CachedImage) -> Result<bool, CreateImageError> {
+        self.check_allowed(&cache_image.src)?;
+        let (source_path, save_path) = self.source_and_save_paths_sync(cache_image)?;
+
+        let cached = std::fs::read(save_path)?;
+        let encoded = encode_optimized_image(
+            cache_image.option.clone(),
+            source_path,
+            &EncodeSettings {
+                deterministic: true,
+                ..self.encode_settings(cache_image)
+            },
+        )?;
+        Ok(cached == encoded)
+    }
+
+    /// Stops accepting new images and waits for the ones being encoded to be written,
+    /// so stopping the server never leaves partial files in the cache.
+    ///
+    /// Returns `false` if encodes were still running when the timeout elapsed.
+    /// Images requested after the call fail with [`CreateImageError::ShuttingDown`].
+    ///
+    /// ```no_run
+    /// use leptos_image::*;
+    /// use std::time::Duration;
+    ///
+    /// # async fn serve(
+    /// #     router: axum::Router,
+    /// #     optimizer: ImageOptimizer,
+    /// #     shutdown_signal: impl std::future::Future<Output = ()> + Send + 'static,
+    /// # ) {
+    /// let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await.unwrap();
+    /// axum::serve(listener, router.into_make_service())
+    ///     .with_graceful_shutdown(shutdown_signal)
+    ///     .await
+    ///     .unwrap();
+    /// optimizer.shutdown(Duration::from_secs(10)).await;
+    /// # }
+    /// ```
+    pub async fn shutdown(&self, timeout: std::time::Duration) -> bool {
+        self.in_flight
+            .closed
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let drained = async {
+            loop {
+                let notified = self.in_flight.drained.notified();
+                if self
+                    .in_flight
+                    .count
+                    .load(std::sync::atomic::Ordering::SeqCst)
+                    == 0
+                {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        let drained = tokio::time::timeout(timeout, drained).await.is_ok();
+        if !drained {
+            tracing::warn!("Image optimizer shut down with encodes still running.");
+        }
+        drained
+    }
+
+    fn encode_settings(&self, cache_image: &CachedImage) -> EncodeSettings {
+        let config = self.config();
+        let (resize_filter, sharpen, preserve_metadata, no_upscale) = match &cache_image.option {
+            CachedImageOption::Resize(resize) => (
+                resize
+                    .filter
+                    .unwrap_or_else(|| config.resize_filter_for(resize.width)),
+                resize.sharpen.unwrap_or(config.sharpen),
+                resize.metadata.unwrap_or(config.preserve_metadata),
+                resize.no_upscale.unwrap_or(config.no_upscale),
+            ),
+            CachedImageOption::Blur(_) => (config.resize_filter, 0, false, false),
+        };
+        let watermark = match &cache_image.option {
+            CachedImageOption::Resize(resize) if resize.watermark != Some(false) => {
+                config.watermark.map(|watermark| {
+                    let path = path_from_segments(vec![&self.root_file_path, &watermark.src]);
+                    (path, watermark)
+                })
+            }
+            _ => None,
+        };
+        EncodeSettings {
+            watermark,
+            blur_template: self.blur_template.clone(),
+            deterministic: config.deterministic,
+            resize_filter,
+            sharpen,
+            preserve_metadata,
+            no_upscale,
+        }
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(src = %cache_image.src, variant = %cache_image, cache_hit = tracing::field::Empty)
+    )]
+    pub(crate) async fn create_image(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<bool, CreateImageError> {
+        let result = self.find_or_create_image(cache_image).await;
+        if let Ok(created) = result {
+            tracing::Span::current().record("cache_hit", !created);
+            crate::telemetry::cache_lookup(!created);
+        }
+        result
+    }
+
+    async fn find_or_create_image(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<bool, CreateImageError> {
+        self.check_allowed(&cache_image.src)?;
+        if is_svg(&cache_image.src) {
+            return Ok(false);
+        }
+        self.check_limits(cache_image)?;
+
+        let (absolute_src_path, save_path) = self.source_and_save_paths(cache_image).await?;
+
+        let complete = cache_file_complete(&save_path).await;
+        // Files being revalidated are served stale without checking their source again.
+        let serve_cached = complete
+            && (self.revalidating.contains(&save_path)
+                || self
+                    .source_unchanged(cache_image, &absolute_src_path, &save_path)
+                    .await);
+        if serve_cached || self.migrate_legacy_file(cache_image, &save_path).await {
+            return Ok(false);
+        }
+        self.load_source(
+            &cache_image.src,
+            cache_image.tenant.as_deref(),
+            &absolute_src_path,
+        )
+        .await?;
+        if !is_remote(&cache_image.src) && !self.source_exists(&absolute_src_path).await {
+            Err(CreateImageError::SourceNotFound(cache_image.src.clone()))
+        } else if complete && self.config().stale_while_revalidate {
+            self.revalidate(cache_image.clone(), absolute_src_path, save_path);
+            Ok(false)
+        } else {
+            self.create_coalesced(cache_image, absolute_src_path, save_path)
+                .await
+        }
+    }
+
+    // Encodes an image, unless a concurrent request for the same file already did.
+    async fn create_coalesced(
+        &self,
+        cache_image: &CachedImage,
+        absolute_src_path: std::path::PathBuf,
+        save_path: std::path::PathBuf,
+    ) -> Result<bool, CreateImageError> {
+        let lock = self.creating.entry(save_path.clone()).or_default().clone();
+        let result = {
+            let _creating = lock.lock().await;
+            if self
+                .cache_hit(cache_image, &absolute_src_path, &save_path)
+                .await
+                || (self.restore(cache_image, &save_path).await
+                    && self
+                        .source_unchanged(cache_image, &absolute_src_path, &save_path)
+                        .await)
+            {
+                Ok(false)
+            } else {
+                self.encode_image(cache_image, absolute_src_path, save_path.clone())
+                    .await
+            }
+        };
+        self.creating
+            .remove_if(&save_path, |_, l| std::sync::Arc::ptr_eq(l, &lock));
+        result
+    }
+
+    // Copies a file missing from the local cache directory from the cache backend, e.g. on a fresh container.
+    async fn restore(&self, cache_image: &CachedImage, save_path: &std::path::Path) -> bool {
+        let Some(storage) = &self.storage else {
+            return false;
+        };
+        // A complete local file is stale, the backend holds the same one.
+        if cache_file_complete(save_path).await {
+            return false;
+        }
+        let key = self.get_file_path(cache_image);
+        let bytes = match storage.get(&key).await {
+            Ok(Some(bytes)) if !bytes.is_empty() => bytes,
+            Ok(_) => return false,
+            Err(e) => {
+                tracing::warn!("Failed to download {key} from the cache backend: {e}");
+                return false;
+            }
+        };
+        let sidecar_key = sidecar_path(std::path::Path::new(&key));
+        let sidecar = match storage.get(&sidecar_key.to_string_lossy()).await {
+            Ok(Some(sidecar)) => sidecar,
+            _ => sidecar(cache_image, None),
+        };
+        let restored = match write_cache_file(save_path, bytes).await {
+            Ok(_) => write_cache_file(&sidecar_path(save_path), sidecar).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = restored {
+            tracing::warn!("Failed to restore {key} from the cache backend: {e}");
+            return false;
+        }
+        self.source_versions.remove(save_path);
+        tracing::debug!("Restored image {cache_image} from the cache backend");
+        true
+    }
+
+    // Copies a created file to the cache backend, without delaying the response.
+    fn upload(&self, cache_image: &CachedImage, bytes: Vec<u8>, sidecar: Vec<u8>) {
+        let (Some(storage), Ok(runtime)) = (&self.storage, tokio::runtime::Handle::try_current())
+        else {
+            return;
+        };
+        let storage = storage.clone();
+        let key = self.get_file_path(cache_image);
+        runtime.spawn(async move {
+            let sidecar_key = sidecar_path(std::path::Path::new(&key));
+            let uploaded = match storage.put(&key, bytes).await {
+                Ok(_) => storage.put(&sidecar_key.to_string_lossy(), sidecar).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = uploaded {
+                tracing::warn!("Failed to upload {key} to the cache backend: {e}");
+            }
+        });
+    }
+
+    // Creates a stale file again in the background, while requests keep being served the stale one.
+    fn revalidate(
+        &self,
+        cache_image: CachedImage,
+        absolute_src_path: std::path::PathBuf,
+        save_path: std::path::PathBuf,
+    ) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if !self.revalidating.insert(save_path.clone()) {
+            return;
+        }
+        tracing::debug!("Serving stale image {cache_image} while it is created again");
+
+        let optimizer = self.clone();
+        runtime.spawn(async move {
+            let _permit = optimizer.background.acquire().await;
+            let result = optimizer
+                .create_coalesced(&cache_image, absolute_src_path, save_path.clone())
+                .await;
+            let result = match (result, &cache_image.option) {
+                (Ok(true), CachedImageOption::Blur(_)) => optimizer.load_blur(&cache_image).await,
+                (result, _) => result.map(|_| ()),
+            };
+            if let Err(e) = result {
+                tracing::error!("Failed to revalidate image {}: {:?}", cache_image, e);
+            }
+            optimizer.revalidating.remove(&save_path);
+        });
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(
+            source = %absolute_src_path.display(),
+            encode_ms = tracing::field::Empty,
+            input_bytes = tracing::field::Empty,
+            output_bytes = tracing::field::Empty,
+        )
+    )]
+    async fn encode_image(
+        &self,
+        cache_image: &CachedImage,
+        absolute_src_path: std::path::PathBuf,
+        save_path: std::path::PathBuf,
+    ) -> Result<bool, CreateImageError> {
+        let (_, semaphore) = self.namespace(cache_image)?;
+        // Downloaded before taking an encode slot, so slow remote hosts don't starve local images.
+        if is_remote(&cache_image.src) {
+            self.download_coalesced(&cache_image.src, &absolute_src_path)
+                .await?;
+        }
+        let waiting = std::time::Instant::now();
+        let _permit = semaphore
+            .acquire()
+            .await
+            .expect("Failed to acquire semaphore");
+        crate::telemetry::semaphore_waited(waiting.elapsed());
+        let guard = self.in_flight.start()?;
+        let encode = {
+            let option = cache_image.option.clone();
+            let settings = self.encode_settings(cache_image);
+            let cache_image = cache_image.clone();
+            let span = tracing::Span::current();
+            // The version is read before encoding, so a source changed mid-encode is encoded again.
+            move || {
+                let _span = span.enter();
+                let version = SourceVersion::of(&cache_image, &absolute_src_path);
+                let input_bytes = std::fs::metadata(&absolute_src_path).map_or(0, |m| m.len());
+                encode_optimized_image(option, absolute_src_path, &settings)
+                    .map(|bytes| (bytes, version, input_bytes))
+            }
+        };
+        let encoder_pool = self.encoder_pool.clone();
+        let optimizer = self.clone();
+        let spec = cache_image.clone();
+
+        let started = std::time::Instant::now();
+        // Spawned, so the file is still written if the request is dropped mid-encode.
+        let task = tokio::spawn(async move {
+            let _guard = guard;
+            let (bytes, version, input_bytes) = match encoder_pool {
+                Some(pool) => pool.run(encode).await?,
+                None => tokio::task::spawn_blocking(encode).await?,
+            }?;
+            let output_bytes = bytes.len() as u64;
+            let sidecar = sidecar(&spec, version.as_ref());
+            write_cache_file(&save_path, bytes.clone()).await?;
+            write_cache_file(&sidecar_path(&save_path), sidecar.clone()).await?;
+            optimizer.record_source_version(&save_path, version);
+            optimizer.upload(&spec, bytes, sidecar);
+            Ok((input_bytes, output_bytes))
+        });
+        let result = match task.await {
+            Err(join_error) => Err(CreateImageError::JoinError(join_error)),
+            Ok(Err(err)) => Err(err),
+            Ok(Ok((input_bytes, output_bytes))) => {
+                crate::telemetry::encoded(started.elapsed());
+                let encode_ms = started.elapsed().as_millis() as u64;
+                let span = tracing::Span::current();
+                span.record("encode_ms", encode_ms);
+                span.record("input_bytes", input_bytes);
+                span.record("output_bytes", output_bytes);
+                tracing::debug!(encode_ms, input_bytes, output_bytes, "Encoded image");
+                Ok(true)
+            }
+        };
+        drop(_permit);
+        if let Ok(true) = result {
+            self.schedule_sweep();
+        }
+
+        if let (Some(auto_tune), CachedImageOption::Resize(resize), None) =
+            (&self.auto_tune, &cache_image.option, &cache_image.tenant)
+        {
+            let pixels = resize.width as u64 * resize.height as u64;
+            let tuned = auto_tune
+                .lock()
+                .expect("Auto tune poisoned")
+                .record(started.elapsed(), pixels);
+            if let Some(parallelism) = tuned {
+                tracing::debug!("Auto tuned image parallelism to {parallelism}");
+                self.update_config(|config| config.parallelism = parallelism);
+            }
+        }
+        result
+    }
+
+    /// Deletes the least recently served images until the cache directory fits
+    /// [`OptimizerConfig::max_cache_size_bytes`], returning the number of bytes freed.
+    ///
+    /// Runs automatically after new images are created. Does nothing without a budget.
+    pub async fn sweep_cache(&self) -> Result<u64, CreateImageError> {
+        let Some(budget) = self.config().max_cache_size_bytes else {
+            return Ok(0);
+        };
+        let mut roots = vec![self.root_file_path.clone()];
+        roots.extend(self.tenants.values().map(|t| t.root_file_path.clone()));
+
+        let mut freed = 0;
+        for root in roots {
+            let sweeper = self.sweeper.clone();
+            let cache = self.cache.clone();
+            let dir = path_from_segments(vec![&root, &self.cache_dir]);
+            freed +=
+                tokio::task::spawn_blocking(move || sweeper.sweep(&dir, budget, &cache)).await??;
+        }
+        if freed > 0 {
+            tracing::debug!("Evicted {freed} bytes of cached images");
+        }
+        Ok(freed)
+    }
+
+    // Sweeps in the background, one sweep at a time.
+    fn schedule_sweep(&self) {
+        use std::sync::atomic::Ordering;
+        if self.config().max_cache_size_bytes.is_none()
+            || self.sweeper.running.swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+        let optimizer = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = optimizer.sweep_cache().await {
+                tracing::warn!("Failed to sweep image cache: {e}");
+            }
+            optimizer.sweeper.running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // Whether a local source exists. Missing sources are remembered for a while,
+    // so repeated requests for a typo in `src` don't hit the disk.
+    async fn source_exists(&self, path: &std::path::Path) -> bool {
+        const MISSING_MAX: usize = 10_000;
+
+        if self.recently_missing(path) {
+            return false;
+        }
+        if file_exists(path).await {
+            self.missing_sources.remove(path);
+            return true;
+        }
+        if self.missing_sources.len() >= MISSING_MAX {
+            self.missing_sources
+                .retain(|_, since| since.elapsed() < MISSING_SOURCE_TTL);
+        }
+        self.missing_sources
+            .insert(path.to_path_buf(), std::time::Instant::now());
+        false
+    }
+
+    fn recently_missing(&self, path: &std::path::Path) -> bool {
+        self.missing_sources
+            .get(path)
+            .is_some_and(|since| since.elapsed() < MISSING_SOURCE_TTL)
+    }
+
+    // Copies a source from the configured `ImageSource` into the cache directory, so it is only loaded once.
+    async fn load_source(
+        &self,
+        src: &str,
+        tenant: Option<&str>,
+        path: &std::path::Path,
+    ) -> Result<(), CreateImageError> {
+        let Some(sources) = &self.sources else {
+            return Ok(());
+        };
+        if is_remote(src) || self.recently_missing(path) || file_exists(path).await {
+            return Ok(());
+        }
+        tracing::debug!("Loading source {src}");
+        match sources.load(src, tenant).await? {
+            Some(bytes) => write_cache_file(path, bytes).await,
+            // Recorded as missing by `source_exists`.
+            None => Ok(()),
+        }
+    }
+
+    // Downloads a remote source next to the local ones, so it is only fetched once.
+    #[cfg(feature = "fetch")]
+    async fn fetch_remote(
+        &self,
+        url: &str,
+        path: &std::path::Path,
+    ) -> Result<(), CreateImageError> {
+        tracing::debug!("Fetching remote image {url}");
+        let mut response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())?;
+        // Redirects to domains that aren't allowed are not followed.
+        if response.status().is_redirection() {
+            return Err(CreateImageError::SourceNotAllowed(url.to_string()));
+        }
+        let is_image = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("image/"));
+        if !is_image {
+            return Err(CreateImageError::SourceNotAllowed(url.to_string()));
+        }
+        if response
+            .content_length()
+            .is_some_and(|length| length > MAX_REMOTE_BYTES)
+        {
+            return Err(CreateImageError::RemoteTooLarge(url.to_string()));
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (bytes.len() + chunk.len()) as u64 > MAX_REMOTE_BYTES {
+                return Err(CreateImageError::RemoteTooLarge(url.to_string()));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        write_cache_file(path, bytes).await
+    }
+
+    // Downloads a remote source once, however many of its variants are created at the same time.
+    async fn download_coalesced(
+        &self,
+        url: &str,
+        path: &std::path::Path,
+    ) -> Result<(), CreateImageError> {
+        let lock = self.creating.entry(path.to_path_buf()).or_default().clone();
+        let result = {
+            let _downloading = lock.lock().await;
+            if file_exists(path).await {
+                Ok(())
+            } else {
+                self.fetch_remote(url, path).await
+            }
+        };
+        self.creating
+            .remove_if(path, |_, l| std::sync::Arc::ptr_eq(l, &lock));
+        result
+    }
+
+    #[cfg(not(feature = "fetch"))]
+    async fn fetch_remote(
+        &self,
+        _url: &str,
+        _path: &std::path::Path,
+    ) -> Result<(), CreateImageError> {
+        Err(CreateImageError::FeatureDisabled("fetch"))
+    }
+
+    /// Reads the dimensions, format and file size of a source image from its header, without decoding it.
+    ///
+    /// Results are kept in memory, so probing a source again is free. Pages rendered afterwards
+    /// use them to set the true `width` and `height` of `<Image/>`.
+    ///
+    /// ```
+    /// use leptos_image::*;
+    ///
+    /// let optimizer = ImageOptimizer::builder().root("./example/start-axum/public").build();
+    /// let info = optimizer.probe("/cute_ferris.png").unwrap();
+    /// assert_eq!(info.format, image::ImageFormat::Png);
+    /// assert!(info.width > 0 && info.height > 0);
+    /// ```
+    pub fn probe(&self, src: &str) -> Result<SourceInfo, CreateImageError> {
+        self.probe_source(src, None)
+    }
+
+    pub(crate) fn probe_source(
+        &self,
+        src: &str,
+        tenant: Option<&str>,
+    ) -> Result<SourceInfo, CreateImageError> {
+        let key = (tenant.map(str::to_string), src.to_string());
+        if let Some(info) = self.source_dimensions.get(&key) {
+            return Ok(*info);
+        }
+        self.check_allowed(src)?;
+        let (root, _) = self.tenant_namespace(tenant)?;
+        let path = self.source_path(root, src);
+        self.check_contained_sync(root, &path, src)?;
+        let info = probe_path(&path).map_err(|err| match err {
+            CreateImageError::IOError(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                CreateImageError::SourceNotFound(src.to_string())
+            }
+            err => err,
+        })?;
+        self.source_dimensions.insert(key, info);
+        Ok(info)
+    }
+
+    // Probes a source so `<Image/>` can emit the dimensions of the resized image, off the async runtime.
+    // Sources that can't be read are skipped.
+    pub(crate) async fn record_source_dimensions(&self, src: &str, tenant: Option<&str>) {
+        let key = (tenant.map(str::to_string), src.to_string());
+        if is_svg(src) || self.source_dimensions.contains_key(&key) {
+            return;
+        }
+        let optimizer = self.clone();
+        let _ = tokio::task::spawn_blocking(move || {
+            let (tenant, src) = key;
+            optimizer.probe_source(&src, tenant.as_deref())
+        })
+        .await;
+    }
+
+    // Generates a blur placeholder in the background and adds it to the cache,
+    // so pages rendered afterwards can inline it. Does nothing if it is already queued.
+    #[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+    pub(crate) fn enqueue_blur(&self, cache_image: CachedImage) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self.cache.contains_key(&cache_image) || !self.pending_blurs.insert(cache_image.clone())
+        {
+            return;
+        }
+
+        let optimizer = self.clone();
+        runtime.spawn(async move {
+            let _permit = optimizer.background.acquire().await;
+            let result = match optimizer.create_image(&cache_image).await {
+                Ok(_) => optimizer.load_blur(&cache_image).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                tracing::error!("Failed to create blur image {}: {:?}", cache_image, e);
+            }
+            optimizer.pending_blurs.remove(&cache_image);
+        });
+    }
+
+    // Reads a created blur placeholder into the in-memory cache.
+    #[cfg_attr(
+        not(any(
+            feature = "blur-placeholder",
+            feature = "axum-handler",
+            feature = "actix"
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) async fn load_blur(
         &self,
         cache_image: &CachedImage,
-    ) -> Result<bool, CreateImageError> {
-        let root = self.root_file_path.as_str();
+    ) -> Result<(), CreateImageError> {
+        let path = self.get_file_path_from_root(cache_image)?;
+        let svg = tokio::fs::read_to_string(path).await?;
+        self.cache.insert(cache_image.without_cache_control(), svg);
+        tracing::debug!("Added image to cache (size {})", self.cache.len());
+        self.save_manifest().await
+    }
+
+    // Manifest of the in-memory cache, so placeholders are available right after a restart.
+    fn manifest_path(&self) -> std::path::PathBuf {
+        path_from_segments(vec![
+            &self.root_file_path,
+            &self.cache_dir,
+            &self.cache_namespace,
+            "manifest.json",
+        ])
+    }
+
+    fn load_manifest(&self) {
+        let path = self.manifest_path();
+        let manifest = match std::fs::read_to_string(&path) {
+            Ok(manifest) => manifest,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                tracing::warn!("Failed to read image manifest {}: {e}", path.display());
+                return;
+            }
+        };
+        match serde_json::from_str::<Vec<(CachedImage, String)>>(&manifest) {
+            Ok(entries) => {
+                tracing::debug!("Loaded {} images from manifest", entries.len());
+                for (image, value) in entries {
+                    // Placeholders of changed sources are dropped, so they are created again when rendered.
+                    let stale = self.source_and_save_paths_sync(&image).is_ok_and(
+                        |(source_path, save_path)| {
+                            !self.source_unchanged_sync(&image, &source_path, &save_path)
+                        },
+                    );
+                    if !stale {
+                        self.cache.insert(image, value);
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Ignoring invalid image manifest {}: {e}", path.display()),
+        }
+    }
+
+    async fn save_manifest(&self) -> Result<(), CreateImageError> {
+        let _lock = self.manifest_lock.lock().await;
+        let entries: Vec<(CachedImage, String)> = self
+            .cache
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let manifest = serde_json::to_vec(&entries).map_err(std::io::Error::from)?;
+        write_cache_file(&self.manifest_path(), manifest).await
+    }
+
+    // Creates the image of an optimized image URL if needed, for the framework handlers.
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
+    pub(crate) async fn cached_file(
+        &self,
+        url: &str,
+        accept: Option<&str>,
+    ) -> Result<Option<CachedFile>, CreateImageError> {
+        let Some(image) = self.parse_image_url(url) else {
+            return Ok(None);
+        };
+
+        // SVG sources are served unconverted, from their own path.
+        if is_svg(&image.src) {
+            self.check_allowed(&image.src)?;
+            if is_remote(&image.src) {
+                return Ok(None);
+            }
+            let (root, _) = self.namespace(&image)?;
+            self.check_contained(
+                root,
+                &path_from_segments(vec![root, &image.src]),
+                &image.src,
+            )
+            .await?;
+            return Ok(Some(CachedFile {
+                path: image.src.trim_start_matches('/').to_string(),
+                root: root.to_string(),
+                image,
+                negotiated: false,
+                generation: None,
+            }));
+        }
+
+        if let Some(key) = &self.signing_key {
+            match self.signature_valid(key, url) {
+                Some(true) => {}
+                None if cache_file_complete(&self.unchecked_paths(&image)?.1).await => {}
+                _ => return Err(CreateImageError::InvalidSignature),
+            }
+        }
+        if self
+            .config
+            .read()
+            .expect("Optimizer config poisoned")
+            .strict
+            && !self
+                .rendered_image(url)
+                .is_some_and(|rendered| self.known_images.contains(&rendered))
+            && !cache_file_complete(&self.unchecked_paths(&image)?.1).await
         {
-            let option = if let CachedImageOption::Resize(_) = cache_image.option {
-                "Resize"
-            } else {
-                "Blur"
-            };
-            tracing::debug!("Creating {option} image for {}", &cache_image.src);
+            return Err(CreateImageError::UnknownImage(image.to_string()));
+        }
+
+        // Other formats of an allowed image are allowed too, so negotiation comes after the checks.
+        let negotiate = self
+            .config
+            .read()
+            .expect("Optimizer config poisoned")
+            .negotiate_format;
+        let lossless = match &image.option {
+            CachedImageOption::Resize(resize)
+                if negotiate && resize.format == OutputFormat::WebP =>
+            {
+                Some(resize.lossless)
+            }
+            _ => None,
+        };
+        let negotiated = lossless.is_some();
+        let image = match (accept, lossless) {
+            (Some(accept), Some(lossless)) => {
+                let format = OutputFormat::negotiate(accept, &image.src, lossless);
+                image.with_format(format)
+            }
+            _ => image,
+        };
+
+        let started = std::time::Instant::now();
+        let result = self.create_image(&image).await;
+        if let Ok(true) = result {
+            tracing::info!("Created Image: {}", image);
         }
+        let generation = result?.then(|| started.elapsed());
 
-        let relative_path_created = self.get_file_path(&cache_image);
+        let path = self.get_file_path(&image);
+        let (root, _) = self.namespace(&image)?;
+        let root = root.to_string();
+        self.sweeper.served(path_from_segments(vec![&root, &path]));
 
-        let save_path = path_from_segments(vec![root, &relative_path_created]);
-        let absolute_src_path = path_from_segments(vec![root, &cache_image.src]);
+        self.add_file_to_cache(&image).await;
 
-        if file_exists(&save_path).await {
-            Ok(false)
-        } else {
-            let _ = self
-                .semaphore
-                .acquire()
-                .await
-                .expect("Failed to acquire semaphore");
-            let task = tokio::task::spawn_blocking({
-                let option = cache_image.option.clone();
-                move || create_optimized_image(option, absolute_src_path, save_path)
-            });
+        Ok(Some(CachedFile {
+            path,
+            root,
+            image,
+            negotiated,
+            generation,
+        }))
+    }
 
-            match task.await {
-                Err(join_error) => Err(CreateImageError::JoinError(join_error)),
-                Ok(Err(err)) => Err(err),
-                Ok(Ok(_)) => Ok(true),
+    // When the image is created, it will be added to the cache.
+    // Mostly helpful for dev server startup.
+    pub(crate) async fn add_file_to_cache(&self, image: &CachedImage) {
+        if let CachedImageOption::Blur(_) = image.option {
+            if !self.cache.contains_key(&image.without_cache_control()) {
+                if let Err(e) = self.load_blur(image).await {
+                    tracing::error!("Failed to read image [{}] with error: {:?}", image, e);
+                }
             }
         }
     }
 
-    #[cfg(feature = "ssr")]
-    pub(crate) fn get_file_path_from_root(&self, cache_image: &CachedImage) -> String {
-        let path = path_from_segments(vec![
-            self.root_file_path.as_ref(),
-            &self.get_file_path(cache_image),
-        ]);
-        path.as_path().to_string_lossy().to_string()
+    // An image held in memory, so it can be served without the filesystem.
+    // Only blur placeholders are kept in memory for now.
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
+    pub(crate) fn memory_image(&self, url: &str) -> Option<(CachedImage, String)> {
+        let image = self.parse_image_url(url)?;
+        let svg = self.cache.get(&image.without_cache_control())?.clone();
+        Some((image, svg))
+    }
+
+    // Absolute paths of the source image and of the optimized image in the cache.
+    async fn source_and_save_paths(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<(std::path::PathBuf, std::path::PathBuf), CreateImageError> {
+        let (root, _) = self.namespace(cache_image)?;
+        let (source_path, save_path) = self.unchecked_paths(cache_image)?;
+        self.check_contained(root, &source_path, &cache_image.src)
+            .await?;
+        Ok((source_path, save_path))
+    }
+
+    fn source_and_save_paths_sync(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<(std::path::PathBuf, std::path::PathBuf), CreateImageError> {
+        let (root, _) = self.namespace(cache_image)?;
+        let (source_path, save_path) = self.unchecked_paths(cache_image)?;
+        self.check_contained_sync(root, &source_path, &cache_image.src)?;
+        Ok((source_path, save_path))
+    }
+
+    // Paths of the source and cached file, without checking the source stays inside the root.
+    fn unchecked_paths(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<(std::path::PathBuf, std::path::PathBuf), CreateImageError> {
+        let (root, _) = self.namespace(cache_image)?;
+        let save_path = path_from_segments(vec![root, &self.get_file_path(cache_image)]);
+        Ok((self.source_path(root, &cache_image.src), save_path))
+    }
+
+    // Rejects a source resolving outside of its root, e.g. through a symlink.
+    // Missing sources can't be resolved, they are reported as not found later on.
+    async fn check_contained(
+        &self,
+        root: &str,
+        path: &std::path::Path,
+        src: &str,
+    ) -> Result<(), CreateImageError> {
+        let root = match self.canonical_roots.get(root) {
+            Some(canonical) => canonical.clone(),
+            None => match tokio::fs::canonicalize(root).await {
+                Ok(canonical) => {
+                    self.canonical_roots
+                        .insert(root.to_string(), canonical.clone());
+                    canonical
+                }
+                Err(_) => return Ok(()),
+            },
+        };
+        match tokio::fs::canonicalize(path).await {
+            Ok(path) => contained(&root, &path, src),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn check_contained_sync(
+        &self,
+        root: &str,
+        path: &std::path::Path,
+        src: &str,
+    ) -> Result<(), CreateImageError> {
+        let (Some(root), Ok(path)) = (self.resolve_root_sync(root), std::fs::canonicalize(path))
+        else {
+            return Ok(());
+        };
+        contained(&root, &path, src)
+    }
+
+    // Canonical path of a root, resolved on the first call.
+    fn resolve_root_sync(&self, root: &str) -> Option<std::path::PathBuf> {
+        if let Some(canonical) = self.canonical_roots.get(root) {
+            return Some(canonical.clone());
+        }
+        let canonical = std::fs::canonicalize(root).ok()?;
+        self.canonical_roots
+            .insert(root.to_string(), canonical.clone());
+        Some(canonical)
+    }
+
+    // Local path of a source. Sources loaded from an `ImageSource` are kept in the cache directory.
+    fn source_path(&self, root: &str, src: &str) -> std::path::PathBuf {
+        if self.sources.is_some() && !is_remote(src) {
+            path_from_segments(vec![root, &self.cache_dir, "sources", src])
+        } else {
+            path_from_segments(vec![root, &local_src(src)])
+        }
+    }
+
+    #[cfg_attr(
+        not(any(
+            feature = "blur-placeholder",
+            feature = "axum-handler",
+            feature = "actix"
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn get_file_path_from_root(
+        &self,
+        cache_image: &CachedImage,
+    ) -> Result<String, CreateImageError> {
+        let (root, _) = self.namespace(cache_image)?;
+        let path = path_from_segments(vec![root, &self.get_file_path(cache_image)]);
+        Ok(path.as_path().to_string_lossy().to_string())
     }
 
     pub(crate) fn get_file_path(&self, cache_image: &CachedImage) -> String {
-        use base64::{engine::general_purpose, Engine as _};
-        // I'm worried this name will become too long.
-        // names are limited to 255 bytes on most filesystems.
+        let settings = self
+            .config
+            .read()
+            .expect("Optimizer config poisoned")
+            .encode_digest();
+        cache_image.get_file_path(&self.namespace_dir(), &settings)
+    }
 
-        let encode = serde_qs::to_string(&cache_image).unwrap();
-        let encode = general_purpose::STANDARD.encode(encode);
+    fn namespace_dir(&self) -> String {
+        format!("{}/{}", self.cache_dir, self.cache_namespace)
+    }
+
+    // URL of the directory the cached files are served from, under the base path of the app.
+    pub(crate) fn static_dir(&self) -> String {
+        format!("{}/{}", self.base_path, self.namespace_dir())
+    }
+
+    // Whether a complete cached file exists, created from the current contents of its source.
+    async fn cache_hit(
+        &self,
+        cache_image: &CachedImage,
+        source_path: &std::path::Path,
+        save_path: &std::path::Path,
+    ) -> bool {
+        cache_file_complete(save_path).await
+            && self
+                .source_unchanged(cache_image, source_path, save_path)
+                .await
+    }
+
+    // Whether the source of a cached file is unchanged since the file was created.
+    // Only the modification time and size are checked, unless they changed. Then the source is hashed
+    // on a blocking thread, see `check_source`.
+    async fn source_unchanged(
+        &self,
+        cache_image: &CachedImage,
+        source_path: &std::path::Path,
+        save_path: &std::path::Path,
+    ) -> bool {
+        if is_remote(&cache_image.src) {
+            return true;
+        }
+        let recorded = self
+            .source_versions
+            .get(save_path)
+            .map(|version| version.clone());
+        let stat = tokio::fs::metadata(source_path)
+            .await
+            .and_then(|metadata| SourceVersion::stat_of(&metadata));
+        match stat {
+            Err(_) => return true,
+            Ok(stat) if recorded.as_ref().is_some_and(|v| v.same_stat(stat)) => return true,
+            Ok(_) => {}
+        }
+        let check = {
+            let cache_image = cache_image.clone();
+            let (source_path, save_path) = (source_path.to_path_buf(), save_path.to_path_buf());
+            tokio::task::spawn_blocking(move || {
+                check_source(&cache_image, &source_path, &save_path, recorded)
+            })
+            .await
+        };
+        match check {
+            Ok(check) => self.apply_source_check(cache_image, save_path, check),
+            // Like sources that can't be read.
+            Err(_) => true,
+        }
+    }
+
+    // `source_unchanged` for synchronous callers, blocking on the filesystem.
+    fn source_unchanged_sync(
+        &self,
+        cache_image: &CachedImage,
+        source_path: &std::path::Path,
+        save_path: &std::path::Path,
+    ) -> bool {
+        if is_remote(&cache_image.src) {
+            return true;
+        }
+        let recorded = self
+            .source_versions
+            .get(save_path)
+            .map(|version| version.clone());
+        let check = check_source(cache_image, source_path, save_path, recorded);
+        self.apply_source_check(cache_image, save_path, check)
+    }
 
-        let mut path = path_from_segments(vec!["cache/image", &encode, &cache_image.src]);
+    fn apply_source_check(
+        &self,
+        cache_image: &CachedImage,
+        save_path: &std::path::Path,
+        check: SourceCheck,
+    ) -> bool {
+        match check {
+            SourceCheck::Unchanged(None) => true,
+            SourceCheck::Unchanged(Some(version)) => {
+                self.source_versions
+                    .insert(save_path.to_path_buf(), version);
+                true
+            }
+            SourceCheck::Changed => {
+                tracing::info!("Source of {cache_image} changed, creating it again");
+                self.source_versions.remove(save_path);
+                self.cache.remove(&cache_image.without_cache_control());
+                false
+            }
+        }
+    }
 
-        if let CachedImageOption::Resize { .. } = cache_image.option {
-            path.set_extension("webp");
+    fn record_source_version(&self, save_path: &std::path::Path, version: Option<SourceVersion>) {
+        if let Some(version) = version {
+            self.source_versions
+                .insert(save_path.to_path_buf(), version);
         } else {
-            path.set_extension("svg");
+            self.source_versions.remove(save_path);
+        }
+    }
+
+    // Moves a file cached under its legacy name to its content-hash name,
+    // so caches created by earlier versions aren't regenerated.
+    #[cfg(feature = "legacy-cache")]
+    async fn migrate_legacy_file(
+        &self,
+        cache_image: &CachedImage,
+        save_path: &std::path::Path,
+    ) -> bool {
+        let Some(legacy) = self.legacy_path(cache_image) else {
+            return false;
+        };
+        if !tokio::fs::try_exists(&legacy).await.unwrap_or(false) {
+            return false;
+        }
+        let migrated = async {
+            if let Some(parent) = save_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(&legacy, save_path).await?;
+            tokio::fs::write(sidecar_path(save_path), sidecar(cache_image, None)).await
         };
+        log_migration(&legacy, migrated.await)
+    }
 
-        path.as_path().to_string_lossy().to_string()
+    #[cfg(feature = "legacy-cache")]
+    fn migrate_legacy_file_sync(
+        &self,
+        cache_image: &CachedImage,
+        save_path: &std::path::Path,
+    ) -> bool {
+        let Some(legacy) = self.legacy_path(cache_image) else {
+            return false;
+        };
+        if !legacy.exists() {
+            return false;
+        }
+        let migrated = create_nested_if_needed(save_path)
+            .and_then(|_| std::fs::rename(&legacy, save_path))
+            .and_then(|_| std::fs::write(sidecar_path(save_path), sidecar(cache_image, None)));
+        log_migration(&legacy, migrated)
+    }
+
+    #[cfg(feature = "legacy-cache")]
+    fn legacy_path(&self, cache_image: &CachedImage) -> Option<std::path::PathBuf> {
+        let (root, _) = self.namespace(cache_image).ok()?;
+        Some(path_from_segments(vec![
+            root,
+            &cache_image.legacy_file_path(&self.namespace_dir()),
+        ]))
+    }
+
+    #[cfg(not(feature = "legacy-cache"))]
+    async fn migrate_legacy_file(&self, _: &CachedImage, _: &std::path::Path) -> bool {
+        false
+    }
+
+    #[cfg(not(feature = "legacy-cache"))]
+    fn migrate_legacy_file_sync(&self, _: &CachedImage, _: &std::path::Path) -> bool {
+        false
+    }
+}
+
+// Logs the outcome of moving a legacy file, true if it was moved.
+#[cfg(all(feature = "ssr", feature = "legacy-cache"))]
+fn log_migration(legacy: &std::path::Path, migrated: std::io::Result<()>) -> bool {
+    match migrated {
+        Ok(_) => {
+            tracing::debug!("Migrated cached image {}", legacy.display());
+            true
+        }
+        Err(e) => {
+            tracing::warn!("Failed to migrate cached image {}: {e}", legacy.display());
+            false
+        }
+    }
+}
+
+// How long a source stays known as missing, before the disk or `ImageSource` is checked again.
+#[cfg(feature = "ssr")]
+const MISSING_SOURCE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+// `/app` for `app/`, empty for the root.
+#[cfg(feature = "ssr")]
+fn normalize_base_path(base_path: &str) -> String {
+    let base_path = base_path.trim_matches('/');
+    if base_path.is_empty() {
+        String::new()
+    } else {
+        format!("/{base_path}")
     }
 }
 
+// A namespace usable as a single directory name.
+#[cfg(feature = "ssr")]
+fn normalize_cache_namespace(namespace: &str) -> String {
+    namespace.trim_matches('/').replace('/', "_")
+}
+
+#[cfg(feature = "ssr")]
+fn default_cache_namespace() -> String {
+    use leptos::leptos_config::Env;
+    match Env::from(&std::env::var("LEPTOS_ENV")) {
+        Env::DEV => "dev",
+        Env::PROD => "prod",
+    }
+    .to_string()
+}
+
+// Optimizer settings that affect how an image is encoded, captured when the encode starts.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Default)]
+struct EncodeSettings {
+    #[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+    blur_template: BlurTemplate,
+    deterministic: bool,
+    resize_filter: ResizeFilter,
+    sharpen: u8,
+    preserve_metadata: bool,
+    no_upscale: bool,
+    // The watermark and the path of its image.
+    watermark: Option<(std::path::PathBuf, Watermark)>,
+}
+
 #[cfg(feature = "ssr")]
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(
+        source = %AsRef::<std::path::Path>::as_ref(&source_path).display(),
+        save = %AsRef::<std::path::Path>::as_ref(&save_path).display(),
+    )
+)]
 fn create_optimized_image<P>(
     config: CachedImageOption,
     source_path: P,
     save_path: P,
+    settings: &EncodeSettings,
 ) -> Result<(), CreateImageError>
 where
     P: AsRef<std::path::Path> + AsRef<std::ffi::OsStr>,
 {
-    use webp::*;
+    let bytes = encode_optimized_image(config, source_path, settings)?;
+    create_nested_if_needed(&save_path)?;
+    let partial = partial_path(save_path.as_ref());
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, save_path)?;
+    Ok(())
+}
+
+// Writes an encoded image to the cache, without blocking a thread on the write.
+#[cfg(feature = "ssr")]
+pub(crate) async fn write_cache_file(
+    save_path: &std::path::Path,
+    bytes: Vec<u8>,
+) -> Result<(), CreateImageError> {
+    if let Some(parent) = save_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let partial = partial_path(save_path);
+    tokio::fs::write(&partial, bytes).await?;
+    tokio::fs::rename(&partial, save_path).await?;
+    Ok(())
+}
+
+// Metadata file next to a cached file, holding the spec it was created from.
+#[cfg(feature = "ssr")]
+fn sidecar_path(save_path: &std::path::Path) -> std::path::PathBuf {
+    save_path.with_extension("json")
+}
+
+#[cfg(feature = "ssr")]
+fn sidecar(cache_image: &CachedImage, source: Option<&SourceVersion>) -> Vec<u8> {
+    #[derive(Serialize)]
+    struct Sidecar<'a> {
+        #[serde(flatten)]
+        image: CachedImage,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<&'a SourceVersion>,
+    }
+    serde_json::to_vec(&Sidecar {
+        image: cache_image.without_cache_control(),
+        source,
+    })
+    .expect("Failed to serialize image spec")
+}
+
+// Outcome of comparing a source with the version its cached file was created from.
+#[cfg(feature = "ssr")]
+enum SourceCheck {
+    // With the version to remember, if it wasn't known yet.
+    Unchanged(Option<SourceVersion>),
+    Changed,
+}
+
+// Compares a source with the version its cached file was created from, reading the sidecar and hashing
+// the source when its modification time or size changed. Files cached before sources were versioned,
+// and sources that can't be read, count as unchanged. Blocks on the filesystem.
+#[cfg(feature = "ssr")]
+fn check_source(
+    cache_image: &CachedImage,
+    source_path: &std::path::Path,
+    save_path: &std::path::Path,
+    recorded: Option<SourceVersion>,
+) -> SourceCheck {
+    let Ok(stat) = SourceVersion::stat(source_path) else {
+        return SourceCheck::Unchanged(None);
+    };
+    if recorded.as_ref().is_some_and(|v| v.same_stat(stat)) {
+        return SourceCheck::Unchanged(None);
+    }
+    let recorded = recorded.or_else(|| sidecar_source_version(save_path));
+    if let Some(recorded) = recorded.as_ref().filter(|v| v.same_stat(stat)) {
+        return SourceCheck::Unchanged(Some(recorded.clone()));
+    }
+    let Ok(current) = SourceVersion::read(source_path) else {
+        return SourceCheck::Unchanged(None);
+    };
+    if recorded.is_some_and(|recorded| recorded.hash != current.hash) {
+        return SourceCheck::Changed;
+    }
+    // Record the new modification time, so the source isn't hashed again.
+    let sidecar = sidecar(cache_image, Some(&current));
+    if let Err(e) = std::fs::write(sidecar_path(save_path), sidecar) {
+        tracing::warn!("Failed to update sidecar of {}: {e}", save_path.display());
+    }
+    SourceCheck::Unchanged(Some(current))
+}
+
+// Version of the source a cached file was created from, from its sidecar.
+#[cfg(feature = "ssr")]
+fn sidecar_source_version(save_path: &std::path::Path) -> Option<SourceVersion> {
+    #[derive(Deserialize)]
+    struct Sidecar {
+        source: Option<SourceVersion>,
+    }
+    let sidecar = std::fs::read(sidecar_path(save_path)).ok()?;
+    serde_json::from_slice::<Sidecar>(&sidecar).ok()?.source
+}
+
+// Identifies the contents of a local source, so cached images are created again when it is overwritten.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SourceVersion {
+    // Modification time in nanoseconds since the epoch. Compared first, as it only needs a `stat`.
+    modified: u64,
+    len: u64,
+    // Hex SHA-256 of the contents, so sources only touched, e.g. by a fresh checkout, keep their images.
+    hash: String,
+}
+
+#[cfg(feature = "ssr")]
+impl SourceVersion {
+    // Version of the source of an image. Remote sources are downloaded once, so they have none.
+    fn of(cache_image: &CachedImage, source_path: &std::path::Path) -> Option<Self> {
+        if is_remote(&cache_image.src) {
+            return None;
+        }
+        Self::read(source_path).ok()
+    }
+
+    fn read(path: &std::path::Path) -> std::io::Result<Self> {
+        use sha2::{Digest, Sha256};
+        let (modified, len) = Self::stat(path)?;
+        let hash = Sha256::digest(std::fs::read(path)?)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Ok(Self {
+            modified,
+            len,
+            hash,
+        })
+    }
+
+    fn stat(path: &std::path::Path) -> std::io::Result<(u64, u64)> {
+        Self::stat_of(&std::fs::metadata(path)?)
+    }
+
+    fn stat_of(metadata: &std::fs::Metadata) -> std::io::Result<(u64, u64)> {
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Ok((modified, metadata.len()))
+    }
+
+    fn same_stat(&self, (modified, len): (u64, u64)) -> bool {
+        self.modified == modified && self.len == len
+    }
+}
 
+// Files are written next to their final path and renamed, so readers never see a partial file.
+#[cfg(feature = "ssr")]
+fn partial_path(save_path: &std::path::Path) -> std::path::PathBuf {
+    static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let id = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let mut partial = save_path.as_os_str().to_owned();
+    partial.push(format!(".{}-{id}.partial", std::process::id()));
+    partial.into()
+}
+
+#[cfg(feature = "ssr")]
+fn encode_optimized_image<P>(
+    config: CachedImageOption,
+    source_path: P,
+    settings: &EncodeSettings,
+) -> Result<Vec<u8>, CreateImageError>
+where
+    P: AsRef<std::path::Path> + AsRef<std::ffi::OsStr>,
+{
     match config {
         CachedImageOption::Resize(Resize {
             width,
             height,
             quality,
+            format,
+            fit,
+            lossless,
+            transforms,
+            ..
         }) => {
-            let img = image::open(source_path)?;
-            let new_img = img.resize(
-                width,
-                height,
-                // Cubic Filter.
-                image::imageops::FilterType::CatmullRom,
-            );
-            // Create the WebP encoder for the above image
-            let encoder: Encoder = Encoder::from_image(&new_img).unwrap();
-            // Encode the image at a specified quality 0-100
-            let webp: WebPMemory = encoder.encode(quality as f32);
-            create_nested_if_needed(&save_path)?;
-            std::fs::write(save_path, &*webp)?;
-
-            Ok(())
+            let img = open_source(&source_path)?;
+            let img = transforms
+                .iter()
+                .fold(img, |img, transform| transform.apply(img));
+            let (width, height) = if settings.no_upscale {
+                limit_upscale((img.width(), img.height()), width, height, fit)
+            } else {
+                (width, height)
+            };
+            let new_img = resize_image(&img, width, height, fit, settings.resize_filter);
+            let new_img = sharpen_image(new_img, settings.sharpen);
+            let new_img = match &settings.watermark {
+                Some((path, watermark)) if new_img.width() >= watermark.min_width => {
+                    draw_watermark(new_img, &open_source(path)?, watermark)
+                }
+                _ => new_img,
+            };
+            match format {
+                OutputFormat::WebP if settings.preserve_metadata => {
+                    let webp = encode_webp(&new_img, quality, lossless)?;
+                    let metadata =
+                        crate::metadata::SourceMetadata::read(&std::fs::read(&source_path)?);
+                    Ok(crate::metadata::embed_webp_metadata(
+                        webp,
+                        new_img.width(),
+                        new_img.height(),
+                        &metadata,
+                    ))
+                }
+                OutputFormat::WebP => encode_webp(&new_img, quality, lossless),
+                OutputFormat::Avif => encode_avif(&new_img, quality, settings.deterministic),
+                OutputFormat::Jpeg => encode_jpeg(&new_img, quality),
+                OutputFormat::Png => encode_png(&new_img),
+                OutputFormat::Jxl => encode_jxl(&new_img),
+            }
         }
+        #[cfg(feature = "blur-placeholder")]
         CachedImageOption::Blur(blur) => {
-            let svg = create_image_blur(source_path, blur)?;
-            create_nested_if_needed(&save_path)?;
-            std::fs::write(save_path, &*svg)?;
-            Ok(())
+            let svg = create_image_blur(source_path, blur, &settings.blur_template)?;
+            Ok(svg.into_bytes())
+        }
+        #[cfg(not(feature = "blur-placeholder"))]
+        CachedImageOption::Blur(_) => Err(CreateImageError::FeatureDisabled("blur-placeholder")),
+    }
+}
+
+// Shrinks the requested box, keeping its aspect ratio, so the source isn't scaled up to fill it.
+pub(crate) fn limit_upscale(
+    source: (u32, u32),
+    width: u32,
+    height: u32,
+    fit: ResizeMode,
+) -> (u32, u32) {
+    let (source_width, source_height) = (source.0.max(1) as f64, source.1.max(1) as f64);
+    let (scale_x, scale_y) = (width as f64 / source_width, height as f64 / source_height);
+    let scale = match fit {
+        ResizeMode::Contain => scale_x.min(scale_y),
+        ResizeMode::Fill | ResizeMode::Cover | ResizeMode::Crop(_) | ResizeMode::Focus(_) => {
+            scale_x.max(scale_y)
+        }
+    };
+    if scale <= 1.0 {
+        return (width, height);
+    }
+    let shrink = |side: u32| ((side as f64 / scale).round() as u32).max(1);
+    (shrink(width), shrink(height))
+}
+
+// Unsharp mask: adds the difference to a blurred copy back to the image, scaled by the amount in percent.
+#[cfg(feature = "ssr")]
+fn sharpen_image(img: image::DynamicImage, amount: u8) -> image::DynamicImage {
+    if amount == 0 {
+        return img;
+    }
+    let mut sharpened = img.to_rgba8();
+    let blurred = image::imageops::blur(&sharpened, 1.0);
+    let amount = amount as f32 / 100.0;
+    for (pixel, blurred) in sharpened.pixels_mut().zip(blurred.pixels()) {
+        // Alpha is left as is.
+        for channel in 0..3 {
+            let value = pixel[channel] as f32;
+            let detail = value - blurred[channel] as f32;
+            pixel[channel] = (value + detail * amount).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    let sharpened = image::DynamicImage::ImageRgba8(sharpened);
+    // Opaque images stay without an alpha channel, which encoders would keep.
+    if img.color().has_alpha() {
+        sharpened
+    } else {
+        image::DynamicImage::ImageRgb8(sharpened.to_rgb8())
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn draw_watermark(
+    img: image::DynamicImage,
+    mark: &image::DynamicImage,
+    watermark: &Watermark,
+) -> image::DynamicImage {
+    let mut mark = mark.to_rgba8();
+    let opacity = watermark.opacity.min(100) as u32;
+    for pixel in mark.pixels_mut() {
+        pixel[3] = (pixel[3] as u32 * opacity / 100) as u8;
+    }
+    let mut marked = img.to_rgba8();
+    let free = |size: u32, mark: u32| size.saturating_sub(mark + 2 * watermark.margin);
+    let (x, y) = watermark.position.offsets();
+    let left = watermark.margin + free(marked.width(), mark.width()) * x / 2;
+    let top = watermark.margin + free(marked.height(), mark.height()) * y / 2;
+    image::imageops::overlay(&mut marked, &mark, left as i64, top as i64);
+    let marked = image::DynamicImage::ImageRgba8(marked);
+    // Opaque images stay without an alpha channel, which encoders would keep.
+    if img.color().has_alpha() {
+        marked
+    } else {
+        image::DynamicImage::ImageRgb8(marked.to_rgb8())
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn resize_image(
+    img: &image::DynamicImage,
+    width: u32,
+    height: u32,
+    fit: ResizeMode,
+    filter: ResizeFilter,
+) -> image::DynamicImage {
+    let filter = filter.filter_type();
+    let focus = match fit {
+        ResizeMode::Contain => return img.resize(width, height, filter),
+        ResizeMode::Fill => return img.resize_exact(width, height, filter),
+        ResizeMode::Cover if cfg!(feature = "smartcrop") => None,
+        ResizeMode::Cover => Some(Gravity::Center.focal_point()),
+        ResizeMode::Crop(gravity) => Some(gravity.focal_point()),
+        ResizeMode::Focus(focus) => Some(focus),
+    };
+    // Scale so the image covers the box, then crop the overflow around the focal point.
+    let (source_width, source_height) = (img.width().max(1) as u64, img.height().max(1) as u64);
+    let (width, height) = (width.max(1), height.max(1));
+    let scaled = if width as u64 * source_height > height as u64 * source_width {
+        let scaled_height = (width as u64 * source_height).div_ceil(source_width);
+        (width, scaled_height as u32)
+    } else {
+        let scaled_width = (height as u64 * source_width).div_ceil(source_height);
+        (scaled_width as u32, height)
+    };
+    let scaled = img.resize_exact(scaled.0, scaled.1, filter);
+    let focus = focus.unwrap_or_else(|| detailed_focal_point(&scaled, width, height));
+    let left = (scaled.width() - width) * focus.x as u32 / 1000;
+    let top = (scaled.height() - height) * focus.y as u32 / 1000;
+    scaled.crop_imm(left, top, width, height)
+}
+
+// Focal point of the crop window with the most detail, measured by the edges of a downscaled copy.
+// Ties go to the window closest to the center.
+#[cfg(feature = "ssr")]
+fn detailed_focal_point(scaled: &image::DynamicImage, width: u32, height: u32) -> FocalPoint {
+    let center = Gravity::Center.focal_point();
+    let horizontal = scaled.width() > width;
+    let (length, window) = if horizontal {
+        (scaled.width(), width)
+    } else {
+        (scaled.height(), height)
+    };
+    if length <= window {
+        return center;
+    }
+    let small = scaled.thumbnail(256, 256).to_luma8();
+    let (small_length, other) = if horizontal {
+        (small.width(), small.height())
+    } else {
+        (small.height(), small.width())
+    };
+    let window =
+        ((window as u64 * small_length as u64 / length as u64) as u32).clamp(1, small_length);
+    if window == small_length {
+        return center;
+    }
+    let luma = |along: u32, across: u32| {
+        let (x, y) = if horizontal {
+            (along, across)
+        } else {
+            (across, along)
+        };
+        small.get_pixel(x, y)[0] as i32
+    };
+    // Edge energy of each column, or row, from the differences with the next pixel on both axes.
+    let mut prefix = vec![0u64; small_length as usize + 1];
+    for along in 0..small_length {
+        let mut energy = 0u64;
+        for across in 0..other {
+            let value = luma(along, across);
+            if along + 1 < small_length {
+                energy += (luma(along + 1, across) - value).unsigned_abs() as u64;
+            }
+            if across + 1 < other {
+                energy += (luma(along, across + 1) - value).unsigned_abs() as u64;
+            }
+        }
+        prefix[along as usize + 1] = prefix[along as usize] + energy;
+    }
+    let overflow = small_length - window;
+    let best = (0..=overflow)
+        .max_by_key(|&start| {
+            let energy = prefix[(start + window) as usize] - prefix[start as usize];
+            let off_center = (2 * start as i64 - overflow as i64).unsigned_abs();
+            (energy, std::cmp::Reverse(off_center))
+        })
+        .unwrap_or(overflow / 2);
+    let fraction = (best as u64 * 1000 / overflow as u64) as u16;
+    if horizontal {
+        FocalPoint {
+            x: fraction,
+            ..center
+        }
+    } else {
+        FocalPoint {
+            y: fraction,
+            ..center
+        }
+    }
+}
+
+// Decodes a source image. The format is sniffed from the contents, so legacy files
+// with a missing or wrong extension (e.g. an ICO saved as `.png`) still decode.
+// BMP, TIFF and ICO go through the same pipeline as other formats, 16 bit and paletted
+// sources are converted to 8 bit RGB(A) before encoding.
+#[cfg(feature = "ssr")]
+fn open_source<P>(source_path: P) -> Result<image::DynamicImage, CreateImageError>
+where
+    P: AsRef<std::path::Path>,
+{
+    Ok(image::io::Reader::open(source_path)?
+        .with_guessed_format()?
+        .decode()?)
+}
+
+// Reads the header of a source. The format is sniffed from the contents, like `open_source`.
+#[cfg(feature = "ssr")]
+fn probe_path(path: &std::path::Path) -> Result<SourceInfo, CreateImageError> {
+    let bytes = std::fs::metadata(path)?.len();
+    let reader = image::io::Reader::open(path)?.with_guessed_format()?;
+    let Some(format) = reader.format() else {
+        let unknown = image::error::ImageFormatHint::Unknown;
+        return Err(image::ImageError::Unsupported(unknown.into()).into());
+    };
+    let (width, height) = reader.into_dimensions()?;
+    Ok(SourceInfo {
+        width,
+        height,
+        format,
+        bytes,
+    })
+}
+
+// Whether WebP images are lossy, encoded with libwebp.
+#[cfg(feature = "ssr")]
+const LOSSY_WEBP: bool = cfg!(all(feature = "webp", not(feature = "pure-rust")));
+
+// Encodes the image at a specified quality 0-100, or losslessly.
+#[cfg(all(feature = "ssr", feature = "webp", not(feature = "pure-rust")))]
+fn encode_webp(
+    img: &image::DynamicImage,
+    quality: u8,
+    lossless: bool,
+) -> Result<Vec<u8>, CreateImageError> {
+    let encode = |encoder: webp::Encoder| {
+        if lossless {
+            encoder.encode_lossless().to_vec()
+        } else {
+            encoder.encode(quality as f32).to_vec()
         }
-    }
+    };
+    // Hand libwebp raw pixels, so the webp crate never sees a `DynamicImage` of another `image` version.
+    let webp = if img.color().has_alpha() {
+        let img = img.to_rgba8();
+        encode(webp::Encoder::from_rgba(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+        ))
+    } else {
+        let img = img.to_rgb8();
+        encode(webp::Encoder::from_rgb(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+        ))
+    };
+    Ok(webp)
+}
+
+#[cfg(feature = "ssr")]
+fn encode_jpeg(img: &image::DynamicImage, quality: u8) -> Result<Vec<u8>, CreateImageError> {
+    use image::ImageEncoder;
+    let img = img.to_rgb8();
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100))
+        .write_image(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            image::ColorType::Rgb8,
+        )?;
+    Ok(bytes)
 }
 
 #[cfg(feature = "ssr")]
-fn create_image_blur<P>(source_path: P, blur: Blur) -> Result<String, CreateImageError>
+fn encode_png(img: &image::DynamicImage) -> Result<Vec<u8>, CreateImageError> {
+    use image::ImageEncoder;
+    let img = img.to_rgba8();
+    let mut bytes = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut bytes).write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        image::ColorType::Rgba8,
+    )?;
+    Ok(bytes)
+}
+
+#[cfg(all(feature = "ssr", feature = "avif"))]
+fn encode_avif(
+    img: &image::DynamicImage,
+    quality: u8,
+    deterministic: bool,
+) -> Result<Vec<u8>, CreateImageError> {
+    use image::ImageEncoder;
+    let img = img.to_rgba8();
+    let mut bytes = Vec::new();
+    // Speed 6 trades a slightly larger file for much faster encoding than the default.
+    image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut bytes, 6, quality.min(100))
+        // rav1e output depends on how work is split between threads.
+        .with_num_threads(deterministic.then_some(1))
+        .write_image(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            image::ColorType::Rgba8,
+        )?;
+    Ok(bytes)
+}
+
+#[cfg(all(feature = "ssr", not(feature = "avif")))]
+fn encode_avif(
+    _img: &image::DynamicImage,
+    _quality: u8,
+    _deterministic: bool,
+) -> Result<Vec<u8>, CreateImageError> {
+    Err(CreateImageError::FeatureDisabled("avif"))
+}
+
+#[cfg(all(feature = "ssr", feature = "jxl"))]
+fn encode_jxl(img: &image::DynamicImage) -> Result<Vec<u8>, CreateImageError> {
+    use image::error::{EncodingError, ImageFormatHint};
+    use zune_core::{bit_depth::BitDepth, colorspace::ColorSpace, options::EncoderOptions};
+    let (pixels, colorspace) = if img.color().has_alpha() {
+        (img.to_rgba8().into_raw(), ColorSpace::RGBA)
+    } else {
+        (img.to_rgb8().into_raw(), ColorSpace::RGB)
+    };
+    let options = EncoderOptions::new(
+        img.width() as usize,
+        img.height() as usize,
+        colorspace,
+        BitDepth::Eight,
+    );
+    let mut bytes = Vec::new();
+    zune_jpegxl::JxlSimpleEncoder::new(&pixels, options)
+        .encode(&mut bytes)
+        .map_err(|e| {
+            image::ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Name("JPEG XL".to_string()),
+                format!("{e:?}"),
+            ))
+        })?;
+    Ok(bytes)
+}
+
+#[cfg(all(feature = "ssr", not(feature = "jxl")))]
+fn encode_jxl(_img: &image::DynamicImage) -> Result<Vec<u8>, CreateImageError> {
+    Err(CreateImageError::FeatureDisabled("jxl"))
+}
+
+// 4x3 components are enough for a placeholder, and keep the hash at 28 characters.
+#[cfg(all(feature = "ssr", feature = "blurhash"))]
+fn create_blurhash(img: &image::DynamicImage) -> Result<String, CreateImageError> {
+    let rgba = img.to_rgba8();
+    Ok(blurhash::encode(
+        4,
+        3,
+        rgba.width(),
+        rgba.height(),
+        rgba.as_raw(),
+    )?)
+}
+
+#[cfg(all(
+    feature = "ssr",
+    feature = "blur-placeholder",
+    not(feature = "blurhash")
+))]
+fn create_blurhash(_img: &image::DynamicImage) -> Result<String, CreateImageError> {
+    Err(CreateImageError::FeatureDisabled("blurhash"))
+}
+
+// Without libwebp only lossless encoding is available, so quality is ignored.
+#[cfg(all(feature = "ssr", any(not(feature = "webp"), feature = "pure-rust")))]
+fn encode_webp(
+    img: &image::DynamicImage,
+    _quality: u8,
+    _lossless: bool,
+) -> Result<Vec<u8>, CreateImageError> {
+    let img = img.to_rgba8();
+    let mut bytes = Vec::new();
+    image::codecs::webp::WebPEncoder::new_lossless(&mut bytes).encode(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        image::ColorType::Rgba8,
+    )?;
+    Ok(bytes)
+}
+
+#[cfg(all(feature = "ssr", feature = "blur-placeholder"))]
+fn create_image_blur<P>(
+    source_path: P,
+    blur: Blur,
+    template: &BlurTemplate,
+) -> Result<String, CreateImageError>
 where
     P: AsRef<std::path::Path> + AsRef<std::ffi::OsStr>,
 {
-    use webp::*;
-
-    let img = image::open(source_path).map_err(|e| CreateImageError::ImageError(e))?;
+    let img = open_source(source_path)?;
 
     let Blur {
         width,
@@ -203,23 +3405,88 @@ where
         svg_height,
         svg_width,
         sigma,
+        mode,
     } = blur;
 
+    // The view box keeps the aspect ratio of the source, so the placeholder isn't stretched
+    // while it covers the final image.
+    let (svg_width, svg_height) = fitted_dimensions(
+        (img.width(), img.height()),
+        svg_width,
+        svg_height,
+        ResizeMode::Contain,
+    );
+
     let img = img.resize(width, height, image::imageops::FilterType::Nearest);
 
-    // Create the WebP encoder for the above image
-    let encoder: Encoder = Encoder::from_image(&img).unwrap();
-    // Encode the image at a specified quality 0-100
-    let webp: WebPMemory = encoder.encode(80.0);
+    if mode == Placeholder::BlurHash {
+        return create_blurhash(&img);
+    }
+
+    let webp = encode_webp(&img, 80, false)?;
 
     // Encode the image to base64
     use base64::{engine::general_purpose, Engine as _};
     let encoded = general_purpose::STANDARD.encode(&*webp);
 
     let uri = format!("data:image/webp;base64,{}", encoded);
+    if mode == Placeholder::CssBlur {
+        return Ok(uri);
+    }
 
-    let svg = format!(
-        r#"
+    let svg = template.render(svg_width, svg_height, sigma, &uri);
+
+    Ok(svg)
+}
+
+/// SVG template used to wrap blur placeholders.
+///
+/// The template may reference the following placeholders, which are substituted when a placeholder is created:
+/// - `{svg_width}` and `{svg_height}`: dimensions of the SVG view box, in the aspect ratio of the source.
+/// - `{sigma}`: standard deviation of the gaussian blur.
+/// - `{uri}`: data URI of the tiny webp image.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let template = BlurTemplate::new(
+///     r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {svg_width} {svg_height}" preserveAspectRatio="xMidYMid slice">
+///         <filter id="b"><feGaussianBlur stdDeviation="{sigma}"/></filter>
+///         <image filter="url(#b)" width="100%" height="100%" href="{uri}"/>
+///     </svg>"#,
+/// );
+/// let optimizer = ImageOptimizer::builder().blur_template(template).build();
+/// ```
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlurTemplate {
+    template: String,
+}
+
+#[cfg(feature = "ssr")]
+impl BlurTemplate {
+    /// Creates a template from an SVG string containing placeholders.
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+
+    #[cfg(feature = "blur-placeholder")]
+    pub(crate) fn render(&self, svg_width: u32, svg_height: u32, sigma: u8, uri: &str) -> String {
+        self.template
+            .replace("{svg_width}", &svg_width.to_string())
+            .replace("{svg_height}", &svg_height.to_string())
+            .replace("{sigma}", &sigma.to_string())
+            .replace("{uri}", uri)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Default for BlurTemplate {
+    fn default() -> Self {
+        Self::new(
+            r#"
 <svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100%" height="100%" viewBox="0 0 {svg_width} {svg_height}" preserveAspectRatio="none">
     <filter id="a" filterUnits="userSpaceOnUse" color-interpolation-filters="sRGB"> 
         <feGaussianBlur stdDeviation="{sigma}" edgeMode="duplicate"/> 
@@ -230,15 +3497,242 @@ where
     <image filter="url(#a)" x="0" y="0" height="100%" width="100%" href="{uri}"/>
 </svg>
 "#,
-    );
+        )
+    }
+}
 
-    Ok(svg)
+// Query of the original download route, see `ImageCacheRoute::image_original_route`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
+pub(crate) struct OriginalImage {
+    pub(crate) src: String,
+    #[serde(rename = "t", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tenant: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) download: bool,
+}
+
+impl OriginalImage {
+    pub(crate) fn get_url(&self, handler_path: &str) -> String {
+        let params = serde_qs::to_string(&self).unwrap();
+        format!("{}/original?{params}", handler_path.trim_end_matches('/'))
+    }
+
+    // The original without the `download` flag, which doesn't change the file served.
+    #[cfg(feature = "ssr")]
+    fn without_download(&self) -> Self {
+        Self {
+            download: false,
+            ..self.clone()
+        }
+    }
 }
 
+/// A single optimized variant of a source image, as stored in the image cache.
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
 pub struct CachedImage {
     pub(crate) src: String,
     pub(crate) option: CachedImageOption,
+    #[serde(rename = "t", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tenant: Option<String>,
+    #[serde(rename = "cc", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cache_control: Option<String>,
+}
+
+impl CachedImage {
+    /// A webp resized to fit within `width` x `height`, as produced by `<Image/>`.
+    pub fn resize(src: impl Into<String>, width: u32, height: u32, quality: u8) -> Self {
+        Self {
+            src: src.into(),
+            option: CachedImageOption::Resize(Resize {
+                quality,
+                width,
+                height,
+                format: OutputFormat::default(),
+                fit: ResizeMode::default(),
+                filter: None,
+                sharpen: None,
+                metadata: None,
+                no_upscale: None,
+                lossless: false,
+                transforms: Vec::new(),
+                watermark: None,
+            }),
+            tenant: None,
+            cache_control: None,
+        }
+    }
+
+    /// The blur placeholder produced by `<Image blur=true/>`.
+    pub fn blur(src: impl Into<String>) -> Self {
+        Self {
+            src: src.into(),
+            option: CachedImageOption::Blur(Blur {
+                width: 20,
+                height: 20,
+                svg_width: 100,
+                svg_height: 100,
+                sigma: 15,
+                mode: Placeholder::Svg,
+            }),
+            tenant: None,
+            cache_control: None,
+        }
+    }
+
+    // Renders a blur placeholder with the given mode. Resized images are unchanged.
+    #[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+    pub(crate) fn with_placeholder(mut self, placeholder: Placeholder) -> Self {
+        if let CachedImageOption::Blur(blur) = &mut self.option {
+            blur.mode = placeholder;
+        }
+        self
+    }
+
+    // Content type of the created file.
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
+    #[cfg(feature = "ssr")]
+    pub(crate) fn mime_type(&self) -> &'static str {
+        match &self.option {
+            CachedImageOption::Resize(resize) => resize.format.mime_type(),
+            CachedImageOption::Blur(blur) => match blur.mode {
+                Placeholder::Svg => "image/svg+xml",
+                Placeholder::BlurHash | Placeholder::CssBlur => "text/plain; charset=utf-8",
+            },
+        }
+    }
+
+    /// Scopes the image to a tenant registered with `ImageOptimizerBuilder::tenant`.
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Sets how a resized image fits its width and height. Blur placeholders are unchanged.
+    pub fn with_fit(mut self, fit: ResizeMode) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.fit = fit;
+        }
+        self
+    }
+
+    /// Sets the filter used to resize the image, overriding `OptimizerConfig::resize_filter`.
+    /// Blur placeholders are unchanged.
+    pub fn with_filter(mut self, filter: ResizeFilter) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.filter = Some(filter);
+        }
+        self
+    }
+
+    /// Sharpens the image after resizing, overriding `OptimizerConfig::sharpen`. The amount is in percent, 0 doesn't sharpen.
+    /// Blur placeholders are unchanged.
+    pub fn with_sharpen(mut self, amount: u8) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.sharpen = Some(amount);
+        }
+        self
+    }
+
+    /// Keeps or strips the EXIF metadata and ICC profile of the source, overriding `OptimizerConfig::preserve_metadata`.
+    /// Blur placeholders never have metadata.
+    ///
+    /// Image URLs don't carry it, so clients can't request the metadata of stripped images.
+    /// Requests get it for images rendered on the server, or created with [`crate::cache_app_images`].
+    pub fn with_preserve_metadata(mut self, preserve: bool) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.metadata = Some(preserve);
+        }
+        self
+    }
+
+    /// Opts the image out of `OptimizerConfig::watermark` with `false`. Blur placeholders are never watermarked.
+    ///
+    /// Image URLs don't carry it, so clients can't remove the watermark.
+    /// Requests get it for images rendered on the server, or created with [`crate::cache_app_images`].
+    pub fn with_watermark(mut self, watermark: bool) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.watermark = Some(watermark);
+        }
+        self
+    }
+
+    /// Adjusts the source before resizing, applying the transforms in order, at most [`MAX_TRANSFORMS`].
+    /// Blur placeholders are unchanged.
+    pub fn with_transforms(mut self, transforms: impl IntoIterator<Item = Transform>) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.transforms = transforms.into_iter().collect();
+        }
+        self
+    }
+
+    /// Encodes the image losslessly, for graphics like diagrams, logos and screenshots that lossy compression blurs.
+    /// The quality is ignored. Only applies to WebP, and to PNG, which is always lossless.
+    pub fn with_lossless(mut self, lossless: bool) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.lossless = lossless;
+        }
+        self
+    }
+
+    /// Keeps the image at the source's resolution when the requested size is larger, overriding `OptimizerConfig::no_upscale`.
+    /// Blur placeholders are unchanged.
+    pub fn with_no_upscale(mut self, enabled: bool) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.no_upscale = Some(enabled);
+        }
+        self
+    }
+
+    /// Sets the encoding of a resized image. Blur placeholders are always SVGs.
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        if let CachedImageOption::Resize(resize) = &mut self.option {
+            resize.format = format;
+        }
+        self
+    }
+
+    /// Overrides the `Cache-Control` header the handler serves this image with.
+    /// Useful for images that are replaced often, like a promo banner.
+    /// Only the server picks it, where the image is rendered or warmed: image URLs don't carry it.
+    pub fn with_cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.cache_control = Some(cache_control.into());
+        self
+    }
+
+    // The image as requested by URLs, which can't carry the settings only the server may pick,
+    // like keeping the metadata of user photos, leaving out the watermark or the `Cache-Control` header.
+    // See `ImageOptimizer::allow_image`.
+    pub(crate) fn without_server_overrides(&self) -> Self {
+        let mut image = self.without_cache_control();
+        if let CachedImageOption::Resize(resize) = &mut image.option {
+            resize.metadata = None;
+            resize.watermark = None;
+        }
+        image
+    }
+
+    // The image itself, regardless of the headers it is served with.
+    pub(crate) fn without_cache_control(&self) -> Self {
+        CachedImage {
+            cache_control: None,
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn scoped(self, tenant: Option<&str>) -> Self {
+        match tenant {
+            Some(tenant) => self.with_tenant(tenant),
+            None => self,
+        }
+    }
+
+    /// The source image path, relative to the site root.
+    pub fn src(&self) -> &str {
+        &self.src
+    }
 }
 
 impl std::fmt::Display for CachedImage {
@@ -252,25 +3746,480 @@ impl std::fmt::Display for CachedImage {
             CachedImageOption::Blur(_) => write!(f, "ImageBlur {}", self.src),
         }
     }
-}
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
+pub(crate) enum CachedImageOption {
+    #[serde(rename = "r")]
+    Resize(Resize),
+    #[serde(rename = "b")]
+    Blur(Blur),
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
+#[serde(rename = "r")]
+pub(crate) struct Resize {
+    #[serde(rename = "w")]
+    pub width: u32,
+    #[serde(rename = "h")]
+    pub height: u32,
+    #[serde(rename = "q")]
+    pub quality: u8,
+    #[serde(
+        rename = "f",
+        default,
+        skip_serializing_if = "OutputFormat::is_default"
+    )]
+    pub format: OutputFormat,
+    #[serde(default, skip_serializing_if = "ResizeMode::is_default")]
+    pub fit: ResizeMode,
+    // Overrides the filters configured on the optimizer.
+    #[serde(rename = "rf", default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ResizeFilter>,
+    // Overrides `OptimizerConfig::sharpen`.
+    #[serde(rename = "sh", default, skip_serializing_if = "Option::is_none")]
+    pub sharpen: Option<u8>,
+    // Overrides `OptimizerConfig::preserve_metadata`.
+    #[serde(rename = "md", default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<bool>,
+    // Overrides `OptimizerConfig::no_upscale`.
+    #[serde(rename = "nu", default, skip_serializing_if = "Option::is_none")]
+    pub no_upscale: Option<bool>,
+    // Encodes WebP losslessly, ignoring the quality.
+    #[serde(rename = "ll", default, skip_serializing_if = "std::ops::Not::not")]
+    pub lossless: bool,
+    #[serde(rename = "tf", default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<Transform>,
+    // Opts out of `OptimizerConfig::watermark` with `Some(false)`.
+    #[serde(rename = "wm", default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<bool>,
+}
+
+/// How a resized image fits the requested width and height.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let thumbnail = CachedImage::resize("/cute_ferris.png", 128, 128, 75).with_fit(ResizeMode::Cover);
+/// let banner = CachedImage::resize("/hero.jpg", 1200, 300, 75).with_fit(ResizeMode::Crop(Gravity::Top));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum ResizeMode {
+    /// Fits inside the box, keeping the aspect ratio. One side may be smaller than requested.
+    #[default]
+    Contain,
+    /// Fills the box exactly, keeping the aspect ratio and cropping the overflow around the center.
+    ///
+    /// With the `smartcrop` feature, the overflow is cropped around the part of the image with the most edges instead,
+    /// which usually holds the subject. Changing the feature doesn't regenerate cached images.
+    Cover,
+    /// Stretches to the exact box, ignoring the aspect ratio.
+    Fill,
+    /// Like [`ResizeMode::Cover`], keeping the part of the image at the given edge or corner.
+    Crop(Gravity),
+    /// Like [`ResizeMode::Cover`], keeping the subject at the focal point in view.
+    Focus(FocalPoint),
+}
+
+/// A point of the image kept in view by [`ResizeMode::Focus`], from the top left corner `(0.0, 0.0)`
+/// to the bottom right corner `(1.0, 1.0)`.
+///
+/// The crop places the focal point as far across the image as it is across the source, like CSS `object-position`,
+/// e.g. `(0.5, 0.5)` crops around the center like [`ResizeMode::Cover`].
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let portrait = CachedImage::resize("/team.jpg", 300, 300, 75)
+///     .with_fit(ResizeMode::Focus(FocalPoint::new(0.3, 0.2)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FocalPoint {
+    // Thousandths, so the point can be hashed as part of the cache key.
+    x: u16,
+    y: u16,
+}
+
+impl FocalPoint {
+    /// A focal point at fractions of the width and height, clamped to `0.0..=1.0`.
+    pub fn new(x: f32, y: f32) -> Self {
+        let thousandths = |value: f32| (value.clamp(0.0, 1.0) * 1000.0).round() as u16;
+        Self {
+            x: thousandths(x),
+            y: thousandths(y),
+        }
+    }
+
+    /// Fraction of the width, from the left edge.
+    pub fn x(&self) -> f32 {
+        self.x as f32 / 1000.0
+    }
+
+    /// Fraction of the height, from the top edge.
+    pub fn y(&self) -> f32 {
+        self.y as f32 / 1000.0
+    }
+
+    // CSS position of the point, for `object-position` and `background-position`.
+    pub(crate) fn css_position(&self) -> String {
+        format!("{}% {}%", self.x() * 100.0, self.y() * 100.0)
+    }
+}
+
+/// The part of the image kept by [`ResizeMode::Crop`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Gravity {
+    /// Keeps the center, like [`ResizeMode::Cover`].
+    #[default]
+    Center,
+    /// Keeps the top edge.
+    Top,
+    /// Keeps the bottom edge.
+    Bottom,
+    /// Keeps the left edge.
+    Left,
+    /// Keeps the right edge.
+    Right,
+    /// Keeps the top left corner.
+    TopLeft,
+    /// Keeps the top right corner.
+    TopRight,
+    /// Keeps the bottom left corner.
+    BottomLeft,
+    /// Keeps the bottom right corner.
+    BottomRight,
+}
+
+impl ResizeMode {
+    fn is_default(&self) -> bool {
+        *self == ResizeMode::default()
+    }
+}
+
+impl Gravity {
+    const ALL: [Gravity; 9] = [
+        Gravity::Center,
+        Gravity::Top,
+        Gravity::Bottom,
+        Gravity::Left,
+        Gravity::Right,
+        Gravity::TopLeft,
+        Gravity::TopRight,
+        Gravity::BottomLeft,
+        Gravity::BottomRight,
+    ];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Gravity::Center => "center",
+            Gravity::Top => "top",
+            Gravity::Bottom => "bottom",
+            Gravity::Left => "left",
+            Gravity::Right => "right",
+            Gravity::TopLeft => "top-left",
+            Gravity::TopRight => "top-right",
+            Gravity::BottomLeft => "bottom-left",
+            Gravity::BottomRight => "bottom-right",
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    fn focal_point(&self) -> FocalPoint {
+        let (x, y) = self.offsets();
+        FocalPoint {
+            x: x as u16 * 500,
+            y: y as u16 * 500,
+        }
+    }
+
+    // Fractions of the overflow cropped from the left and the top.
+    #[cfg(feature = "ssr")]
+    fn offsets(&self) -> (u32, u32) {
+        let x = match self {
+            Gravity::Left | Gravity::TopLeft | Gravity::BottomLeft => 0,
+            Gravity::Right | Gravity::TopRight | Gravity::BottomRight => 2,
+            _ => 1,
+        };
+        let y = match self {
+            Gravity::Top | Gravity::TopLeft | Gravity::TopRight => 0,
+            Gravity::Bottom | Gravity::BottomLeft | Gravity::BottomRight => 2,
+            _ => 1,
+        };
+        (x, y)
+    }
+}
+
+// Written into image URLs and cache keys, e.g. `cover` or `crop-top-left`.
+impl std::fmt::Display for ResizeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResizeMode::Contain => f.write_str("contain"),
+            ResizeMode::Cover => f.write_str("cover"),
+            ResizeMode::Fill => f.write_str("fill"),
+            ResizeMode::Crop(gravity) => write!(f, "crop-{}", gravity.as_str()),
+            ResizeMode::Focus(focus) => write!(f, "focus-{}-{}", focus.x(), focus.y()),
+        }
+    }
+}
+
+impl std::str::FromStr for ResizeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "contain" => Ok(ResizeMode::Contain),
+            "cover" => Ok(ResizeMode::Cover),
+            "fill" => Ok(ResizeMode::Fill),
+            _ => {
+                let crop = s
+                    .strip_prefix("crop-")
+                    .and_then(|gravity| Gravity::ALL.into_iter().find(|g| g.as_str() == gravity))
+                    .map(ResizeMode::Crop);
+                let focus = || {
+                    let (x, y) = s.strip_prefix("focus-")?.split_once('-')?;
+                    let focus = FocalPoint::new(x.parse().ok()?, y.parse().ok()?);
+                    Some(ResizeMode::Focus(focus))
+                };
+                crop.or_else(focus)
+                    .ok_or_else(|| format!("Unknown resize mode {s}"))
+            }
+        }
+    }
+}
+
+impl From<ResizeMode> for String {
+    fn from(mode: ResizeMode) -> Self {
+        mode.to_string()
+    }
+}
+
+impl TryFrom<String> for ResizeMode {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Most transforms an image can have, so image URLs can't be crafted to exhaust the CPU.
+pub const MAX_TRANSFORMS: usize = 8;
+
+/// An adjustment applied to the source before it is resized, in order.
+/// Images with more than [`MAX_TRANSFORMS`] fail with [`CreateImageError::TooManyTransforms`].
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let image = CachedImage::resize("/cute_ferris.png", 400, 300, 75)
+///     .with_transforms([Transform::Rotate90, Transform::Grayscale, Transform::Contrast(20)]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Transform {
+    /// Rotates a quarter turn clockwise.
+    Rotate90,
+    /// Rotates a half turn.
+    Rotate180,
+    /// Rotates three quarter turns clockwise.
+    Rotate270,
+    /// Mirrors left to right.
+    FlipHorizontal,
+    /// Mirrors top to bottom.
+    FlipVertical,
+    /// Removes the colors.
+    Grayscale,
+    /// Adds to every color channel, from -255 to 255.
+    Brightness(i16),
+    /// Changes the contrast in percent. Negative values lower it.
+    Contrast(i16),
+}
+
+impl Transform {
+    // Whether the width and height of the image are swapped.
+    pub(crate) fn swaps_axes(&self) -> bool {
+        matches!(self, Transform::Rotate90 | Transform::Rotate270)
+    }
+
+    #[cfg(feature = "ssr")]
+    fn apply(&self, img: image::DynamicImage) -> image::DynamicImage {
+        match self {
+            Transform::Rotate90 => img.rotate90(),
+            Transform::Rotate180 => img.rotate180(),
+            Transform::Rotate270 => img.rotate270(),
+            Transform::FlipHorizontal => img.fliph(),
+            Transform::FlipVertical => img.flipv(),
+            // Keeps the alpha channel, which `grayscale` drops from some color types.
+            Transform::Grayscale => match img.color().has_alpha() {
+                true => image::DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+                false => image::DynamicImage::ImageLuma8(img.to_luma8()),
+            },
+            Transform::Brightness(value) => img.brighten(*value as i32),
+            Transform::Contrast(percent) => img.adjust_contrast(*percent as f32),
+        }
+    }
+}
+
+// Written into image URLs and cache keys, e.g. `rot90` or `bright-20`.
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transform::Rotate90 => f.write_str("rot90"),
+            Transform::Rotate180 => f.write_str("rot180"),
+            Transform::Rotate270 => f.write_str("rot270"),
+            Transform::FlipHorizontal => f.write_str("fliph"),
+            Transform::FlipVertical => f.write_str("flipv"),
+            Transform::Grayscale => f.write_str("gray"),
+            Transform::Brightness(value) => write!(f, "bright{value}"),
+            Transform::Contrast(percent) => write!(f, "contrast{percent}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rot90" => Ok(Transform::Rotate90),
+            "rot180" => Ok(Transform::Rotate180),
+            "rot270" => Ok(Transform::Rotate270),
+            "fliph" => Ok(Transform::FlipHorizontal),
+            "flipv" => Ok(Transform::FlipVertical),
+            "gray" => Ok(Transform::Grayscale),
+            _ => {
+                let number = |prefix: &str| s.strip_prefix(prefix)?.parse::<i16>().ok();
+                if let Some(value) = number("bright") {
+                    Ok(Transform::Brightness(value.clamp(-255, 255)))
+                } else if let Some(percent) = number("contrast") {
+                    Ok(Transform::Contrast(percent))
+                } else {
+                    Err(format!("Unknown transform {s}"))
+                }
+            }
+        }
+    }
+}
+
+impl From<Transform> for String {
+    fn from(transform: Transform) -> Self {
+        transform.to_string()
+    }
+}
+
+impl TryFrom<String> for Transform {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Encoding of optimized images.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize, Hash)]
+pub enum OutputFormat {
+    /// WebP, supported by every modern browser.
+    #[default]
+    #[serde(rename = "webp")]
+    WebP,
+    /// AVIF, smaller than WebP at the same quality. Encoding requires the `avif` feature.
+    #[serde(rename = "avif")]
+    Avif,
+    /// JPEG, for browsers without WebP support. Transparency is dropped.
+    #[serde(rename = "jpeg")]
+    Jpeg,
+    /// Lossless PNG, for browsers without WebP support. Ignores the quality.
+    #[serde(rename = "png")]
+    Png,
+    /// Lossless JPEG XL. Ignores the quality. Encoding requires the `jxl` feature.
+    #[serde(rename = "jxl")]
+    Jxl,
+}
+
+impl OutputFormat {
+    /// The MIME type, as used in the `type` attribute of `<source>`.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Avif => "image/avif",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jxl => "image/jxl",
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::WebP => "webp",
+            OutputFormat::Avif => "avif",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Jxl => "jxl",
+        }
+    }
+
+    // Universally supported format closest to the source: PNG for formats that can be lossless or transparent, JPEG otherwise.
+    pub(crate) fn fallback_for(src: &str) -> Self {
+        let path = src.split(['?', '#']).next().unwrap_or(src);
+        let extension = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png" | "gif" | "bmp" | "ico" | "tif" | "tiff") => OutputFormat::Png,
+            _ => OutputFormat::Jpeg,
+        }
+    }
+
+    // Best format listed in an `Accept` header. Wildcards are ignored, since browsers send `*/*` whatever they support.
+    #[cfg(feature = "ssr")]
+    pub(crate) fn negotiate(accept: &str, src: &str, lossless: bool) -> Self {
+        let accepts = |mime: &str| {
+            accept.split(',').any(|range| {
+                let mut params = range.split(';').map(str::trim);
+                params.next().is_some_and(|m| m.eq_ignore_ascii_case(mime))
+                    && !params.any(|param| {
+                        param
+                            .strip_prefix("q=")
+                            .and_then(|q| q.parse::<f32>().ok())
+                            .is_some_and(|q| q <= 0.0)
+                    })
+            })
+        };
+        // Only JPEG XL, WebP and PNG are encoded losslessly.
+        if lossless {
+            return if cfg!(feature = "jxl") && accepts("image/jxl") {
+                OutputFormat::Jxl
+            } else if accepts("image/webp") {
+                OutputFormat::WebP
+            } else {
+                OutputFormat::Png
+            };
+        }
+        let fallback = OutputFormat::fallback_for(src);
+        // JPEG XL is encoded losslessly too, so it's never picked for lossy requests.
+        if cfg!(feature = "avif") && accepts("image/avif") {
+            OutputFormat::Avif
+        // Lossless WebP photos are far larger than JPEGs, so without libwebp photos are served as JPEG.
+        } else if accepts("image/webp") && (LOSSY_WEBP || fallback == OutputFormat::Png) {
+            OutputFormat::WebP
+        } else {
+            fallback
+        }
+    }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
-pub(crate) enum CachedImageOption {
-    #[serde(rename = "r")]
-    Resize(Resize),
-    #[serde(rename = "b")]
-    Blur(Blur),
-}
+    #[cfg(feature = "ssr")]
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "webp" => Some(OutputFormat::WebP),
+            "avif" => Some(OutputFormat::Avif),
+            "jpg" => Some(OutputFormat::Jpeg),
+            "png" => Some(OutputFormat::Png),
+            "jxl" => Some(OutputFormat::Jxl),
+            _ => None,
+        }
+    }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
-#[serde(rename = "r")]
-pub(crate) struct Resize {
-    #[serde(rename = "w")]
-    pub width: u32,
-    #[serde(rename = "h")]
-    pub height: u32,
-    #[serde(rename = "q")]
-    pub quality: u8,
+    fn is_default(&self) -> bool {
+        *self == OutputFormat::default()
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Hash)]
@@ -286,50 +4235,470 @@ pub(crate) struct Blur {
     pub svg_height: u32,
     #[serde(rename = "s")]
     pub sigma: u8,
+    #[serde(rename = "m", default, skip_serializing_if = "Placeholder::is_default")]
+    pub mode: Placeholder,
+}
+
+/// How blur placeholders are rendered by `<Image blur=true/>`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Placeholder {
+    /// A tiny blurred WebP wrapped in an SVG, see `BlurTemplate`. Inlined as a data URI of a few KB.
+    #[default]
+    #[serde(rename = "svg")]
+    Svg,
+    /// A [BlurHash](https://blurha.sh) string of about 30 bytes, rendered as CSS gradients.
+    /// Much smaller in the HTML than SVG placeholders, but less detailed. Requires the `blurhash` feature.
+    #[serde(rename = "bh")]
+    BlurHash,
+    /// The tiny WebP inlined as the first source of the `<img>`, blurred with a CSS `filter` until
+    /// the optimized image has loaded. Avoids the SVG filter, which some browsers render differently.
+    /// The image is swapped in once the app has hydrated, like with `<Image progressive=true/>`.
+    #[serde(rename = "css")]
+    CssBlur,
+}
+
+impl Placeholder {
+    fn extension(&self) -> &'static str {
+        match self {
+            Placeholder::Svg => "svg",
+            Placeholder::BlurHash => "bh",
+            Placeholder::CssBlur => "uri",
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Placeholder::default()
+    }
 }
 
 #[cfg(feature = "ssr")]
 #[derive(Debug, thiserror::Error)]
+#[allow(clippy::enum_variant_names)]
+/// Errors that can occur while creating an optimized image.
 pub enum CreateImageError {
     // Unexpected(String),
+    /// The source image could not be decoded or processed.
     #[error("Image Error: {0}")]
     ImageError(#[from] image::ImageError),
+    /// The blocking encode task failed.
     #[error("Join Error: {0}")]
     JoinError(#[from] tokio::task::JoinError),
+    /// Reading or writing a file failed.
     #[error("IO Error: {0}")]
     IOError(#[from] std::io::Error),
+    /// The image belongs to a tenant that isn't registered on the optimizer.
+    #[error("Unknown Tenant: {0}")]
+    UnknownTenant(String),
+    /// The source isn't in the optimizer's allowed sources.
+    #[error("Source Not Allowed: {0}")]
+    SourceNotAllowed(String),
+    /// The source path resolves outside of the site root, with `..` segments or a symlink.
+    #[error("Forbidden Path: {0}")]
+    ForbiddenPath(String),
+    /// The source image doesn't exist.
+    #[error("Source Not Found: {0}")]
+    SourceNotFound(String),
+    /// The requested size exceeds [`OptimizerConfig::max_dimensions`].
+    #[error("Too Large: {0}x{1}")]
+    TooLarge(u32, u32),
+    /// The requested quality is outside of [`OptimizerConfig::quality_range`].
+    #[error("Quality Out Of Range: {0}")]
+    QualityOutOfRange(u8),
+    /// The image has more than [`MAX_TRANSFORMS`] transforms.
+    #[error("Too Many Transforms: {0}")]
+    TooManyTransforms(usize),
+    /// The requested image option needs a cargo feature that is disabled.
+    #[error("Feature Disabled: {0}")]
+    FeatureDisabled(&'static str),
+    /// The image URL isn't signed, or its signature doesn't match, see [`ImageOptimizerBuilder::signing_key`].
+    #[error("Invalid Signature")]
+    InvalidSignature,
+    /// The image wasn't rendered by the app nor created ahead of time, see [`OptimizerConfig::strict`].
+    #[error("Unknown Image: {0}")]
+    UnknownImage(String),
+    /// The optimizer is shutting down, see [`ImageOptimizer::shutdown`].
+    #[error("Shutting Down")]
+    ShuttingDown,
+    /// A request to the cache backend failed, see [`crate::CacheBackend`].
+    #[error("Storage Error: {0}")]
+    StorageError(String),
+    /// Fetching a page or sitemap failed.
+    #[cfg(feature = "fetch")]
+    #[error("Fetch Error: {0}")]
+    FetchError(#[from] reqwest::Error),
+    /// A remote source is larger than the 32 MiB download limit.
+    #[cfg(feature = "fetch")]
+    #[error("Remote Source Too Large: {0}")]
+    RemoteTooLarge(String),
+    /// The BlurHash of a placeholder could not be computed.
+    #[cfg(feature = "blurhash")]
+    #[error("BlurHash Error: {0}")]
+    BlurHashError(#[from] blurhash::Error),
+}
+
+/// Scales the quality of high pixel density (2x, 3x) variants.
+///
+/// Compression artifacts are hard to see on high density screens, so retina variants
+/// can use a much lower quality for the same perceived result, roughly halving their size.
+///
+/// The curve is a list of `(density, factor)` points. The factor multiplies the requested quality,
+/// and is linearly interpolated between points and clamped outside of them.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let curve = DprQualityCurve::default();
+/// assert_eq!(curve.quality(75, 1.0), 75);
+/// assert_eq!(curve.quality(75, 2.0), 55);
+///
+/// let flat = DprQualityCurve::new([(1.0, 1.0)]);
+/// assert_eq!(flat.quality(75, 3.0), 75);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DprQualityCurve {
+    points: Vec<(f32, f32)>,
 }
 
+impl DprQualityCurve {
+    /// Creates a curve from `(density, factor)` points.
+    pub fn new(points: impl IntoIterator<Item = (f32, f32)>) -> Self {
+        let mut points: Vec<(f32, f32)> = points.into_iter().collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { points }
+    }
+
+    /// The quality to use for a variant rendered at `density`.
+    pub fn quality(&self, quality: u8, density: f32) -> u8 {
+        let factor = match self.points.as_slice() {
+            [] => 1.0,
+            [(d, f), ..] if density <= *d => *f,
+            [.., (d, f)] if density >= *d => *f,
+            points => points
+                .windows(2)
+                .find(|w| density >= w[0].0 && density <= w[1].0)
+                .map(|w| {
+                    let (d0, f0) = w[0];
+                    let (d1, f1) = w[1];
+                    f0 + (f1 - f0) * (density - d0) / (d1 - d0)
+                })
+                .unwrap_or(1.0),
+        };
+        (quality as f32 * factor).round().clamp(1.0, 100.0) as u8
+    }
+}
+
+impl Default for DprQualityCurve {
+    fn default() -> Self {
+        Self::new([(1.0, 1.0), (2.0, 0.733), (3.0, 0.6)])
+    }
+}
+
+/// How optimized image URLs are encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UrlScheme {
+    /// Options in the query string, e.g. `/cache/image?src=/hero.jpg&option[r][w]=800&option[r][h]=600&option[r][q]=75`.
+    #[default]
+    Query,
+    /// Options in a path segment, e.g. `/cache/image/w_800,h_600,q_75/hero.jpg`.
+    /// Use it behind CDNs and proxies that ignore or normalize query strings when caching.
+    Path,
+    /// The URL of the cached file itself, e.g. `/cache/image/default/3f/3fa2...c9.webp`, for sites exported
+    /// to static hosting without the image handler. Nothing creates missing images, so every image must be
+    /// created ahead of time, see [`ImageOptimizer::export`](crate::ImageOptimizer::export).
+    /// Only images of the default root are served from the site, tenants with their own root aren't.
+    Static,
+}
+
+// Characters escaped in the source path of path encoded URLs. Slashes are kept.
+pub(crate) const PATH_ENCODE_SET: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
 impl CachedImage {
+    pub(crate) fn get_url(&self, handler_path: &str, url_scheme: UrlScheme) -> String {
+        match url_scheme {
+            UrlScheme::Query => self.get_url_encoded(handler_path),
+            UrlScheme::Path => self.get_path_encoded(handler_path),
+            // The handler path doesn't lead to the cached files, see `ImageConfig::url`.
+            UrlScheme::Static => self.get_path_encoded(handler_path),
+        }
+    }
+
     pub(crate) fn get_url_encoded(&self, handler_path: impl AsRef<str>) -> String {
-        let params = serde_qs::to_string(&self).unwrap();
+        let params = serde_qs::to_string(&self.without_server_overrides()).unwrap();
         format!("{}?{}", handler_path.as_ref(), params)
     }
 
+    pub(crate) fn get_path_encoded(&self, handler_path: impl AsRef<str>) -> String {
+        let mut spec = match &self.option {
+            CachedImageOption::Resize(Resize {
+                width,
+                height,
+                quality,
+                format,
+                fit,
+                filter,
+                sharpen,
+                metadata: _,
+                no_upscale,
+                lossless,
+                transforms,
+                watermark: _,
+            }) => {
+                let mut spec = vec![
+                    format!("w_{width}"),
+                    format!("h_{height}"),
+                    format!("q_{quality}"),
+                ];
+                if !format.is_default() {
+                    spec.push(format!("f_{}", format.extension()));
+                }
+                if !fit.is_default() {
+                    spec.push(format!("fit_{fit}"));
+                }
+                if let Some(filter) = filter {
+                    spec.push(format!("rf_{}", filter.as_str()));
+                }
+                if let Some(sharpen) = sharpen {
+                    spec.push(format!("sh_{sharpen}"));
+                }
+                if let Some(no_upscale) = no_upscale {
+                    spec.push(format!("nu_{}", *no_upscale as u8));
+                }
+                if *lossless {
+                    spec.push("ll_1".to_string());
+                }
+                if !transforms.is_empty() {
+                    let transforms: Vec<_> = transforms.iter().map(Transform::to_string).collect();
+                    spec.push(format!("tf_{}", transforms.join(".")));
+                }
+                spec
+            }
+            CachedImageOption::Blur(Blur {
+                width,
+                height,
+                svg_width,
+                svg_height,
+                sigma,
+                mode,
+            }) => {
+                let mut spec = vec![
+                    "blur".to_string(),
+                    format!("w_{width}"),
+                    format!("h_{height}"),
+                    format!("sw_{svg_width}"),
+                    format!("sh_{svg_height}"),
+                    format!("s_{sigma}"),
+                ];
+                if !mode.is_default() {
+                    spec.push(format!("m_{}", mode.extension()));
+                }
+                spec
+            }
+        };
+        let text = |value: &str| {
+            percent_encoding::utf8_percent_encode(value, percent_encoding::NON_ALPHANUMERIC)
+                .to_string()
+        };
+        if let Some(tenant) = &self.tenant {
+            spec.push(format!("t_{}", text(tenant)));
+        }
+        // Remote sources are a single segment, so they aren't mistaken for local paths.
+        let src = if is_remote(&self.src) {
+            text(&self.src)
+        } else {
+            percent_encoding::utf8_percent_encode(self.src.trim_start_matches('/'), PATH_ENCODE_SET)
+                .to_string()
+        };
+        format!(
+            "{}/{}/{src}",
+            handler_path.as_ref().trim_end_matches('/'),
+            spec.join(",")
+        )
+    }
+
+    // Parses the part of a path encoded URL that follows the handler path.
     #[cfg(feature = "ssr")]
-    pub(crate) fn get_file_path(&self) -> String {
-        use base64::{engine::general_purpose, Engine as _};
-        // I'm worried this name will become too long.
-        // names are limited to 255 bytes on most filesystems.
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
+    pub(crate) fn from_path_encoded(path: &str) -> Option<Self> {
+        let (spec, src) = path.trim_start_matches('/').split_once('/')?;
+        let src = percent_encoding::percent_decode_str(src)
+            .decode_utf8()
+            .ok()?;
 
-        let encode = serde_qs::to_string(&self).unwrap();
-        let encode = general_purpose::STANDARD.encode(encode);
+        let mut blur = false;
+        let mut values = std::collections::HashMap::new();
+        for token in spec.split(',') {
+            if token == "blur" {
+                blur = true;
+            } else {
+                let (key, value) = token.split_once('_')?;
+                values.insert(key, value);
+            }
+        }
+        let value = |key: &str| values.get(key)?.parse::<u32>().ok();
+        let text = |key: &str| {
+            values.get(key).and_then(|value| {
+                percent_encoding::percent_decode_str(value)
+                    .decode_utf8()
+                    .ok()
+                    .map(|value| value.into_owned())
+            })
+        };
 
-        let mut path = path_from_segments(vec!["cache/image", &encode, &self.src]);
+        let option = if blur {
+            CachedImageOption::Blur(Blur {
+                width: value("w")?,
+                height: value("h")?,
+                svg_width: value("sw")?,
+                svg_height: value("sh")?,
+                sigma: value("s")?.try_into().ok()?,
+                mode: match values.get("m") {
+                    None => Placeholder::default(),
+                    Some(&"bh") => Placeholder::BlurHash,
+                    Some(&"uri") => Placeholder::CssBlur,
+                    Some(_) => return None,
+                },
+            })
+        } else {
+            CachedImageOption::Resize(Resize {
+                width: value("w")?,
+                height: value("h")?,
+                quality: value("q")?.try_into().ok()?,
+                format: match values.get("f") {
+                    None => OutputFormat::default(),
+                    Some(format) => OutputFormat::from_extension(format)?,
+                },
+                fit: match values.get("fit") {
+                    None => ResizeMode::default(),
+                    Some(fit) => fit.parse().ok()?,
+                },
+                filter: match values.get("rf") {
+                    None => None,
+                    Some(filter) => Some(ResizeFilter::from_name(filter)?),
+                },
+                sharpen: match values.get("sh") {
+                    None => None,
+                    Some(sharpen) => Some(sharpen.parse().ok()?),
+                },
+                metadata: None,
+                no_upscale: match values.get("nu") {
+                    None => None,
+                    Some(&"1") => Some(true),
+                    Some(&"0") => Some(false),
+                    Some(_) => return None,
+                },
+                lossless: match values.get("ll") {
+                    None => false,
+                    Some(&"1") => true,
+                    Some(_) => return None,
+                },
+                watermark: None,
+                transforms: match values.get("tf") {
+                    None => Vec::new(),
+                    Some(transforms) => transforms
+                        .split('.')
+                        .map(|transform| transform.parse().ok())
+                        .collect::<Option<_>>()?,
+                },
+            })
+        };
 
-        if let CachedImageOption::Resize { .. } = self.option {
-            path.set_extension("webp");
+        let src = if is_remote(&src) {
+            src.into_owned()
         } else {
-            path.set_extension("svg");
+            format!("/{src}")
         };
 
+        Some(CachedImage {
+            src,
+            option,
+            tenant: text("t"),
+            cache_control: None,
+        })
+    }
+
+    // Files are named after a digest of the spec, e.g. `cache/image/prod/3f/3fa2...c9.webp`,
+    // so names stay short however long the source path or options are.
+    // A sidecar `.json` next to each file holds the spec, see `CachedImage::from_file_path`.
+    #[cfg(feature = "ssr")]
+    pub(crate) fn get_file_path(&self, cache_dir: &str, settings: &str) -> String {
+        let digest = self.digest(settings);
+        let mut path = path_from_segments(vec![cache_dir, &digest[..2], &digest]);
+        path.set_extension(self.extension());
+        path.as_path().to_string_lossy().to_string()
+    }
+
+    // URL of the cached file, served as-is from the directory at `static_dir`, see `UrlScheme::Static`.
+    pub(crate) fn get_static_url(&self, static_dir: &str, settings: &str) -> String {
+        let digest = self.digest(settings);
+        format!(
+            "{static_dir}/{}/{digest}.{}",
+            &digest[..2],
+            self.extension()
+        )
+    }
+
+    // `settings` is the `OptimizerConfig::encode_digest` of the optimizer, resized images are encoded with.
+    fn digest(&self, settings: &str) -> String {
+        use sha2::{Digest, Sha256};
+
+        // Response headers don't change the file contents.
+        let mut spec = serde_qs::to_string(&self.without_cache_control()).unwrap();
+        if matches!(self.option, CachedImageOption::Resize(_)) && !settings.is_empty() {
+            spec.push('#');
+            spec.push_str(settings);
+        }
+        Sha256::digest(spec.as_bytes())[..16]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    // Name used before content-hash naming: the base64 encoded spec followed by the source path.
+    // Could exceed the 255 byte file name limit of most filesystems. Only used to migrate old caches.
+    #[cfg(all(feature = "ssr", feature = "legacy-cache"))]
+    pub(crate) fn legacy_file_path(&self, cache_dir: &str) -> String {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let encode = serde_qs::to_string(&self.without_cache_control()).unwrap();
+        let encode = general_purpose::STANDARD.encode(encode);
+
+        let mut path = path_from_segments(vec![cache_dir, &encode, &local_src(&self.src)]);
+        path.set_extension(self.extension());
         path.as_path().to_string_lossy().to_string()
     }
 
+    fn extension(&self) -> &'static str {
+        match &self.option {
+            CachedImageOption::Resize(resize) => resize.format.extension(),
+            CachedImageOption::Blur(blur) => blur.mode.extension(),
+        }
+    }
+
+    // Resolves the spec of a cached file from its sidecar, or from the name of a legacy file.
     #[allow(dead_code)]
     #[cfg(feature = "ssr")]
-    // TODO: Fix this. Super Yuck.
     pub(crate) fn from_file_path(path: &str) -> Option<Self> {
+        if let Ok(sidecar) = std::fs::read(sidecar_path(std::path::Path::new(path))) {
+            return serde_json::from_slice(&sidecar).ok();
+        }
+        Self::from_legacy_file_path(path)
+    }
+
+    #[cfg(all(feature = "ssr", feature = "legacy-cache"))]
+    fn from_legacy_file_path(path: &str) -> Option<Self> {
         use base64::{engine::general_purpose, Engine as _};
         path.split('/')
             .filter_map(|s| {
@@ -341,16 +4710,213 @@ impl CachedImage {
             .find_map(|encoded| serde_qs::from_str(&encoded).ok())
     }
 
+    #[cfg(all(feature = "ssr", not(feature = "legacy-cache")))]
+    fn from_legacy_file_path(_: &str) -> Option<Self> {
+        None
+    }
+
     #[cfg(feature = "ssr")]
+    #[cfg_attr(
+        not(any(feature = "axum-handler", feature = "actix")),
+        allow(dead_code)
+    )]
     pub(crate) fn from_url_encoded(url: &str) -> Result<CachedImage, serde_qs::Error> {
-        let url = url.split('?').filter(|s| *s != "?").last().unwrap_or(url);
+        let url = url.split('?').rfind(|s| *s != "?").unwrap_or(url);
         let result: Result<CachedImage, serde_qs::Error> = serde_qs::from_str(url);
-        result
+        result.map(|image| image.without_server_overrides())
+    }
+}
+
+/// Header information of a source image, see [`ImageOptimizer::probe`].
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceInfo {
+    /// Width of the source in pixels.
+    pub width: u32,
+    /// Height of the source in pixels.
+    pub height: u32,
+    /// Format sniffed from the contents of the file.
+    pub format: image::ImageFormat,
+    /// Size of the file in bytes.
+    pub bytes: u64,
+}
+
+// A source and the tenant it belongs to.
+pub(crate) type SourceKey = (Option<String>, String);
+
+// Dimensions of a source of `source` dimensions once resized, following `image::DynamicImage::resize` for `Contain`.
+pub(crate) fn fitted_dimensions(
+    source: (u32, u32),
+    width: u32,
+    height: u32,
+    fit: ResizeMode,
+) -> (u32, u32) {
+    let (source_width, source_height) = source;
+    if fit != ResizeMode::Contain || source_width == 0 || source_height == 0 {
+        return (width, height);
+    }
+    let ratio = f64::min(
+        width as f64 / source_width as f64,
+        height as f64 / source_height as f64,
+    );
+    let scale = |side: u32| ((side as f64 * ratio).round() as u32).max(1);
+    (scale(source_width), scale(source_height))
+}
+
+// Whether the source is an SVG. Vector images are served as-is, they can't be rasterized.
+pub(crate) fn is_svg(src: &str) -> bool {
+    let path = src.split(['?', '#']).next().unwrap_or(src);
+    path.len() >= 4 && path[path.len() - 4..].eq_ignore_ascii_case(".svg")
+}
+
+// Whether the source is a remote `http(s)` URL rather than a path relative to the root.
+pub(crate) fn is_remote(src: &str) -> bool {
+    src.starts_with("https://") || src.starts_with("http://")
+}
+
+// Host of a remote source without its port, e.g. `cdn.example.com`. `None` for local sources and invalid hosts.
+fn remote_host(src: &str) -> Option<&str> {
+    let rest = src
+        .strip_prefix("https://")
+        .or_else(|| src.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    // Credentials in the URL could be used to disguise the host.
+    if authority.contains('@') {
+        return None;
+    }
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => host,
+        _ => authority,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+// Remote sources are downloaded within this time, so a slow host can't hold an encode permit.
+#[cfg(feature = "fetch")]
+const REMOTE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+// Largest remote source downloaded, see `CreateImageError::RemoteTooLarge`.
+#[cfg(feature = "fetch")]
+const MAX_REMOTE_BYTES: u64 = 32 * 1024 * 1024;
+
+// Client downloading remote sources. Redirects are only followed to allowed domains,
+// so an open redirect on one of them can't reach other hosts.
+#[cfg(feature = "fetch")]
+fn remote_client(config: std::sync::Arc<std::sync::RwLock<OptimizerConfig>>) -> reqwest::Client {
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        let config = config.read().expect("Optimizer config poisoned");
+        if attempt.previous().len() >= 5 {
+            attempt.error("Too many redirects")
+        } else if remote_domain_allowed(&config.remote_domains, attempt.url().as_str()) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+    reqwest::Client::builder()
+        .redirect(policy)
+        .timeout(REMOTE_TIMEOUT)
+        .build()
+        .expect("Failed to create the HTTP client")
+}
+
+pub(crate) fn remote_domain_allowed(domains: &[String], src: &str) -> bool {
+    let Some(host) = remote_host(src) else {
+        return false;
+    };
+    let host = host.to_ascii_lowercase();
+    domains.iter().any(|domain| {
+        let domain = domain.to_ascii_lowercase();
+        match domain.strip_prefix("*.") {
+            Some(parent) => host
+                .strip_suffix(parent)
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            None => host == domain,
+        }
+    })
+}
+
+// Path of a source relative to the root. Remote sources are downloaded to `cache/remote/<host>/`,
+// named after the hash of their URL so different query strings don't collide.
+#[cfg(feature = "ssr")]
+fn local_src(src: &str) -> std::borrow::Cow<'_, str> {
+    use sha2::{Digest, Sha256};
+    match remote_host(src) {
+        Some(host) => {
+            let name = crate::storage::hex(&Sha256::digest(src.as_bytes())[..16]);
+            format!("cache/remote/{host}/{name}")
+                .replace(':', "_")
+                .into()
+        }
+        None => src.into(),
+    }
+}
+
+// Removes the `dpr` client override from the query string of an image URL.
+// An unparsable density is kept as `Some(0)`, so it gets rejected rather than ignored.
+#[cfg(feature = "ssr")]
+fn split_client_dpr(url: &str) -> (std::borrow::Cow<'_, str>, Option<u8>) {
+    let (url, dpr) = split_query_param(url, "dpr");
+    (url, dpr.map(|value| value.parse().unwrap_or(0)))
+}
+
+// Removes a query parameter from a URL, returning its value.
+#[cfg(feature = "ssr")]
+fn split_query_param<'a>(url: &'a str, name: &str) -> (std::borrow::Cow<'a, str>, Option<String>) {
+    let Some((base, query)) = url.split_once('?') else {
+        return (url.into(), None);
+    };
+    let mut value = None;
+    let rest: Vec<&str> = query
+        .split('&')
+        .filter(|pair| match pair.split_once('=') {
+            Some((key, found)) if key == name => {
+                value = Some(found.to_string());
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    if value.is_none() {
+        return (url.into(), None);
     }
+    let url = if rest.is_empty() {
+        base.to_string()
+    } else {
+        format!("{base}?{}", rest.join("&"))
+    };
+    (url.into(), value)
+}
+
+// Truncated HMAC of an image spec, signing its URL in either scheme.
+// The path scheme always roots the source, so the leading `/` isn't signed. Neither are server overrides,
+// which only the instance that rendered the URL knows, so other instances can verify it too.
+#[cfg(feature = "ssr")]
+fn url_signature(key: &[u8], image: &CachedImage) -> String {
+    let image = image.without_server_overrides();
+    let image = CachedImage {
+        src: image.src.trim_start_matches('/').to_string(),
+        ..image
+    };
+    let spec = serde_qs::to_string(&image).expect("Failed to encode image");
+    crate::storage::hex(&crate::storage::hmac_sha256(key, spec.as_bytes())[..16])
+}
+
+// Truncated HMAC of an original, signing its URL. Prefixed so it never matches the signature of an image spec.
+#[cfg(feature = "ssr")]
+fn original_signature(key: &[u8], original: &OriginalImage) -> String {
+    let query = serde_qs::to_string(original).expect("Failed to encode original");
+    let spec = format!("original:{query}");
+    crate::storage::hex(&crate::storage::hmac_sha256(key, spec.as_bytes())[..16])
 }
 
 #[cfg(feature = "ssr")]
-fn path_from_segments(segments: Vec<&str>) -> std::path::PathBuf {
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) fn path_from_segments(segments: Vec<&str>) -> std::path::PathBuf {
     segments
         .into_iter()
         .map(|s| s.trim_start_matches('/'))
@@ -359,6 +4925,20 @@ fn path_from_segments(segments: Vec<&str>) -> std::path::PathBuf {
         .collect()
 }
 
+// Rejects a canonical source path outside of its canonical root.
+#[cfg(feature = "ssr")]
+fn contained(
+    root: &std::path::Path,
+    path: &std::path::Path,
+    src: &str,
+) -> Result<(), CreateImageError> {
+    if path.starts_with(root) {
+        Ok(())
+    } else {
+        Err(CreateImageError::ForbiddenPath(src.to_string()))
+    }
+}
+
 #[cfg(feature = "ssr")]
 async fn file_exists<P>(path: P) -> bool
 where
@@ -367,6 +4947,15 @@ where
     tokio::fs::metadata(path).await.is_ok()
 }
 
+// Whether a cached file was completely written. Files are renamed into place once written,
+// but an empty file, e.g. left by a full disk or an older version, is created again instead of served.
+#[cfg(feature = "ssr")]
+async fn cache_file_complete(path: &std::path::Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.len() > 0)
+}
+
 #[cfg(feature = "ssr")]
 fn create_nested_if_needed<P>(path: P) -> std::io::Result<()>
 where
@@ -380,7 +4969,7 @@ where
 }
 
 // Test module
-#[cfg(test)]
+#[cfg(all(test, feature = "ssr"))]
 mod optimizer_tests {
     use super::*;
 
@@ -392,7 +4981,18 @@ mod optimizer_tests {
                 quality: 75,
                 width: 100,
                 height: 100,
+                format: OutputFormat::default(),
+                fit: ResizeMode::default(),
+                filter: None,
+                sharpen: None,
+                metadata: None,
+                no_upscale: None,
+                lossless: false,
+                transforms: Vec::new(),
+                watermark: None,
             }),
+            tenant: None,
+            cache_control: None,
         };
 
         let encoded = img.get_url_encoded("/cache/image/test");
@@ -402,9 +5002,516 @@ mod optimizer_tests {
         assert!(img == decoded);
     }
 
-    const TEST_IMAGE: &str = "./example/start-axum/public/cute_ferris.png";
+    #[test]
+    fn url_encode_tenant() {
+        let img = CachedImage::resize("test.jpg", 100, 100, 75).with_tenant("acme");
+
+        let encoded = img.get_url_encoded("/cache/image");
+        let decoded = CachedImage::from_url_encoded(&encoded).unwrap();
+
+        assert_eq!(img, decoded);
+        assert!(!CachedImage::resize("test.jpg", 100, 100, 75)
+            .get_url_encoded("/cache/image")
+            .contains("t="));
+    }
+
+    #[test]
+    fn path_encode() {
+        let images = [
+            CachedImage::resize("/assets/hero image.jpg", 800, 600, 75),
+            CachedImage::blur("/cute_ferris.png").with_tenant("acme"),
+            CachedImage::resize("/cute_ferris.png", 400, 300, 60).with_format(OutputFormat::Avif),
+        ];
+
+        for img in images {
+            let encoded = img.get_path_encoded("/cache/image");
+            let path = encoded.strip_prefix("/cache/image").unwrap();
+            let decoded = CachedImage::from_path_encoded(path).unwrap();
+            assert_eq!(img, decoded);
+        }
+
+        assert_eq!(
+            CachedImage::resize("/assets/hero.jpg", 800, 600, 75).get_path_encoded("/cache/image"),
+            "/cache/image/w_800,h_600,q_75/assets/hero.jpg"
+        );
+    }
+
+    #[test]
+    fn allowed_sources() {
+        let config = OptimizerConfig {
+            allowed_sources: vec!["/assets/".to_string()],
+            ..Default::default()
+        };
+        assert!(config.is_allowed("/assets/hero.jpg"));
+        assert!(config.is_allowed("assets/hero.jpg"));
+        assert!(!config.is_allowed("/private/hero.jpg"));
+        assert!(OptimizerConfig::default().is_allowed("/private/hero.jpg"));
+    }
+
+    #[test]
+    fn resize_filter_overrides() {
+        let config = OptimizerConfig {
+            resize_filter_overrides: vec![
+                (1024, ResizeFilter::CatmullRom),
+                (128, ResizeFilter::Triangle),
+            ],
+            resize_filter: ResizeFilter::Lanczos3,
+            ..Default::default()
+        };
+        assert_eq!(config.resize_filter_for(64), ResizeFilter::Triangle);
+        assert_eq!(config.resize_filter_for(128), ResizeFilter::Triangle);
+        assert_eq!(config.resize_filter_for(640), ResizeFilter::CatmullRom);
+        assert_eq!(config.resize_filter_for(1920), ResizeFilter::Lanczos3);
+    }
+
+    #[test]
+    fn auto_tune() {
+        let ms = std::time::Duration::from_millis;
+        let mut tune = AutoTune::new(2);
+        assert_eq!(tune.record(ms(10), 100), None);
+        // A spike lowers parallelism, down to one.
+        assert_eq!(tune.record(ms(100), 100), Some(1));
+        assert_eq!(tune.record(ms(100), 100), None);
+        // Larger images are expected to take longer.
+        assert_eq!(tune.record(ms(100), 1000), None);
+        // Recovers after a few healthy encodes.
+        assert_eq!(tune.record(ms(10), 100), None);
+        assert_eq!(tune.record(ms(10), 100), None);
+        assert_eq!(tune.record(ms(10), 100), Some(2));
+    }
+
+    #[test]
+    fn base_path() {
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .base_path("app/")
+            .build();
+        assert_eq!(optimizer.handler_path(), "/app/cache/image");
+
+        let image = CachedImage::resize("/hero.jpg", 100, 50, 75);
+        let url = image.get_path_encoded(optimizer.handler_path());
+        assert_eq!(url, "/app/cache/image/w_100,h_50,q_75/hero.jpg");
+        assert_eq!(optimizer.parse_image_url(&url), Some(image));
+    }
+
+    #[test]
+    fn signed_urls() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_namespace("signed")
+            .signing_key("secret")
+            .build();
+        let image = CachedImage::resize(TEST_IMAGE, 40, 40, 75);
+        let signed = |image: &CachedImage, scheme| {
+            optimizer.rendered_url(image.get_url(&optimizer.handler_path(), scheme), image)
+        };
+        let path_url = signed(&image, UrlScheme::Path);
+        let query_url = signed(&image, UrlScheme::Query);
+        let unsigned = image.get_path_encoded(optimizer.handler_path());
+        // The path scheme roots the source, so it's a different file than `image`.
+        let unsigned_image = optimizer.parse_image_url(&unsigned).unwrap();
+        std::fs::remove_file(
+            optimizer
+                .source_and_save_paths_sync(&unsigned_image)
+                .unwrap()
+                .1,
+        )
+        .ok();
+
+        assert_eq!(optimizer.parse_image_url(&query_url), Some(image.clone()));
+        runtime.block_on(async {
+            assert!(matches!(
+                optimizer.cached_file(&unsigned, None).await,
+                Err(CreateImageError::InvalidSignature)
+            ));
+            assert!(optimizer
+                .cached_file(&path_url, None)
+                .await
+                .unwrap()
+                .is_some());
+            assert!(optimizer
+                .cached_file(&query_url, None)
+                .await
+                .unwrap()
+                .is_some());
+            // Unsigned URLs are served once the image exists.
+            assert!(optimizer
+                .cached_file(&unsigned, None)
+                .await
+                .unwrap()
+                .is_some());
+
+            let tampered = path_url.replace("w_40,h_40", "w_400,h_400");
+            assert!(matches!(
+                optimizer.cached_file(&tampered, None).await,
+                Err(CreateImageError::InvalidSignature)
+            ));
+        });
+
+        // Another instance, or the same one after a restart, doesn't know the server overrides.
+        let banner = image.clone().with_cache_control("public, max-age=300");
+        let fresh = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .signing_key("secret")
+            .build();
+        for scheme in [UrlScheme::Path, UrlScheme::Query] {
+            assert_eq!(
+                fresh.signature_valid(b"secret", &signed(&banner, scheme)),
+                Some(true)
+            );
+        }
+    }
+
+    #[test]
+    fn builder_options() {
+        let optimizer = ImageOptimizer::builder()
+            .root(".")
+            .api_handler_path("/cache/image")
+            .url_scheme(UrlScheme::Path)
+            .base_path("app/")
+            .base_url("https://example.com")
+            .cache_namespace("builder/options")
+            .tenant("acme", "./example", 2)
+            .cache_control("public, max-age=60")
+            .warmup_token("token")
+            .signing_key("key")
+            .build();
+
+        assert_eq!(optimizer.url_scheme, UrlScheme::Path);
+        assert_eq!(optimizer.handler_path(), "/app/cache/image");
+        assert_eq!(optimizer.base_url.as_deref(), Some("https://example.com"));
+        assert_eq!(optimizer.cache_namespace, "builder_options");
+        assert!(optimizer.tenant_namespace(Some("acme")).is_ok());
+        assert_eq!(
+            optimizer.cache_control.as_deref(),
+            Some("public, max-age=60")
+        );
+        assert_eq!(optimizer.warmup_token.as_deref(), Some("token"));
+        assert_eq!(optimizer.signing_key.as_deref(), Some(&b"key"[..]));
+    }
+
+    #[test]
+    fn strict_mode() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .root(".")
+            .api_handler_path("/cache/image")
+            .strict(true)
+            .cache_namespace("strict")
+            .build();
+        let image = CachedImage::resize(TEST_IMAGE, 40, 40, 75);
+        let url = image.get_url_encoded(optimizer.handler_path());
+        std::fs::remove_file(optimizer.source_and_save_paths_sync(&image).unwrap().1).ok();
+
+        runtime.block_on(async {
+            assert!(matches!(
+                optimizer.cached_file(&url, None).await,
+                Err(CreateImageError::UnknownImage(_))
+            ));
+            let rendered = optimizer.rendered_url(url.clone(), &image);
+            assert_eq!(rendered, url);
+            assert!(optimizer.cached_file(&url, None).await.unwrap().is_some());
+        });
+    }
+
+    #[test]
+    fn forbidden_paths() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let root = "cache/traversal";
+        std::fs::create_dir_all(root).unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", root, 1);
+        // Resolved once, not on every request.
+        assert!(optimizer.canonical_roots.contains_key(root));
+        let climbing =
+            CachedImage::resize("/../example/start-axum/public/cute_ferris.png", 40, 40, 75);
+
+        runtime.block_on(async {
+            assert!(matches!(
+                optimizer.create_image(&climbing).await,
+                Err(CreateImageError::ForbiddenPath(_))
+            ));
+            assert!(matches!(
+                optimizer.probe(&climbing.src),
+                Err(CreateImageError::ForbiddenPath(_))
+            ));
+            #[cfg(unix)]
+            {
+                let link = format!("{root}/link.png");
+                std::fs::remove_file(&link).ok();
+                std::os::unix::fs::symlink(std::fs::canonicalize(TEST_IMAGE).unwrap(), &link)
+                    .unwrap();
+                assert!(matches!(
+                    optimizer
+                        .create_image(&CachedImage::resize("/link.png", 40, 40, 75))
+                        .await,
+                    Err(CreateImageError::ForbiddenPath(_))
+                ));
+                assert!(matches!(
+                    optimizer.probe("/link.png"),
+                    Err(CreateImageError::ForbiddenPath(_))
+                ));
+            }
+        });
+    }
+
+    #[test]
+    fn client_dpr() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image = CachedImage::resize("/hero.jpg", 100, 50, 75);
+        let path_url = image.get_path_encoded("/cache/image");
+        let query_url = image.get_url_encoded("/cache/image");
+
+        // Rejected until enabled.
+        assert_eq!(
+            optimizer.parse_image_url(&format!("{path_url}?dpr=2")),
+            None
+        );
+        assert_eq!(optimizer.parse_image_url(&path_url), Some(image.clone()));
+
+        optimizer.update_config(|config| config.max_client_dpr = 2);
+        let expected = CachedImage::resize("/hero.jpg", 200, 100, 55);
+        assert_eq!(
+            optimizer.parse_image_url(&format!("{path_url}?dpr=2")),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            optimizer.parse_image_url(&format!("{query_url}&dpr=2")),
+            Some(expected)
+        );
+        assert_eq!(
+            optimizer.parse_image_url(&format!("{path_url}?dpr=1")),
+            Some(image)
+        );
+
+        for dpr in ["3", "0", "1.5", "abc"] {
+            assert_eq!(
+                optimizer.parse_image_url(&format!("{path_url}?dpr={dpr}")),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn remote_sources() {
+        let config = OptimizerConfig {
+            remote_domains: vec!["images.example.com".into(), "*.cdn.example.org".into()],
+            ..Default::default()
+        };
+        assert!(config.is_allowed("https://images.example.com/a.jpg"));
+        assert!(config.is_allowed("http://IMAGES.example.com/a.jpg?v=2"));
+        assert!(config.is_allowed("https://eu.cdn.example.org/a.jpg"));
+        assert!(!config.is_allowed("https://cdn.example.org/a.jpg"));
+        assert!(!config.is_allowed("https://evilcdn.example.org/a.jpg"));
+        assert!(!config.is_allowed("https://images.example.com.evil.com/a.jpg"));
+        assert!(!config.is_allowed("https://images.example.com@evil.com/a.jpg"));
+        assert!(config.is_allowed("https://images.example.com:443/a.jpg"));
+        assert!(!config.is_allowed("https://images.example.com.evil.com:443/a.jpg"));
+        assert!(!OptimizerConfig::default().is_allowed("https://images.example.com/a.jpg"));
+
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image = CachedImage::resize("https://images.example.com/a b.jpg?v=2", 100, 50, 75);
+        let url = image.get_path_encoded(optimizer.handler_path());
+        assert_eq!(url.matches('/').count(), 4, "{url}");
+        assert_eq!(optimizer.parse_image_url(&url), Some(image.clone()));
+
+        let src_path = local_src(&image.src);
+        assert!(
+            src_path.starts_with("cache/remote/images.example.com/"),
+            "{src_path}"
+        );
+        assert!(image
+            .get_file_path("cache/image/test", "")
+            .ends_with(".webp"));
+    }
+
+    const TEST_IMAGE: &str = "./example/start-axum/public/cute_ferris.png";
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn limits_remote_downloads() {
+        use std::io::{Read, Write};
+
+        // Redirects to another host, or announces a body above the download limit.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).unwrap_or(0);
+                let response = if request[..read].starts_with(b"GET /redirect") {
+                    format!("HTTP/1.1 302 Found\r\nLocation: http://localhost:{port}/a.png\r\nContent-Length: 0\r\n\r\n")
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 1000000000\r\n\r\n"
+                        .to_string()
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("remote");
+        optimizer.update_config(|config| config.remote_domains = vec!["127.0.0.1".into()]);
+        let create = |path: &str| {
+            let image = CachedImage::resize(format!("http://127.0.0.1:{port}{path}"), 64, 64, 75);
+            runtime.block_on(optimizer.create_image(&image))
+        };
+        assert!(matches!(
+            create("/redirect.png"),
+            Err(CreateImageError::SourceNotAllowed(_))
+        ));
+        assert!(matches!(
+            create("/huge.png"),
+            Err(CreateImageError::RemoteTooLarge(_))
+        ));
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn downloads_outside_of_encode_slots() {
+        use std::io::{Read, Write};
+
+        // Stalls every download until released.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (requested, requests) = std::sync::mpsc::channel();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let png = std::fs::read(TEST_IMAGE).unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0; 1024]);
+                let _ = requested.send(());
+                let _ = released.recv();
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n",
+                    png.len()
+                );
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(&png);
+            }
+        });
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("remote-slots");
+        optimizer.update_config(|config| config.remote_domains = vec!["127.0.0.1".into()]);
+        let src = format!("http://127.0.0.1:{port}/stalled.png");
+        let remote = [
+            CachedImage::resize(src.clone(), 64, 64, 75),
+            CachedImage::resize(src, 32, 32, 75),
+        ];
+        let local = CachedImage::resize(TEST_IMAGE, 36, 36, 75);
+        for image in remote.iter().chain([&local]) {
+            let (source, save) = optimizer.source_and_save_paths_sync(image).unwrap();
+            std::fs::remove_file(save).ok();
+            if is_remote(&image.src) {
+                std::fs::remove_file(source).ok();
+            }
+        }
+
+        runtime.block_on(async {
+            let downloads: Vec<_> = remote
+                .iter()
+                .map(|image| {
+                    let (optimizer, image) = (optimizer.clone(), image.clone());
+                    tokio::spawn(async move { optimizer.create_image(&image).await })
+                })
+                .collect();
+            let requests = tokio::task::spawn_blocking(move || {
+                requests.recv().unwrap();
+                requests
+            })
+            .await
+            .unwrap();
+
+            // The only encode slot is free while the remote host stalls.
+            let created = tokio::time::timeout(
+                std::time::Duration::from_secs(10),
+                optimizer.create_image(&local),
+            )
+            .await;
+            assert!(matches!(created, Ok(Ok(true))));
+
+            drop(release);
+            for download in downloads {
+                assert!(download.await.unwrap().is_ok());
+            }
+            // Both variants were created from a single download.
+            assert!(requests.try_recv().is_err());
+        });
+    }
+
+    #[test]
+    fn resize_modes() {
+        let modes = [
+            ResizeMode::Cover,
+            ResizeMode::Fill,
+            ResizeMode::Crop(Gravity::BottomRight),
+            ResizeMode::Focus(FocalPoint::new(0.25, 0.4)),
+        ];
+        for fit in modes {
+            let image = CachedImage::resize("/hero.jpg", 100, 50, 75).with_fit(fit);
+            let url = image.get_path_encoded("/cache/image");
+            assert!(url.contains(&format!(",fit_{fit}/")), "{url}");
+            assert_eq!(
+                CachedImage::from_path_encoded(url.trim_start_matches("/cache/image")),
+                Some(image.clone())
+            );
+            assert_eq!(
+                CachedImage::from_url_encoded(&image.get_url_encoded("/cache/image")).unwrap(),
+                image
+            );
+        }
+        assert_eq!(
+            "crop-top-left".parse(),
+            Ok(ResizeMode::Crop(Gravity::TopLeft))
+        );
+        assert!("crop-middle".parse::<ResizeMode>().is_err());
+        assert_eq!(
+            "focus-0.25-0.4".parse(),
+            Ok(ResizeMode::Focus(FocalPoint::new(0.25, 0.4)))
+        );
+        assert!("focus-0.25".parse::<ResizeMode>().is_err());
+
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image =
+            CachedImage::resize("/hero.jpg", 100, 50, 75).with_filter(ResizeFilter::Lanczos3);
+        let url = image.get_path_encoded("/cache/image");
+        assert!(url.contains(",rf_lanczos3/"), "{url}");
+        assert_eq!(optimizer.parse_image_url(&url), Some(image.clone()));
+        assert_eq!(
+            optimizer.encode_settings(&image).resize_filter,
+            ResizeFilter::Lanczos3
+        );
+
+        // The test image is wider than tall.
+        let img = image::open(TEST_IMAGE).unwrap();
+        let filter = ResizeFilter::Triangle;
+        let contain = resize_image(&img, 64, 64, ResizeMode::Contain, filter);
+        assert!(contain.width() == 64 && contain.height() < 64);
+        for fit in [
+            ResizeMode::Cover,
+            ResizeMode::Fill,
+            ResizeMode::Crop(Gravity::Left),
+        ] {
+            let resized = resize_image(&img, 64, 64, fit, filter);
+            assert_eq!((resized.width(), resized.height()), (64, 64), "{fit}");
+        }
+        let left = resize_image(&img, 64, 64, ResizeMode::Crop(Gravity::Left), filter);
+        let right = resize_image(&img, 64, 64, ResizeMode::Crop(Gravity::Right), filter);
+        assert_ne!(left.as_bytes(), right.as_bytes());
+        let focus = ResizeMode::Focus(FocalPoint::new(0.0, 0.5));
+        assert_eq!(
+            resize_image(&img, 64, 64, focus, filter).as_bytes(),
+            left.as_bytes()
+        );
+    }
 
     #[test]
+    #[cfg(feature = "legacy-cache")]
     fn file_path() {
         let spec = CachedImage {
             src: TEST_IMAGE.to_string(),
@@ -414,12 +5521,15 @@ mod optimizer_tests {
                 svg_height: 100,
                 svg_width: 100,
                 sigma: 20,
+                mode: Placeholder::Svg,
             }),
+            tenant: None,
+            cache_control: None,
         };
 
-        let file_path = spec.get_file_path();
+        let file_path = spec.legacy_file_path("cache/image/test");
 
-        dbg!(spec.get_file_path());
+        dbg!(spec.legacy_file_path("cache/image/test"));
 
         let result = CachedImage::from_file_path(&file_path).unwrap();
 
@@ -427,6 +5537,53 @@ mod optimizer_tests {
     }
 
     #[test]
+    fn content_hash_file_names() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("hashed");
+        let long_src = format!("/{}.png", "a".repeat(300));
+        let long = CachedImage::resize(long_src, 100, 50, 75);
+        let file_name = optimizer.get_file_path(&long);
+        let file_name = file_name.rsplit('/').next().unwrap();
+        assert_eq!(
+            file_name.len(),
+            "0123456789abcdef0123456789abcdef.webp".len()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "legacy-cache")]
+    fn migrates_legacy_files() {
+        // Legacy files are moved to their new name instead of being created again.
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("hashed");
+        let legacy_file = |image: &CachedImage| {
+            let legacy = image.legacy_file_path(&optimizer.namespace_dir());
+            let path = optimizer.get_file_path(image);
+            let _ = std::fs::remove_file(&path);
+            create_optimized_image(
+                image.option.clone(),
+                TEST_IMAGE.to_string(),
+                legacy.clone(),
+                &EncodeSettings::default(),
+            )
+            .unwrap();
+            (legacy, path)
+        };
+
+        let image = CachedImage::resize(TEST_IMAGE, 64, 32, 75);
+        let (legacy, path) = legacy_file(&image);
+        assert!(!optimizer.optimize_sync(&image).unwrap());
+        assert!(!std::path::Path::new(&legacy).exists());
+        assert_eq!(CachedImage::from_file_path(&path), Some(image));
+
+        let image = CachedImage::resize(TEST_IMAGE, 32, 16, 75);
+        let (legacy, path) = legacy_file(&image);
+        assert!(!runtime.block_on(optimizer.create_image(&image)).unwrap());
+        assert!(!std::path::Path::new(&legacy).exists());
+        assert_eq!(CachedImage::from_file_path(&path), Some(image));
+    }
+
+    #[test]
+    #[cfg(feature = "blur-placeholder")]
     fn create_blur() {
         let result = create_image_blur(
             TEST_IMAGE.to_string(),
@@ -436,13 +5593,82 @@ mod optimizer_tests {
                 svg_height: 100,
                 svg_width: 100,
                 sigma: 20,
+                mode: Placeholder::Svg,
             },
+            &BlurTemplate::default(),
         );
         assert!(result.is_ok());
         println!("{}", result.unwrap());
     }
 
     #[test]
+    #[cfg(feature = "blur-placeholder")]
+    fn blur_template_render() {
+        let template =
+            BlurTemplate::new("<svg viewBox=\"0 0 {svg_width} {svg_height}\">{sigma}|{uri}</svg>");
+        let svg = template.render(40, 30, 15, "data:image/webp;base64,AA==");
+        assert_eq!(
+            svg,
+            "<svg viewBox=\"0 0 40 30\">15|data:image/webp;base64,AA==</svg>"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "blurhash")]
+    fn blurhash_placeholder() {
+        let blur = |mode| Blur {
+            width: 20,
+            height: 20,
+            svg_height: 100,
+            svg_width: 100,
+            sigma: 15,
+            mode,
+        };
+        let hash = create_image_blur(
+            TEST_IMAGE,
+            blur(Placeholder::BlurHash),
+            &BlurTemplate::default(),
+        )
+        .unwrap();
+        assert_eq!(hash.len(), 28);
+        assert_eq!(blurhash::decode(&hash, 4, 3, 1.0).unwrap().len(), 4 * 3 * 4);
+
+        // The mode is part of the cache key.
+        let image = CachedImage::blur("/hero.jpg").with_placeholder(Placeholder::BlurHash);
+        assert_ne!(image, CachedImage::blur("/hero.jpg"));
+        assert!(image.get_file_path("cache/image/test", "").ends_with(".bh"));
+        let url = image.get_path_encoded("/cache/image");
+        assert!(url.contains(",m_bh"), "{url}");
+        assert_eq!(
+            CachedImage::from_path_encoded(url.trim_start_matches("/cache/image")),
+            Some(image)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "blur-placeholder")]
+    fn css_blur_placeholder() {
+        let CachedImageOption::Blur(blur) = CachedImage::blur(TEST_IMAGE)
+            .with_placeholder(Placeholder::CssBlur)
+            .option
+        else {
+            unreachable!()
+        };
+        let uri = create_image_blur(TEST_IMAGE, blur, &BlurTemplate::default()).unwrap();
+        assert!(uri.starts_with("data:image/webp;base64,"), "{uri}");
+
+        let image = CachedImage::blur("/hero.jpg").with_placeholder(Placeholder::CssBlur);
+        assert_eq!(image.mime_type(), "text/plain; charset=utf-8");
+        let url = image.get_path_encoded("/cache/image");
+        assert!(url.contains(",m_uri"), "{url}");
+        assert_eq!(
+            CachedImage::from_path_encoded(url.trim_start_matches("/cache/image")),
+            Some(image)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "blur-placeholder")]
     fn create_and_save_blur() {
         let spec = CachedImage {
             src: TEST_IMAGE.to_string(),
@@ -452,18 +5678,999 @@ mod optimizer_tests {
                 svg_height: 100,
                 svg_width: 100,
                 sigma: 20,
+                mode: Placeholder::Svg,
             }),
+            tenant: None,
+            cache_control: None,
         };
 
-        let file_path = spec.get_file_path();
+        let file_path = spec.get_file_path("cache/image/test", "");
 
-        let result = create_optimized_image(spec.option, TEST_IMAGE.to_string(), file_path.clone());
+        let result = create_optimized_image(
+            spec.option,
+            TEST_IMAGE.to_string(),
+            file_path.clone(),
+            &EncodeSettings::default(),
+        );
 
         assert!(result.is_ok());
 
         println!("Saved SVG at {file_path}");
     }
 
+    #[test]
+    #[cfg(feature = "blur-placeholder")]
+    fn blur_keeps_aspect_ratio() {
+        let CachedImageOption::Blur(blur) = CachedImage::blur(TEST_IMAGE).option else {
+            unreachable!()
+        };
+        let template = BlurTemplate::new("{svg_width}x{svg_height}");
+        let svg = create_image_blur(TEST_IMAGE, blur, &template).unwrap();
+
+        let img = open_source(TEST_IMAGE).unwrap();
+        assert_ne!(img.width(), img.height());
+        assert_eq!(
+            svg,
+            format!(
+                "100x{}",
+                (100.0 * img.height() as f64 / img.width() as f64).round()
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "blur-placeholder")]
+    fn enqueue_blur() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        // Placeholders of a previous run are loaded from the manifest, so they aren't queued.
+        let _ = std::fs::remove_dir_all("cache/image/enqueue");
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("enqueue");
+        let spec = CachedImage::blur(TEST_IMAGE);
+
+        runtime.block_on(async {
+            optimizer.enqueue_blur(spec.clone());
+            // Queuing the same placeholder twice is a no-op.
+            optimizer.enqueue_blur(spec.clone());
+            assert_eq!(optimizer.pending_blurs.len(), 1);
+
+            for _ in 0..500 {
+                if optimizer.cache.contains_key(&spec) {
+                    break;
+                }
+                tokio::task::spawn_blocking(|| {
+                    std::thread::sleep(std::time::Duration::from_millis(20))
+                })
+                .await
+                .unwrap();
+            }
+        });
+
+        assert!(optimizer.cache.contains_key(&spec));
+    }
+
+    #[test]
+    #[cfg(feature = "blur-placeholder")]
+    fn manifest_survives_restart() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("manifest");
+        let spec = CachedImage::blur(TEST_IMAGE);
+
+        runtime.block_on(async {
+            optimizer.create_image(&spec).await.unwrap();
+            optimizer.load_blur(&spec).await.unwrap();
+        });
+
+        let restarted =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("manifest");
+        assert_eq!(
+            restarted.cache.get(&spec).map(|svg| svg.clone()),
+            optimizer.cache.get(&spec).map(|svg| svg.clone())
+        );
+        // Other namespaces keep their own manifest.
+        let other = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("other");
+        assert!(!other.cache.contains_key(&spec));
+        // Including ones derived from a clone, which leave the placeholders of the original alone.
+        let derived = optimizer.clone().with_cache_namespace("other");
+        assert!(!derived.cache.contains_key(&spec));
+        assert!(optimizer.cache.contains_key(&spec));
+    }
+
+    #[test]
+    fn builder() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_dir("/cache/custom/")
+            .default_quality(60)
+            .default_format(OutputFormat::WebP)
+            .max_dimensions(100, 100)
+            .build();
+        assert_eq!(optimizer.config().default_quality, 60);
+
+        let small = CachedImage::resize(TEST_IMAGE, 100, 50, 75);
+        assert!(optimizer.get_file_path(&small).starts_with("cache/custom/"));
+
+        runtime.block_on(async {
+            assert!(optimizer.create_image(&small).await.is_ok());
+            let large = CachedImage::resize(TEST_IMAGE, 200, 50, 75);
+            assert!(matches!(
+                optimizer.create_image(&large).await,
+                Err(CreateImageError::TooLarge(200, 50))
+            ));
+        });
+    }
+
+    #[test]
+    fn request_limits() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .max_dimensions(100, 100)
+            .quality_range(10..=80)
+            .build();
+        let url = CachedImage::resize(TEST_IMAGE, 400, 200, 95).get_url_encoded("/cache/image");
+
+        let image = optimizer.parse_image_url(&url).unwrap();
+        runtime.block_on(async {
+            assert!(matches!(
+                optimizer.create_image(&image).await,
+                Err(CreateImageError::TooLarge(400, 200))
+            ));
+            assert!(matches!(
+                optimizer
+                    .create_image(&CachedImage::resize(TEST_IMAGE, 40, 40, 95))
+                    .await,
+                Err(CreateImageError::QualityOutOfRange(95))
+            ));
+        });
+
+        optimizer.update_config(|config| config.clamp_requests = true);
+        assert_eq!(
+            optimizer.parse_image_url(&url),
+            Some(CachedImage::resize(TEST_IMAGE, 100, 50, 80))
+        );
+    }
+
+    #[test]
+    fn transform_limits() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image = CachedImage::resize(TEST_IMAGE, 40, 40, 75)
+            .with_transforms([Transform::Rotate90; MAX_TRANSFORMS + 1]);
+
+        for url in [
+            image.get_url_encoded("/cache/image"),
+            image.get_path_encoded("/cache/image"),
+        ] {
+            assert!(matches!(
+                runtime.block_on(optimizer.cached_file(&url, None)),
+                Err(CreateImageError::TooManyTransforms(9))
+            ));
+        }
+    }
+
+    #[test]
+    fn blur_request_limits() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .max_dimensions(100, 100)
+            .build();
+        let mut blur = CachedImage::blur(TEST_IMAGE);
+        if let CachedImageOption::Blur(options) = &mut blur.option {
+            options.width = 60000;
+            options.height = 30000;
+        }
+
+        for url in [
+            blur.get_url_encoded("/cache/image"),
+            blur.get_path_encoded("/cache/image"),
+        ] {
+            let image = optimizer.parse_image_url(&url).unwrap();
+            assert!(matches!(
+                runtime.block_on(optimizer.cached_file(&url, None)),
+                Err(CreateImageError::TooLarge(60000, 30000))
+            ));
+
+            optimizer.update_config(|config| config.clamp_requests = true);
+            let clamped = optimizer.parse_image_url(&url).unwrap();
+            optimizer.update_config(|config| config.clamp_requests = false);
+            let CachedImageOption::Blur(options) = clamped.option else {
+                panic!("{image:?} parsed as {clamped:?}");
+            };
+            assert_eq!((options.width, options.height), (100, 50));
+            assert_eq!((options.svg_width, options.svg_height), (100, 100));
+        }
+    }
+
+    #[test]
+    fn sweep_cache() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .root(".")
+            .cache_dir("cache/sweep")
+            .build();
+        let images: Vec<_> = [16, 24, 32]
+            .into_iter()
+            .map(|width| CachedImage::resize(TEST_IMAGE, width, width, 75))
+            .collect();
+        for image in &images {
+            optimizer.optimize_sync(image).unwrap();
+        }
+        // Without a budget nothing is evicted.
+        assert_eq!(runtime.block_on(optimizer.sweep_cache()).unwrap(), 0);
+
+        // The first image is served last, so it is kept.
+        let path =
+            |image: &CachedImage| path_from_segments(vec![".", &optimizer.get_file_path(image)]);
+        for image in images.iter().rev() {
+            optimizer.sweeper.served(path(image));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let kept = std::fs::metadata(path(&images[0])).unwrap().len()
+            + std::fs::metadata(sidecar_path(&path(&images[0])))
+                .unwrap()
+                .len();
+        optimizer.update_config(|config| config.max_cache_size_bytes = Some(kept));
+
+        assert!(runtime.block_on(optimizer.sweep_cache()).unwrap() > 0);
+        assert!(path(&images[0]).exists());
+        assert!(!path(&images[1]).exists());
+        assert!(!path(&images[2]).exists());
+        assert!(!sidecar_path(&path(&images[2])).exists());
+    }
+
+    #[test]
+    fn coalesce_concurrent_requests() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 4).with_cache_namespace("coalesce");
+        let image = CachedImage::resize(TEST_IMAGE, 120, 80, 75);
+        let _ = std::fs::remove_file(optimizer.get_file_path(&image));
+
+        let created = runtime.block_on(async {
+            let tasks: Vec<_> = (0..4)
+                .map(|_| {
+                    let optimizer = optimizer.clone();
+                    let image = image.clone();
+                    tokio::spawn(async move { optimizer.create_image(&image).await.unwrap() })
+                })
+                .collect();
+            let mut created = 0;
+            for task in tasks {
+                created += task.await.unwrap() as usize;
+            }
+            created
+        });
+        assert_eq!(created, 1);
+        assert!(optimizer.creating.is_empty());
+    }
+
+    #[test]
+    fn recreate_empty_file() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("empty");
+        let image = CachedImage::resize(TEST_IMAGE, 40, 40, 75);
+        let path = optimizer.get_file_path(&image);
+        create_nested_if_needed(&path).unwrap();
+        std::fs::write(&path, b"").unwrap();
+
+        assert!(runtime.block_on(optimizer.create_image(&image)).unwrap());
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+    }
+
+    #[test]
+    fn preserve_metadata() {
+        use image::ImageEncoder;
+
+        // A JPEG source with an EXIF segment right after the start of image marker.
+        let img = open_source(TEST_IMAGE).unwrap().to_rgb8();
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+            .write_image(
+                img.as_raw(),
+                img.width(),
+                img.height(),
+                image::ColorType::Rgb8,
+            )
+            .unwrap();
+        let exif = b"MM\0\x2a\0\0\0\x08\0\0".to_vec();
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&(2 + 6 + exif.len() as u16).to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&exif);
+        jpeg.splice(2..2, app1);
+        let src = "/cache/metadata/source.jpg";
+        create_nested_if_needed(&src[1..]).unwrap();
+        std::fs::write(&src[1..], &jpeg).unwrap();
+
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("metadata");
+        let stripped = CachedImage::resize(src, 64, 64, 75);
+        let kept = stripped.clone().with_preserve_metadata(true);
+        // Clients can't ask for the metadata, only the server keeps it.
+        let requested = format!("/cache/image?{}", serde_qs::to_string(&kept).unwrap());
+        assert_eq!(
+            optimizer.parse_image_url(&requested),
+            Some(stripped.clone())
+        );
+        optimizer.allow_image(&kept);
+        for url in [
+            kept.get_path_encoded(optimizer.handler_path()),
+            kept.get_url_encoded(optimizer.handler_path()),
+        ] {
+            assert_eq!(optimizer.parse_image_url(&url), Some(kept.clone()));
+        }
+
+        for image in [&stripped, &kept] {
+            optimizer.optimize_sync(image).unwrap();
+        }
+        let read = |image: &CachedImage| std::fs::read(optimizer.get_file_path(image)).unwrap();
+        let metadata = crate::metadata::SourceMetadata::read(&read(&kept));
+        assert_eq!(metadata.exif, Some(exif));
+        assert!(crate::metadata::SourceMetadata::read(&read(&stripped)).is_empty());
+        // Still a valid image.
+        let decoded = image::load_from_memory(&read(&kept)).unwrap();
+        assert_eq!(decoded.width().max(decoded.height()), 64);
+    }
+
+    #[test]
+    fn no_upscale() {
+        assert_eq!(
+            limit_upscale((100, 50), 400, 400, ResizeMode::Contain),
+            (100, 100)
+        );
+        assert_eq!(
+            limit_upscale((100, 50), 400, 400, ResizeMode::Cover),
+            (50, 50)
+        );
+        assert_eq!(limit_upscale((100, 50), 80, 40, ResizeMode::Fill), (80, 40));
+
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("upscale");
+        let limited = CachedImage::resize(TEST_IMAGE, 2688, 1792, 75).with_no_upscale(true);
+        assert_eq!(
+            optimizer
+                .parse_image_url(&limited.get_path_encoded(optimizer.handler_path()))
+                .map(|image| image.option),
+            Some(limited.option.clone())
+        );
+
+        optimizer.optimize_sync(&limited).unwrap();
+        let bytes = std::fs::read(optimizer.get_file_path(&limited)).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (1344, 896));
+
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .no_upscale(true)
+            .cache_namespace("upscale-default")
+            .build();
+        let cover = CachedImage::resize(TEST_IMAGE, 2000, 2000, 75).with_fit(ResizeMode::Cover);
+        optimizer.optimize_sync(&cover).unwrap();
+        let bytes = std::fs::read(optimizer.get_file_path(&cover)).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (896, 896));
+    }
+
+    #[test]
+    fn names_files_after_encode_settings() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image = CachedImage::resize(TEST_IMAGE, 64, 64, 75);
+        let blur = CachedImage::blur(TEST_IMAGE);
+        let (resized, placeholder) = (
+            optimizer.get_file_path(&image),
+            optimizer.get_file_path(&blur),
+        );
+        // Caches created with the defaults keep their names.
+        assert_eq!(resized, image.get_file_path(&optimizer.namespace_dir(), ""));
+
+        optimizer.update_config(|config| config.sharpen = 40);
+        assert_ne!(optimizer.get_file_path(&image), resized);
+        assert_eq!(optimizer.get_file_path(&blur), placeholder);
+        optimizer.update_config(|config| config.sharpen = 0);
+        assert_eq!(optimizer.get_file_path(&image), resized);
+    }
+
+    #[test]
+    fn negotiates_formats() {
+        let chrome = "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8";
+        let expected = if cfg!(feature = "avif") {
+            OutputFormat::Avif
+        } else {
+            OutputFormat::WebP
+        };
+        assert_eq!(
+            OutputFormat::negotiate(chrome, "/ferris.png", false),
+            expected
+        );
+        assert_eq!(
+            OutputFormat::negotiate("image/webp,*/*", "/ferris.png", false),
+            OutputFormat::WebP
+        );
+        let photo = if LOSSY_WEBP {
+            OutputFormat::WebP
+        } else {
+            OutputFormat::Jpeg
+        };
+        assert_eq!(
+            OutputFormat::negotiate("image/webp,*/*", "/ferris.jpg", false),
+            photo
+        );
+        assert_eq!(
+            OutputFormat::negotiate("image/avif;q=0,image/webp", "/ferris.png", false),
+            OutputFormat::WebP
+        );
+        // Old Safari, without WebP support.
+        let safari = "image/png,image/svg+xml,image/*;q=0.8,video/*;q=0.8,*/*;q=0.5";
+        assert_eq!(
+            OutputFormat::negotiate(safari, "/ferris.png", false),
+            OutputFormat::Png
+        );
+        assert_eq!(
+            OutputFormat::negotiate(safari, "/ferris.jpg", false),
+            OutputFormat::Jpeg
+        );
+    }
+
+    #[cfg(feature = "jxl")]
+    #[test]
+    fn encodes_jxl() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image = CachedImage::resize(TEST_IMAGE, 64, 64, 75).with_format(OutputFormat::Jxl);
+        assert_eq!(
+            optimizer
+                .parse_image_url(&image.get_path_encoded(optimizer.handler_path()))
+                .map(|image| image.option),
+            Some(image.option.clone())
+        );
+        optimizer.optimize_sync(&image).unwrap();
+        let path = optimizer.get_file_path(&image);
+        assert!(path.ends_with(".jxl"));
+        // The codestream signature of a bare JPEG XL file.
+        assert_eq!(&std::fs::read(path).unwrap()[..2], &[0xFF, 0x0A]);
+
+        let safari = "image/webp,image/avif,image/jxl,image/heic,image/*;q=0.8";
+        let expected = if cfg!(feature = "avif") {
+            OutputFormat::Avif
+        } else {
+            OutputFormat::WebP
+        };
+        assert_eq!(
+            OutputFormat::negotiate(safari, "/ferris.png", false),
+            expected
+        );
+        // Lossless JPEG XL photos are far larger than lossy ones, so only lossless requests get it.
+        let photo = if cfg!(feature = "avif") {
+            OutputFormat::Avif
+        } else if LOSSY_WEBP {
+            OutputFormat::WebP
+        } else {
+            OutputFormat::Jpeg
+        };
+        assert_eq!(OutputFormat::negotiate(safari, "/ferris.jpg", false), photo);
+        assert_eq!(
+            OutputFormat::negotiate(safari, "/ferris.png", true),
+            OutputFormat::Jxl
+        );
+    }
+
+    #[test]
+    fn lossless_webp() {
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("lossless");
+        let lossy = CachedImage::resize(TEST_IMAGE, 64, 64, 75);
+        let lossless = lossy.clone().with_lossless(true);
+        assert_ne!(
+            optimizer.get_file_path(&lossy),
+            optimizer.get_file_path(&lossless)
+        );
+        assert_eq!(
+            optimizer
+                .parse_image_url(&lossless.get_path_encoded(optimizer.handler_path()))
+                .map(|image| image.option),
+            Some(lossless.option.clone())
+        );
+
+        // The first chunk of a WebP file tells lossy (`VP8 `) and lossless (`VP8L`) encodings apart.
+        let chunk = |image: &CachedImage| {
+            optimizer.optimize_sync(image).unwrap();
+            std::fs::read(optimizer.get_file_path(image)).unwrap()[12..16].to_vec()
+        };
+        assert_eq!(chunk(&lossless), b"VP8L");
+        if LOSSY_WEBP {
+            assert_ne!(chunk(&lossy), b"VP8L");
+        }
+
+        let safari = "image/png,image/svg+xml,image/*;q=0.8,*/*;q=0.5";
+        assert_eq!(
+            OutputFormat::negotiate(safari, "/ferris.jpg", true),
+            OutputFormat::Png
+        );
+        assert_eq!(
+            OutputFormat::negotiate("image/avif,image/webp", "/ferris.jpg", true),
+            OutputFormat::WebP
+        );
+    }
+
+    #[test]
+    fn sharpens_images() {
+        // A soft edge between two grays.
+        let edge =
+            image::RgbImage::from_fn(8, 8, |x, _| image::Rgb([if x < 4 { 100 } else { 150 }; 3]));
+        let edge = image::DynamicImage::ImageRgb8(edge);
+        assert_eq!(sharpen_image(edge.clone(), 0), edge);
+
+        let sharpened = sharpen_image(edge, 80);
+        assert!(!sharpened.color().has_alpha());
+        let sharpened = sharpened.to_rgb8();
+        assert!(sharpened.get_pixel(3, 4)[0] < 100);
+        assert!(sharpened.get_pixel(4, 4)[0] > 150);
+
+        let image = CachedImage::resize(TEST_IMAGE, 64, 64, 75).with_sharpen(50);
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        assert_eq!(
+            optimizer
+                .parse_image_url(&image.get_path_encoded(optimizer.handler_path()))
+                .map(|image| image.option),
+            Some(image.option.clone())
+        );
+        assert!(image.get_path_encoded("/cache/image").contains("sh_50"));
+    }
+
+    #[test]
+    fn transforms() {
+        let image = CachedImage::resize(TEST_IMAGE, 100, 100, 75)
+            .with_lossless(true)
+            .with_transforms([
+                Transform::Rotate90,
+                Transform::Grayscale,
+                Transform::Brightness(-20),
+            ]);
+        assert_eq!(
+            CachedImage::from_url_encoded(&image.get_url_encoded("/cache/image")).unwrap(),
+            image
+        );
+        let path = image.get_path_encoded("/cache/image");
+        assert!(path.contains("tf_rot90.gray.bright-20"));
+        assert_eq!(
+            CachedImage::from_path_encoded(path.strip_prefix("/cache/image").unwrap())
+                .unwrap()
+                .option,
+            image.option
+        );
+        assert!("contrast".parse::<Transform>().is_err());
+
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("transforms");
+        optimizer.optimize_sync(&image).unwrap();
+        let output = image::open(optimizer.get_file_path(&image))
+            .unwrap()
+            .to_rgb8();
+        // The landscape source is turned upright.
+        assert_eq!(output.dimensions(), (67, 100));
+        assert!(output
+            .pixels()
+            .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+    }
+
+    #[test]
+    fn watermarks() {
+        let mark = "/cache/watermark/mark.png";
+        create_nested_if_needed(&mark[1..]).unwrap();
+        image::RgbaImage::from_pixel(16, 16, image::Rgba([255, 0, 0, 255]))
+            .save(&mark[1..])
+            .unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .watermark(Watermark {
+                opacity: 100,
+                margin: 0,
+                min_width: 100,
+                ..Watermark::new(mark)
+            })
+            .cache_namespace("watermark")
+            .build();
+        let red = |image: &CachedImage, x: u32, y: u32| {
+            optimizer.optimize_sync(image).unwrap();
+            let output = image::open(optimizer.get_file_path(image))
+                .unwrap()
+                .to_rgb8();
+            let (x, y) = (x.min(output.width() - 1), y.min(output.height() - 1));
+            output.get_pixel(x, y).0 == [255, 0, 0]
+        };
+
+        let image = CachedImage::resize(TEST_IMAGE, 150, 150, 75).with_lossless(true);
+        assert!(red(&image, u32::MAX, u32::MAX));
+        assert!(!red(&image, 0, 0));
+        let opted_out = image.clone().with_watermark(false);
+        // Clients can't opt out, only images rendered on the server.
+        let url = opted_out.get_url_encoded(optimizer.handler_path());
+        assert_eq!(optimizer.parse_image_url(&url), Some(image.clone()));
+        optimizer.allow_image(&opted_out);
+        assert_eq!(optimizer.parse_image_url(&url), Some(opted_out.clone()));
+        assert!(!red(&opted_out, u32::MAX, u32::MAX));
+        // Files cached with another watermark aren't served.
+        let watermarked = optimizer.get_file_path(&image);
+        optimizer.update_config(|config| config.watermark.as_mut().unwrap().opacity = 40);
+        assert_ne!(optimizer.get_file_path(&image), watermarked);
+        // Below the minimum width.
+        let thumbnail = CachedImage::resize(TEST_IMAGE, 64, 64, 75).with_lossless(true);
+        assert!(!red(&thumbnail, u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn detailed_crops() {
+        // A flat left half, and stripes on the right.
+        let img = image::RgbImage::from_fn(200, 100, |x, y| {
+            let value = if x >= 120 && (x + y) % 4 < 2 { 255 } else { 90 };
+            image::Rgb([value; 3])
+        });
+        let img = image::DynamicImage::ImageRgb8(img);
+        let focus = detailed_focal_point(&img, 80, 100);
+        assert!(focus.x() > 0.9, "{focus:?}");
+        assert_eq!(focus.y(), 0.5);
+        let flat = image::DynamicImage::ImageRgb8(image::RgbImage::new(100, 200));
+        assert_eq!(
+            detailed_focal_point(&flat, 100, 100),
+            FocalPoint::new(0.5, 0.5)
+        );
+
+        if cfg!(feature = "smartcrop") {
+            let cropped = resize_image(&img, 80, 100, ResizeMode::Cover, ResizeFilter::Nearest);
+            assert!(cropped.to_rgb8().pixels().any(|pixel| pixel[0] == 255));
+        }
+    }
+
+    #[test]
+    fn svg_passthrough() {
+        assert!(is_svg("/logo.svg"));
+        assert!(is_svg("/icons/Logo.SVG?v=2"));
+        assert!(!is_svg("/logo.svg.png"));
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        create_nested_if_needed("cache/svg/logo.svg").unwrap();
+        std::fs::write(
+            "cache/svg/logo.svg",
+            "<svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+        )
+        .unwrap();
+
+        let image = CachedImage::resize("/cache/svg/logo.svg", 100, 100, 75);
+        assert!(!optimizer.optimize_sync(&image).unwrap());
+        let url = image.get_path_encoded(optimizer.handler_path());
+        let file = runtime
+            .block_on(optimizer.cached_file(&url, None))
+            .unwrap()
+            .unwrap();
+        assert_eq!(file.path, "cache/svg/logo.svg");
+        assert_eq!(file.generation, None);
+    }
+
+    #[test]
+    fn fallback_formats() {
+        assert_eq!(OutputFormat::fallback_for("/hero.JPG"), OutputFormat::Jpeg);
+        assert_eq!(
+            OutputFormat::fallback_for("/logo.png?v=2"),
+            OutputFormat::Png
+        );
+        assert_eq!(OutputFormat::fallback_for("/photo"), OutputFormat::Jpeg);
+
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        for format in [OutputFormat::Jpeg, OutputFormat::Png] {
+            let hero = CachedImage::resize("/hero.jpg", 64, 64, 75).with_format(format);
+            let url = hero.get_path_encoded(optimizer.handler_path());
+            assert_eq!(optimizer.parse_image_url(&url), Some(hero));
+
+            let image = CachedImage::resize(TEST_IMAGE, 64, 64, 75).with_format(format);
+
+            optimizer.optimize_sync(&image).unwrap();
+            let path = optimizer.get_file_path(&image);
+            assert!(path.ends_with(format.extension()), "{path}");
+            let bytes = std::fs::read(path).unwrap();
+            assert_eq!(
+                image::guess_format(&bytes).unwrap().to_mime_type(),
+                format.mime_type()
+            );
+        }
+    }
+
+    #[test]
+    fn probes_sources() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let info = optimizer.probe(TEST_IMAGE).unwrap();
+        let img = open_source(TEST_IMAGE).unwrap();
+        assert_eq!((info.width, info.height), (img.width(), img.height()));
+        assert_eq!(info.format, image::ImageFormat::Png);
+        assert_eq!(info.bytes, std::fs::metadata(TEST_IMAGE).unwrap().len());
+        // Probes are kept, so renders of `<Image/>` can use them.
+        let key = (None, TEST_IMAGE.to_string());
+        assert_eq!(*optimizer.source_dimensions.get(&key).unwrap(), info);
+
+        assert!(matches!(
+            optimizer.probe("/missing.png"),
+            Err(CreateImageError::SourceNotFound(src)) if src == "/missing.png"
+        ));
+    }
+
+    #[test]
+    fn source_dimensions() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        runtime.block_on(async {
+            optimizer.record_source_dimensions(TEST_IMAGE, None).await;
+            optimizer
+                .record_source_dimensions("/missing.png", None)
+                .await;
+        });
+        let key = (None, TEST_IMAGE.to_string());
+        let info = *optimizer.source_dimensions.get(&key).unwrap();
+        let source = (info.width, info.height);
+        assert_eq!(optimizer.source_dimensions.len(), 1);
+
+        // Matches the size of the resized image.
+        let img = open_source(TEST_IMAGE).unwrap();
+        for (width, height) in [(100, 100), (300, 40), (2000, 1000)] {
+            let resized = resize_image(
+                &img,
+                width,
+                height,
+                ResizeMode::Contain,
+                ResizeFilter::Triangle,
+            );
+            assert_eq!(
+                fitted_dimensions(source, width, height, ResizeMode::Contain),
+                (resized.width(), resized.height())
+            );
+        }
+        assert_eq!(
+            fitted_dimensions(source, 100, 40, ResizeMode::Cover),
+            (100, 40)
+        );
+    }
+
+    #[test]
+    fn missing_source() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let image = CachedImage::resize("/does-not-exist.png", 100, 100, 75);
+
+        runtime.block_on(async {
+            for _ in 0..2 {
+                assert!(matches!(
+                    optimizer.create_image(&image).await,
+                    Err(CreateImageError::SourceNotFound(src)) if src == "/does-not-exist.png"
+                ));
+            }
+        });
+        assert_eq!(optimizer.missing_sources.len(), 1);
+    }
+
+    #[test]
+    fn recreate_changed_source() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let src = "/cache/versioned/source.png";
+        create_nested_if_needed(&src[1..]).unwrap();
+        std::fs::copy(TEST_IMAGE, &src[1..]).unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("versioned");
+        let image = CachedImage::resize(src, 40, 40, 75);
+
+        runtime.block_on(async {
+            assert!(optimizer.create_image(&image).await.unwrap());
+            assert!(!optimizer.create_image(&image).await.unwrap());
+
+            // Touched without changes, e.g. by a fresh checkout.
+            let touched = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+            std::fs::File::options()
+                .write(true)
+                .open(&src[1..])
+                .unwrap()
+                .set_modified(touched)
+                .unwrap();
+            assert!(!optimizer.create_image(&image).await.unwrap());
+
+            open_source(TEST_IMAGE)
+                .unwrap()
+                .fliph()
+                .save(&src[1..])
+                .unwrap();
+            assert!(optimizer.create_image(&image).await.unwrap());
+        });
+
+        // A restarted server reads the version from the sidecar.
+        let restarted =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("versioned");
+        assert!(!restarted.optimize_sync(&image).unwrap());
+    }
+
+    #[test]
+    fn stale_while_revalidate() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let src = "/cache/revalidate/source.png";
+        create_nested_if_needed(&src[1..]).unwrap();
+        std::fs::copy(TEST_IMAGE, &src[1..]).unwrap();
+        let _ = std::fs::remove_dir_all("cache/image/revalidate");
+        let optimizer = ImageOptimizer::builder()
+            .root(".")
+            .stale_while_revalidate(true)
+            .cache_namespace("revalidate")
+            .build();
+        let image = CachedImage::resize(src, 40, 40, 75);
+        let path = optimizer.get_file_path(&image);
+
+        runtime.block_on(async {
+            assert!(optimizer.create_image(&image).await.unwrap());
+            let stale = std::fs::read(&path).unwrap();
+
+            open_source(TEST_IMAGE)
+                .unwrap()
+                .fliph()
+                .save(&src[1..])
+                .unwrap();
+            // The stale image is served while the new one is created.
+            assert!(!optimizer.create_image(&image).await.unwrap());
+            assert_eq!(std::fs::read(&path).unwrap(), stale);
+
+            while !optimizer.revalidating.is_empty() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            assert_ne!(std::fs::read(&path).unwrap(), stale);
+            assert!(!optimizer.create_image(&image).await.unwrap());
+        });
+    }
+
+    #[test]
+    fn restore_from_backend() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        for dir in ["cache/image/backend", "cache/backend"] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        let optimizer = ImageOptimizer::builder()
+            .root(".")
+            .cache_backend(crate::LocalCache::new("cache/backend"))
+            .cache_namespace("backend")
+            .build();
+        let image = CachedImage::resize(TEST_IMAGE, 40, 40, 75);
+        let path = optimizer.get_file_path(&image);
+        let uploaded = std::path::Path::new("cache/backend").join(&path);
+
+        runtime.block_on(async {
+            assert!(optimizer.create_image(&image).await.unwrap());
+            while !sidecar_path(&uploaded).exists() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+
+            // A fresh instance downloads the image instead of encoding it.
+            let created = std::fs::read(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert!(!optimizer.create_image(&image).await.unwrap());
+            assert_eq!(std::fs::read(&path).unwrap(), created);
+        });
+    }
+
+    #[test]
+    fn load_from_image_source() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        // Nothing is served from the root, sources are read from the example's public folder.
+        let _ = std::fs::remove_dir_all("cache/sourced");
+        let optimizer = ImageOptimizer::builder()
+            .root("cache/sourced")
+            .image_source(crate::LocalSource::new("./example/start-axum/public"))
+            .build();
+        let image = CachedImage::resize("/cute_ferris.png", 40, 40, 75);
+        let missing = CachedImage::resize("/missing.png", 40, 40, 75);
+
+        runtime.block_on(async {
+            assert!(optimizer.create_image(&image).await.unwrap());
+            assert!(!optimizer.create_image(&image).await.unwrap());
+            assert!(matches!(
+                optimizer.create_image(&missing).await,
+                Err(CreateImageError::SourceNotFound(_))
+            ));
+        });
+        assert!(std::path::Path::new("cache/sourced/cache/image/sources/cute_ferris.png").exists());
+    }
+
+    #[test]
+    fn verify_deterministic_image() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        optimizer.update_config(|config| config.deterministic = true);
+        let image = CachedImage::resize(TEST_IMAGE, 64, 64, 75);
+
+        optimizer.optimize_sync(&image).unwrap();
+        assert!(optimizer.verify_sync(&image).unwrap());
+    }
+
+    #[test]
+    fn shutdown() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+
+        runtime.block_on(async {
+            let guard = optimizer.in_flight.start().unwrap();
+            let timeout = std::time::Duration::from_millis(10);
+            assert!(!optimizer.shutdown(timeout).await);
+
+            drop(guard);
+            assert!(optimizer.shutdown(timeout).await);
+        });
+
+        let image = CachedImage::resize(TEST_IMAGE, 32, 32, 75);
+        assert!(matches!(
+            optimizer.optimize_sync(&image),
+            Err(CreateImageError::ShuttingDown)
+        ));
+    }
+
+    #[test]
+    fn encoder_threads() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .encoder_threads(1)
+            .build();
+        let image = CachedImage::resize(TEST_IMAGE, 48, 48, 75);
+
+        let created = runtime.block_on(optimizer.create_image(&image));
+        assert!(created.is_ok());
+    }
+
+    #[test]
+    fn legacy_source_formats() {
+        let dir = std::path::Path::new("cache/legacy-sources");
+        std::fs::create_dir_all(dir).unwrap();
+        // ICO embeds RGBA PNGs, the other formats keep the alpha channel as well.
+        let source = image::DynamicImage::ImageRgba8(
+            image::open(TEST_IMAGE)
+                .unwrap()
+                .thumbnail_exact(64, 64)
+                .to_rgba8(),
+        );
+
+        let sources = [
+            ("ferris.bmp", image::ImageFormat::Bmp),
+            ("ferris.tiff", image::ImageFormat::Tiff),
+            ("ferris.ico", image::ImageFormat::Ico),
+            // Legacy libraries often have files with the wrong extension.
+            ("ico_as.png", image::ImageFormat::Ico),
+        ];
+        for (name, format) in sources {
+            let path = dir.join(name);
+            source.save_with_format(&path, format).unwrap();
+
+            let bytes = encode_optimized_image(
+                CachedImageOption::Resize(Resize {
+                    width: 32,
+                    height: 32,
+                    quality: 75,
+                    format: OutputFormat::WebP,
+                    fit: ResizeMode::default(),
+                    filter: None,
+                    sharpen: None,
+                    metadata: None,
+                    no_upscale: None,
+                    lossless: false,
+                    transforms: Vec::new(),
+                    watermark: None,
+                }),
+                path,
+                &EncodeSettings::default(),
+            )
+            .unwrap();
+            let output =
+                image::load_from_memory_with_format(&bytes, image::ImageFormat::WebP).unwrap();
+            assert_eq!((output.width(), output.height()), (32, 32), "{name}");
+        }
+    }
+
     #[test]
     fn create_opt_image() {
         let spec = CachedImage {
@@ -472,12 +6679,28 @@ mod optimizer_tests {
                 quality: 75,
                 width: 100,
                 height: 100,
+                format: OutputFormat::default(),
+                fit: ResizeMode::default(),
+                filter: None,
+                sharpen: None,
+                metadata: None,
+                no_upscale: None,
+                lossless: false,
+                transforms: Vec::new(),
+                watermark: None,
             }),
+            tenant: None,
+            cache_control: None,
         };
 
-        let file_path = spec.get_file_path();
+        let file_path = spec.get_file_path("cache/image/test", "");
 
-        let result = create_optimized_image(spec.option, TEST_IMAGE.to_string(), file_path.clone());
+        let result = create_optimized_image(
+            spec.option,
+            TEST_IMAGE.to_string(),
+            file_path.clone(),
+            &EncodeSettings::default(),
+        );
 
         assert!(result.is_ok());
 
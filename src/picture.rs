@@ -0,0 +1,172 @@
+use crate::optimizer::*;
+
+use leptos::*;
+
+/// An alternative source of a [`Picture`], rendered when its media query matches.
+///
+/// Used for art direction, e.g. a tighter crop of a banner on small screens.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let mobile = PictureSource::new("(max-width: 600px)", "/banner-mobile.jpg", 600, 600);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PictureSource {
+    /// Media query selecting this source, e.g. `(max-width: 600px)`.
+    pub media: String,
+    /// Path to a static image, relative to the site root.
+    pub src: String,
+    /// Resize width, the aspect ratio is maintained.
+    pub width: u32,
+    /// Resize height, the aspect ratio is maintained.
+    pub height: u32,
+}
+
+impl PictureSource {
+    /// Creates a source for the given media query.
+    pub fn new(media: impl Into<String>, src: impl Into<String>, width: u32, height: u32) -> Self {
+        Self {
+            media: media.into(),
+            src: src.into(),
+            width,
+            height,
+        }
+    }
+}
+
+/// Renders a `<picture>` with an AVIF and a WebP `<source>`, and an `<img>` fallback in the format of the source,
+/// JPEG or PNG, for browsers without WebP support. Every variant is generated by the optimizer.
+///
+/// AVIF sources are only rendered when the server is built with the `avif` feature.
+/// Art direction [`PictureSource`]s are rendered before the default source, so the first matching one wins.
+///
+/// ```
+/// use leptos::*;
+/// use leptos_image::*;
+///
+/// #[component]
+/// fn Banner() -> impl IntoView {
+///     view! {
+///         <Picture
+///             src="/banner.jpg"
+///             width=1200
+///             height=400
+///             alt="Banner"
+///             sources=vec![PictureSource::new("(max-width: 600px)", "/banner-mobile.jpg", 600, 600)]
+///         />
+///     }
+/// }
+/// ```
+#[component]
+pub fn Picture(
+    /// Path to a static image, relative to the site root.
+    #[prop(into)]
+    src: String,
+    /// Resize width, the aspect ratio is maintained.
+    width: u32,
+    /// Resize height, the aspect ratio is maintained.
+    height: u32,
+    /// Art direction sources, each selected by a media query.
+    #[prop(optional)]
+    sources: Vec<PictureSource>,
+    /// How the image fits the `width` x `height` box. Defaults to [`crate::ResizeMode::Contain`].
+    #[prop(optional)]
+    fit: crate::ResizeMode,
+    /// Image quality. 0-100.
+    /// Defaults to the quality configured on the optimizer, 75 unless changed.
+    #[prop(optional)]
+    quality: Option<u8>,
+    /// Lazy load image.
+    #[prop(default = true)]
+    lazy: bool,
+    /// Image alt text.
+    #[prop(into, optional)]
+    alt: MaybeSignal<String>,
+    /// Style class for the `<img>`.
+    #[prop(into, optional)]
+    class: Option<AttributeValue>,
+    /// Tenant the image belongs to. Defaults to the tenant provided with [`crate::provide_image_tenant`].
+    #[prop(into, optional)]
+    tenant: Option<String>,
+) -> impl IntoView {
+    let tenant = tenant.or_else(crate::provider::use_image_tenant);
+    let resource = crate::use_image_cache_resource();
+
+    let src = store_value(src);
+    let sources = store_value(sources);
+    let tenant = store_value(tenant);
+    let alt = store_value(alt);
+    let class = store_value(class.map(|c| c.into_attribute_boxed()));
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                resource
+                    .get()
+                    .map(|config| {
+                        let quality = quality.unwrap_or(config.default_quality);
+                        let url = |src: &str, width: u32, height: u32, format: OutputFormat| {
+                            let image = CachedImage::resize(src, width, height, quality)
+                                .with_fit(fit)
+                                .with_format(format);
+                            tenant
+                                .with_value(|tenant| config.url(&image.scoped(tenant.as_deref())))
+                        };
+                        let formats: Vec<_> = [OutputFormat::Avif, OutputFormat::WebP]
+                            .into_iter()
+                            .filter(|format| *format != OutputFormat::Avif || config.avif)
+                            .collect();
+                        let art = sources
+                            .get_value()
+                            .into_iter()
+                            .flat_map(|source| {
+                                let fallback = OutputFormat::fallback_for(&source.src);
+                                formats
+                                    .iter()
+                                    .copied()
+                                    .chain([fallback])
+                                    .map(|format| {
+                                        view! {
+                                            <source
+                                                media=source.media.clone()
+                                                type=format.mime_type()
+                                                srcset=url(&source.src, source.width, source.height, format)
+                                            />
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()
+                            })
+                            .collect_view();
+                        let src = src.get_value();
+                        let typed = formats
+                            .iter()
+                            .map(|&format| {
+                                view! {
+                                    <source type=format.mime_type() srcset=url(&src, width, height, format)/>
+                                }
+                            })
+                            .collect_view();
+                        let fallback = url(&src, width, height, OutputFormat::fallback_for(&src));
+                        let loading = if lazy { "lazy" } else { "eager" };
+                        view! {
+                            <picture>
+                                {art}
+                                {typed}
+                                <img
+                                    src=fallback
+                                    alt=alt.get_value()
+                                    class=class.get_value()
+                                    width=width
+                                    height=height
+                                    decoding="async"
+                                    loading=loading
+                                />
+                            </picture>
+                        }
+                    })
+            }}
+
+        </Suspense>
+    }
+}
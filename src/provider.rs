@@ -1,10 +1,20 @@
-use crate::optimizer::CachedImage;
+use crate::optimizer::{
+    CachedImage, OriginalImage, OutputFormat, Placeholder, ResizeMode, SourceKey, Transform,
+    UrlScheme,
+};
 use leptos::*;
 
 /// Provides Image Cache Context so that Images can use their blur placeholders if they exist.
 ///
 /// This should go in the base of your Leptos <App/>.
 ///
+/// Only the placeholders of the images on the server rendered page are sent to the client. Images rendered
+/// later, inside a `<Suspense/>` or after navigating in the browser, render without a placeholder,
+/// unless it was already generated when the server renders them.
+///
+/// If the image config can't be loaded, e.g. because the `ImageOptimizer` is missing from the server
+/// context, the error is logged and images render nothing, see [`use_image_context_error`].
+///
 /// Example
 ///
 /// ```
@@ -21,47 +31,456 @@ use leptos::*;
 ///
 /// ```
 pub fn provide_image_context() {
-    let resource: ImageResource = create_blocking_resource(
-        || (),
-        |_| async {
-            get_image_config()
-                .await
-                .expect("Failed to retrieve image cache")
-        },
-    );
+    let page = PageImages {
+        placeholders: store_value(Vec::new()),
+        sources: store_value(Vec::new()),
+    };
+    // Read once the page has rendered, so it lists the images rendered outside of a `<Suspense/>`.
+    let resource = ImageResource::new(move || {
+        (
+            page.placeholders.try_get_value().unwrap_or_default(),
+            page.sources.try_get_value().unwrap_or_default(),
+        )
+    });
 
     leptos::provide_context(resource);
+    leptos::provide_context(page);
+    leptos::provide_context(PreloadBudget::new(DEFAULT_PRELOAD_BUDGET));
+}
+
+// Blur placeholders and source dimensions of the images on the page, collected while rendering
+// so only these are sent to the client.
+#[derive(Clone, Copy)]
+struct PageImages {
+    placeholders: StoredValue<Vec<CachedImage>>,
+    sources: StoredValue<Vec<SourceKey>>,
+}
+
+// Adds the placeholder of an image to the ones of the page. Images rendered after the image config
+// has loaded, e.g. in a `<Suspense/>` or after navigating in the browser, render without a placeholder.
+#[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+pub(crate) fn request_placeholder(blur_image: CachedImage) {
+    if let Some(page) = use_context::<PageImages>() {
+        page.placeholders
+            .update_value(|images| images.push(blur_image));
+    }
+}
+
+// Adds the dimensions of a source to the ones of the page, like `request_placeholder`.
+pub(crate) fn request_source_dimensions(src: &str, tenant: Option<&str>) {
+    if let Some(page) = use_context::<PageImages>() {
+        let key = (tenant.map(str::to_string), src.to_string());
+        page.sources.update_value(|sources| {
+            if !sources.contains(&key) {
+                sources.push(key);
+            }
+        });
+    }
+}
+
+/// Scopes every `<Image/>` rendered below this point to a tenant registered with `ImageOptimizerBuilder::tenant`.
+///
+/// Call it wherever the tenant is known, e.g. at the top of a tenant's route.
+pub fn provide_image_tenant(tenant: impl Into<String>) {
+    leptos::provide_context(ImageTenant(tenant.into()));
+}
+
+#[derive(Clone, Debug)]
+struct ImageTenant(String);
+
+pub(crate) fn use_image_tenant() -> Option<String> {
+    use_context::<ImageTenant>().map(|t| t.0)
+}
+
+/// Number of `priority` images that get a preload `<link>` per page by default.
+pub const DEFAULT_PRELOAD_BUDGET: usize = 4;
+
+/// Overrides how many `priority` images on a page get a preload `<link>`.
+///
+/// Preloading too many images competes with more important resources, so images beyond the budget
+/// are rendered with `fetchpriority="high"` instead. Call this after [`provide_image_context`].
+///
+/// ```
+/// use leptos::*;
+///
+/// #[component]
+/// pub fn App() -> impl IntoView {
+///     leptos_image::provide_image_context();
+///     leptos_image::provide_preload_budget(2);
+///
+///     view!{
+///       <div/>
+///     }
+/// }
+/// ```
+pub fn provide_preload_budget(limit: usize) {
+    leptos::provide_context(PreloadBudget::new(limit));
+}
+
+// Tracks the preloads emitted while rendering a single page.
+#[derive(Clone, Copy)]
+struct PreloadBudget {
+    limit: usize,
+    used: StoredValue<usize>,
+}
+
+impl PreloadBudget {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: store_value(0),
+        }
+    }
+}
+
+// Returns true if the image may add a preload link to the head.
+pub(crate) fn claim_preload(src: &str) -> bool {
+    let Some(budget) = use_context::<PreloadBudget>() else {
+        return true;
+    };
+    let used = budget.used.get_value();
+    budget.used.set_value(used + 1);
+    if used < budget.limit {
+        true
+    } else {
+        logging::debug_warn!(
+            "Preload budget of {} exceeded, using fetchpriority for {src}",
+            budget.limit
+        );
+        false
+    }
+}
+
+/// Why images can't load the image config, see [`use_image_context_error`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ImageContextError {
+    /// [`provide_image_context`] wasn't called above the image.
+    /// Images still render, but each loads the config on its own and without a blur placeholder.
+    MissingContext,
+    /// The `GetImageCache` server function failed, e.g. because the `ImageOptimizer` is missing
+    /// from the server context. Images render nothing.
+    ServerFn(String),
+}
+
+impl std::fmt::Display for ImageContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingContext => {
+                write!(
+                    f,
+                    "Missing image context, call `provide_image_context` in your App"
+                )
+            }
+            Self::ServerFn(e) => write!(f, "Failed to retrieve image cache: {e}"),
+        }
+    }
 }
 
-type ImageResource = Resource<(), ImageConfig>;
+impl std::error::Error for ImageContextError {}
+
+/// The error preventing images from rendering as configured, if any.
+///
+/// ```
+/// use leptos::*;
+///
+/// #[component]
+/// pub fn ImageStatus() -> impl IntoView {
+///     let error = leptos_image::use_image_context_error();
+///
+///     view!{
+///       <Show when=move || error.with(Option::is_some)>
+///         <p>"Images are unavailable"</p>
+///       </Show>
+///     }
+/// }
+/// ```
+pub fn use_image_context_error() -> Signal<Option<ImageContextError>> {
+    match use_context::<ImageResource>() {
+        Some(resource) => Signal::derive(move || resource.error()),
+        None => Signal::derive(|| Some(ImageContextError::MissingContext)),
+    }
+}
+
+// The image config shared by the images of the page.
+#[derive(Clone, Copy)]
+pub(crate) struct ImageResource(Resource<(), Result<ImageConfig, ImageContextError>>);
+
+impl ImageResource {
+    // Loads the config with the cached placeholders of the blur images and the dimensions of the sources.
+    fn new(images: impl Fn() -> (Vec<CachedImage>, Vec<SourceKey>) + 'static) -> Self {
+        Self(create_blocking_resource(
+            || (),
+            move |_| {
+                let (images, sources) = images();
+                async move {
+                    get_image_config(images, sources).await.map_err(|e| {
+                        let error = ImageContextError::ServerFn(e.to_string());
+                        logging::error!("{error}");
+                        error
+                    })
+                }
+            },
+        ))
+    }
+
+    pub(crate) fn get(&self) -> Option<ImageConfig> {
+        self.0.get().and_then(Result::ok)
+    }
+
+    pub(crate) fn with<U>(&self, f: impl FnOnce(&ImageConfig) -> U) -> Option<U> {
+        self.0.with(|config| {
+            config
+                .as_ref()
+                .and_then(|config| config.as_ref().ok())
+                .map(f)
+        })
+    }
+
+    fn error(&self) -> Option<ImageContextError> {
+        self.0
+            .with(|config| config.as_ref().and_then(|config| config.clone().err()))
+    }
+}
+
+// Config resolved once for many images, so each doesn't wait on the resource itself, see `ImageGallery`.
+#[derive(Clone, Copy)]
+pub(crate) struct SharedImageConfig {
+    pub(crate) config: StoredValue<ImageConfig>,
+    // Cached files by image, indexed in one pass instead of a lookup in `ImageConfig::cache` per image.
+    #[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+    pub(crate) cache: StoredValue<std::collections::HashMap<CachedImage, String>>,
+}
+
+impl SharedImageConfig {
+    pub(crate) fn new(config: ImageConfig) -> Self {
+        let cache = config.cache.iter().cloned().collect();
+        Self {
+            config: store_value(config),
+            cache: store_value(cache),
+        }
+    }
+}
 
 #[doc(hidden)]
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ImageConfig {
     pub(crate) api_handler_path: String,
+    pub(crate) url_scheme: UrlScheme,
+    // Where cached files are served from, for `UrlScheme::Static`.
+    pub(crate) static_dir: String,
+    // Names of static files depend on it, see `OptimizerConfig::encode_digest`.
+    pub(crate) encode_digest: String,
+    pub(crate) base_url: Option<String>,
+    // Path prefix the app is served under, see `ImageOptimizerBuilder::base_path`.
+    pub(crate) base_path: String,
+    pub(crate) default_quality: u8,
+    pub(crate) dpr_quality: crate::DprQualityCurve,
+    // Default of `<Image no_upscale/>`, see `OptimizerConfig::no_upscale`.
+    pub(crate) no_upscale: bool,
+    pub(crate) formats: Vec<OutputFormat>,
+    // Whether the server can encode AVIF images.
+    pub(crate) avif: bool,
+    pub(crate) remote_domains: Vec<String>,
+    pub(crate) placeholder: Placeholder,
     pub(crate) cache: Vec<(CachedImage, String)>,
+    // Known dimensions of the sources on the page, keyed by tenant and source.
+    pub(crate) dimensions: Vec<(SourceKey, (u32, u32))>,
+    // Whether the page request asked to save data, see `<Image reduced_data/>`.
+    pub(crate) reduced_data: bool,
+}
+
+impl ImageConfig {
+    // URL of the optimized image, in the scheme configured on the optimizer.
+    // While rendering on the server, the image is allowed in strict mode and its URL signed,
+    // see `OptimizerConfig::strict` and `ImageOptimizerBuilder::signing_key`.
+    pub(crate) fn url(&self, image: &CachedImage) -> String {
+        // Static files are neither signed nor checked by the handler, they only have to exist.
+        if self.url_scheme == UrlScheme::Static {
+            return image.get_static_url(&self.static_dir, &self.encode_digest);
+        }
+        let url = image.get_url(&self.api_handler_path, self.url_scheme);
+        #[cfg(feature = "ssr")]
+        if let Some(optimizer) = use_context::<crate::ImageOptimizer>() {
+            return optimizer.rendered_url(url, image);
+        }
+        url
+    }
+
+    // URL of a static source rendered as-is, like an SVG, under the base path of the app.
+    pub(crate) fn static_url(&self, src: &str) -> String {
+        if src.starts_with('/') && !src.starts_with("//") {
+            format!("{}{src}", self.base_path)
+        } else {
+            src.to_string()
+        }
+    }
+
+    // Whether a remote `http(s)` source is downloaded and optimized by the server.
+    pub(crate) fn optimizes_remote(&self, src: &str) -> bool {
+        crate::optimizer::remote_domain_allowed(&self.remote_domains, src)
+    }
+
+    // Dimensions of the resized image, once the dimensions of its source are known.
+    // Otherwise the requested ones, which only differ for `ResizeMode::Contain` and `no_upscale`.
+    pub(crate) fn fitted_dimensions(
+        &self,
+        src: &str,
+        tenant: Option<&str>,
+        (width, height): (u32, u32),
+        fit: ResizeMode,
+        transforms: &[Transform],
+        no_upscale: bool,
+    ) -> (u32, u32) {
+        self.source_dimensions(src, tenant)
+            .map(|(source_width, source_height)| {
+                // Quarter turns are applied before resizing.
+                let turns = transforms.iter().filter(|t| t.swaps_axes()).count();
+                if turns % 2 == 1 {
+                    (source_height, source_width)
+                } else {
+                    (source_width, source_height)
+                }
+            })
+            .map_or((width, height), |source| {
+                let (width, height) = if no_upscale {
+                    crate::optimizer::limit_upscale(source, width, height, fit)
+                } else {
+                    (width, height)
+                };
+                crate::optimizer::fitted_dimensions(source, width, height, fit)
+            })
+    }
+
+    pub(crate) fn source_dimensions(&self, src: &str, tenant: Option<&str>) -> Option<(u32, u32)> {
+        self.dimensions
+            .iter()
+            .find(|((t, s), _)| t.as_deref() == tenant && s == src)
+            .map(|(_, dimensions)| *dimensions)
+    }
+
+    // Absolute URL of the optimized image, relative if no base url is configured.
+    pub(crate) fn absolute_url(&self, image: &CachedImage) -> String {
+        let url = self.url(image);
+        match &self.base_url {
+            Some(base_url) => format!("{}{url}", base_url.trim_end_matches('/')),
+            None => {
+                logging::debug_warn!("No base url configured on the optimizer, {url} is relative.");
+                url
+            }
+        }
+    }
+}
+
+/// URL serving the untouched source image, e.g. for a "download full resolution" link.
+///
+/// Requires the route added by `ImageCacheRoute::image_original_route`.
+/// With `download`, the browser saves the file instead of displaying it.
+/// With a signing key or in strict mode, only links rendered on the server are served.
+pub fn use_original_image_url(src: impl Into<String>, download: bool) -> Signal<Option<String>> {
+    let resource = use_image_cache_resource();
+    let original = store_value(OriginalImage {
+        src: src.into(),
+        tenant: use_image_tenant(),
+        download,
+    });
+    #[cfg(feature = "ssr")]
+    let optimizer = use_context::<crate::ImageOptimizer>();
+    Signal::derive(move || {
+        resource.get().map(|config| {
+            original.with_value(|original| {
+                let url = original.get_url(&config.api_handler_path);
+                #[cfg(feature = "ssr")]
+                if let Some(optimizer) = &optimizer {
+                    return optimizer.rendered_original_url(url, original);
+                }
+                url
+            })
+        })
+    })
 }
 
+// The config of the page, or one loaded for the caller alone if `provide_image_context` wasn't called.
 pub(crate) fn use_image_cache_resource() -> ImageResource {
-    use_context::<ImageResource>().expect("Missing Image Resource")
+    use_context::<ImageResource>().unwrap_or_else(|| {
+        logging::debug_warn!("{}", ImageContextError::MissingContext);
+        ImageResource::new(Default::default)
+    })
+}
+
+// False if `provide_image_context` wasn't called, so the page's placeholders aren't sent to the client.
+#[cfg_attr(not(feature = "blur-placeholder"), allow(dead_code))]
+pub(crate) fn has_image_context() -> bool {
+    use_context::<ImageResource>().is_some()
 }
 
-#[server(GetImageCache)]
-pub(crate) async fn get_image_config() -> Result<ImageConfig, ServerFnError> {
-    let optimizer = use_optimizer()?;
+pub(crate) use server_fns::get_image_config;
+pub use server_fns::GetImageCache;
 
-    let cache = optimizer
-        .cache
-        .iter()
-        .map(|entry| (entry.key().clone(), entry.value().clone()))
-        .collect();
+// The server function generates a public struct of its arguments, without documentation.
+#[allow(missing_docs)]
+mod server_fns {
+    use super::*;
 
-    let api_handler_path = optimizer.api_handler_path.clone();
+    // Loads the config of the optimizer, with the cached placeholders of the given blur images
+    // and the known dimensions of the given sources only.
+    #[server(GetImageCache)]
+    pub(crate) async fn get_image_config(
+        images: Vec<CachedImage>,
+        sources: Vec<SourceKey>,
+    ) -> Result<ImageConfig, ServerFnError> {
+        let optimizer = use_optimizer()?;
+        let config = optimizer.config();
 
-    Ok(ImageConfig {
-        api_handler_path,
-        cache,
-    })
+        let mut cache: Vec<(CachedImage, String)> = Vec::new();
+        for image in images {
+            let image = image.with_placeholder(config.placeholder);
+            if cache.iter().any(|(cached, _)| *cached == image) {
+                continue;
+            }
+            if let Some(placeholder) = optimizer.cache.get(&image) {
+                cache.push((image, placeholder.clone()));
+            }
+        }
+
+        // Probed before the page renders, so its images have their dimensions on the first render.
+        for (tenant, src) in &sources {
+            optimizer
+                .record_source_dimensions(src, tenant.as_deref())
+                .await;
+        }
+
+        let api_handler_path = optimizer.handler_path();
+
+        Ok(ImageConfig {
+            api_handler_path,
+            url_scheme: optimizer.url_scheme,
+            static_dir: optimizer.static_dir(),
+            encode_digest: config.encode_digest(),
+            base_url: optimizer.base_url.clone(),
+            base_path: optimizer.base_path.clone(),
+            default_quality: config.default_quality,
+            dpr_quality: config.dpr_quality,
+            no_upscale: config.no_upscale,
+            formats: config.formats,
+            avif: cfg!(feature = "avif"),
+            // Remote images can only be downloaded with the `fetch` feature.
+            remote_domains: if cfg!(feature = "fetch") {
+                config.remote_domains
+            } else {
+                Vec::new()
+            },
+            placeholder: config.placeholder,
+            cache,
+            dimensions: sources
+                .into_iter()
+                .filter_map(|key| {
+                    let info = optimizer.source_dimensions.get(&key)?;
+                    Some((key, (info.width, info.height)))
+                })
+                .collect(),
+            reduced_data: crate::image::prefers_reduced_data(),
+        })
+    }
 }
 
 #[cfg(feature = "ssr")]
@@ -0,0 +1,107 @@
+use axum::http::{Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Tower layer logging every image request with its spec, cache status and latency.
+///
+/// Events are emitted with `tracing` under the `leptos_image::request` target, with the fields
+/// `method`, `path`, `status`, `spec`, `cache` (`memory`, `disk` or `generated`), `total_ms` and `generation_ms`.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// # fn router(optimizer: ImageOptimizer) -> axum::Router<()> {
+/// let images = axum::Router::new()
+///     .image_cache_route(&optimizer)
+///     .layer(ImageLogLayer)
+///     .with_state(optimizer);
+/// # images
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImageLogLayer;
+
+impl<S> tower::Layer<S> for ImageLogLayer {
+    type Service = ImageLog<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ImageLog { inner }
+    }
+}
+
+/// Service created by [`ImageLogLayer`].
+#[derive(Clone, Debug)]
+pub struct ImageLog<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> tower::Service<Request<ReqBody>> for ImageLog<S>
+where
+    S: tower::Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().clone();
+        let path = req.uri().path().to_string();
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let response = future.await?;
+            let total_ms = started.elapsed().as_secs_f64() * 1000.0;
+            let status = response.status().as_u16();
+
+            match response.extensions().get::<ImageRequestInfo>() {
+                Some(info) => tracing::info!(
+                    target: "leptos_image::request",
+                    %method,
+                    path,
+                    status,
+                    spec = %info.spec,
+                    cache = info.cache.as_str(),
+                    total_ms,
+                    generation_ms = info.generation.map(|d| d.as_secs_f64() * 1000.0),
+                ),
+                None => {
+                    tracing::info!(target: "leptos_image::request", %method, path, status, total_ms)
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+// Added to the extensions of image responses by the handler, read by `ImageLog`.
+#[derive(Clone, Debug)]
+pub(crate) struct ImageRequestInfo {
+    pub(crate) spec: String,
+    pub(crate) cache: CacheStatus,
+    pub(crate) generation: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CacheStatus {
+    Memory,
+    Disk,
+    Generated,
+}
+
+impl CacheStatus {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Memory => "memory",
+            CacheStatus::Disk => "disk",
+            CacheStatus::Generated => "generated",
+        }
+    }
+}
@@ -1,173 +1,290 @@
-use crate::optimizer::{CachedImage, CachedImageOption, CreateImageError, ImageOptimizer};
-use axum::extract::FromRef;
-use axum::response::Response as AxumResponse;
-use axum::{
-    body::Body,
-    http::{Request, Response, Uri},
-    response::IntoResponse,
-};
-use std::convert::Infallible;
-use tower::ServiceExt;
-use tower_http::services::fs::ServeFileSystemResponseBody;
-use tower_http::services::ServeDir;
-
-/// This trait prevents using incorrect route for image cache handler.
-pub trait ImageCacheRoute<S>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    /// Adds a route to the app for serving cached images.
-    /// Requires an axum State that contains the optimizer [`crate::ImageOptimizer`].
-    ///
-    /// ```
-    /// use leptos_image::*;
-    /// use leptos::*;
-    /// use axum::*;
-    /// use axum::routing::post;
-    /// use leptos_axum::{generate_route_list, handle_server_fns, LeptosRoutes};
-    ///
-    /// #[cfg(feature = "ssr")]
-    /// async fn your_main_function() {
-    ///
-    ///   let options = get_configuration(None).await.unwrap().leptos_options;
-    ///   let optimizer = ImageOptimizer::new("/__cache/image", options.site_root.clone(), 1);
-    ///   let state = AppState {leptos_options: options, optimizer: optimizer.clone() };
-    ///   let routes = generate_route_list(App);
-    ///
-    ///   let router: Router<()> = Router::new()
-    ///    .route("/api/*fn_name", post(leptos_axum::handle_server_fns))
-    ///    // Add a handler for serving the cached images.
-    ///    .image_cache_route(&state)
-    ///    .leptos_routes_with_context(&state, routes, optimizer.provide_context(), App)
-    ///    .with_state(state);
-    ///
-    ///   // Rest of your function ...
-    /// }
-    ///
-    /// // Composite App State with the optimizer and leptos options.
-    /// #[derive(Clone, axum::extract::FromRef)]
-    /// struct AppState {
-    ///   leptos_options: leptos::LeptosOptions,
-    ///   optimizer: leptos_image::ImageOptimizer,
-    /// }
-    ///
-    /// #[component]
-    /// fn App() -> impl IntoView {
-    ///   provide_image_context();
-    ///   ()
-    /// }
-    ///
-    /// ```
-    ///
-    ///
-    fn image_cache_route(self, state: &S) -> Self;
+use crate::optimizer::{CachedImage, CreateImageError, ImageOptimizer, OriginalImage};
+
+/// Response to a request for an optimized image, see [`ImageOptimizer::handle_request`].
+#[derive(Debug)]
+pub struct ImageResponse {
+    /// The image served, in the format picked for the request.
+    pub image: CachedImage,
+    /// The contents to send.
+    pub body: ImageBody,
+    /// Value of the `Cache-Control` header, if one is configured for the image.
+    pub cache_control: Option<String>,
+    /// Whether the format was picked from the `Accept` header, so the response must send `Vary: Accept`.
+    pub vary_accept: bool,
+    /// How long it took to create the image, if it was created for this request.
+    pub generation: Option<std::time::Duration>,
 }
 
-impl<S> ImageCacheRoute<S> for axum::Router<S>
-where
-    S: Clone + Send + Sync + 'static,
-    ImageOptimizer: FromRef<S>,
-{
-    fn image_cache_route(self, state: &S) -> Self {
-        let optimizer = ImageOptimizer::from_ref(state);
+/// Contents of an [`ImageResponse`].
+#[derive(Debug)]
+pub enum ImageBody {
+    /// A placeholder held in memory, e.g. a blur SVG.
+    Memory(String),
+    /// A file of the cache, created for this request if it didn't exist yet.
+    File {
+        /// Root the file is served from.
+        root: String,
+        /// Path of the file, relative to `root`.
+        path: String,
+    },
+}
 
-        let path = optimizer.api_handler_path.clone();
-        let handler = move |req: Request<Body>| image_cache_handler_inner(optimizer, req);
+impl ImageResponse {
+    /// Value of the `Content-Type` header.
+    pub fn content_type(&self) -> &'static str {
+        self.image.mime_type()
+    }
+}
 
-        self.route(&path, axum::routing::get(handler))
+impl ImageBody {
+    /// Full path of the file to stream, `None` for contents held in memory.
+    pub fn file_path(&self) -> Option<std::path::PathBuf> {
+        match self {
+            ImageBody::Memory(_) => None,
+            ImageBody::File { root, path } => Some(std::path::Path::new(root).join(path)),
+        }
     }
 }
 
-async fn image_cache_handler_inner(optimizer: ImageOptimizer, req: Request<Body>) -> AxumResponse {
-    let root = optimizer.root_file_path.clone();
-    let cache_result = check_cache_image(&optimizer, req.uri().clone()).await;
+/// Why a request for an optimized image can't be served, see [`ImageOptimizer::handle_request`].
+#[derive(Debug, thiserror::Error)]
+pub enum ImageRequestError {
+    /// The URL isn't an optimized image URL of this optimizer.
+    #[error("Invalid image URL: {0}")]
+    InvalidUrl(String),
+    /// The image couldn't be created.
+    #[error(transparent)]
+    Create(#[from] CreateImageError),
+}
+
+impl ImageRequestError {
+    /// HTTP status code to respond with.
+    pub fn status(&self) -> u16 {
+        self.response().0
+    }
 
-    match cache_result {
-        Ok(Some(uri)) => {
-            let response = execute_file_handler(uri, &root).await.unwrap();
-            response.into_response()
+    /// Body to respond with. Short, and without details of the server.
+    pub fn message(&self) -> &'static str {
+        self.response().1
+    }
+
+    fn response(&self) -> (u16, &'static str) {
+        match self {
+            ImageRequestError::InvalidUrl(_) => (404, "Invalid Image."),
+            ImageRequestError::Create(e) => match e {
+                CreateImageError::SourceNotAllowed(_) | CreateImageError::ForbiddenPath(_) => {
+                    (403, "Forbidden Image.")
+                }
+                CreateImageError::SourceNotFound(_) => (404, "Image Not Found."),
+                CreateImageError::TooLarge(_, _) => (400, "Image Too Large."),
+                CreateImageError::QualityOutOfRange(_) => (400, "Invalid Image Quality."),
+                CreateImageError::TooManyTransforms(_) => (400, "Too Many Transforms."),
+                CreateImageError::InvalidSignature => (403, "Invalid Signature."),
+                CreateImageError::UnknownImage(_) | CreateImageError::UnknownTenant(_) => {
+                    (404, "Invalid Image.")
+                }
+                CreateImageError::ShuttingDown => (503, "Server is shutting down."),
+                _ => (500, "Error creating image"),
+            },
         }
+    }
 
-        Ok(None) => Response::builder()
-            .status(404)
-            .body("Invalid Image.".to_string())
-            .unwrap()
-            .into_response(),
-
-        Err(e) => {
-            tracing::error!("Failed to create image: {:?}", e);
-            Response::builder()
-                .status(500)
-                .body("Error creating image".to_string())
-                .unwrap()
-                .into_response()
+    // Logs the error at the level it deserves, client errors are expected.
+    fn log(&self, url: &str) {
+        let ImageRequestError::Create(e) = self else {
+            tracing::debug!("Invalid image requested: {url}");
+            return;
+        };
+        match e {
+            CreateImageError::SourceNotAllowed(src) => {
+                tracing::warn!("Image requested for source that isn't allowed: {src}")
+            }
+            CreateImageError::ForbiddenPath(src) => {
+                tracing::warn!("Image requested for a source outside of the root: {src}")
+            }
+            CreateImageError::SourceNotFound(src) => {
+                tracing::debug!("Image requested for missing source: {src}")
+            }
+            CreateImageError::TooLarge(width, height) => {
+                tracing::warn!("Image requested above the maximum dimensions: {width}x{height}")
+            }
+            CreateImageError::QualityOutOfRange(quality) => {
+                tracing::warn!("Image requested outside of the allowed qualities: {quality}")
+            }
+            CreateImageError::TooManyTransforms(count) => {
+                tracing::warn!("Image requested with too many transforms: {count}")
+            }
+            CreateImageError::InvalidSignature => {
+                tracing::debug!("Image requested with an invalid signature: {url}")
+            }
+            CreateImageError::UnknownImage(image) => {
+                tracing::debug!("Image requested that the app never rendered: {image}")
+            }
+            CreateImageError::UnknownTenant(tenant) => {
+                tracing::warn!("Image requested for unknown tenant: {tenant}")
+            }
+            CreateImageError::ShuttingDown => {}
+            e => tracing::error!("Failed to create image: {:?}", e),
         }
     }
 }
 
-async fn execute_file_handler(
-    uri: Uri,
-    root: &str,
-) -> Result<Response<ServeFileSystemResponseBody>, Infallible> {
-    let req = Request::builder()
-        .uri(uri.clone())
-        .body(Body::empty())
-        .unwrap();
-    ServeDir::new(root).oneshot(req).await
-}
+impl ImageOptimizer {
+    /// Serves a request for an optimized image, for web frameworks without a handler in this crate.
+    ///
+    /// `url` is the path and query of the request, in either [`crate::UrlScheme`], and `accept` its `Accept`
+    /// header, used to pick the format. The image is created first if needed. Errors are logged, respond
+    /// with their [`ImageRequestError::status`] and [`ImageRequestError::message`].
+    ///
+    /// Register the handler for the optimizer's handler path, and every path below it.
+    ///
+    /// ```no_run
+    /// use leptos_image::*;
+    ///
+    /// # async fn your_handler(optimizer: ImageOptimizer, url: &str, accept: Option<&str>) {
+    /// match optimizer.handle_request(url, accept).await {
+    ///     Ok(response) => {
+    ///         let content_type = response.content_type();
+    ///         // Set the `Cache-Control` and `Vary` headers, then send the body:
+    ///         match response.body {
+    ///             ImageBody::Memory(svg) => { /* send the string */ }
+    ///             ImageBody::File { .. } => { /* stream `response.body.file_path()` */ }
+    ///         }
+    ///     }
+    ///     Err(e) => { /* respond with `e.status()` and `e.message()` */ }
+    /// }
+    /// # }
+    /// ```
+    pub async fn handle_request(
+        &self,
+        url: &str,
+        accept: Option<&str>,
+    ) -> Result<ImageResponse, ImageRequestError> {
+        self.image_response(url, accept)
+            .await
+            .inspect_err(|e| e.log(url))
+    }
 
-async fn check_cache_image(
-    optimizer: &ImageOptimizer,
-    uri: Uri,
-) -> Result<Option<Uri>, CreateImageError> {
-    let cache_image = {
-        let url = uri.to_string();
+    // Serves a request for an untouched source, see `ImageCacheRoute::image_original_route`.
+    #[cfg_attr(not(feature = "axum-handler"), allow(dead_code))]
+    pub(crate) async fn handle_original_request(
+        &self,
+        url: &str,
+    ) -> Result<(OriginalImage, ImageBody), ImageRequestError> {
+        self.original_file(url)
+            .await
+            .map_err(ImageRequestError::from)
+            .and_then(|file| file.ok_or_else(|| ImageRequestError::InvalidUrl(url.to_string())))
+            .inspect_err(|e| e.log(url))
+    }
 
-        if let Some(img) = CachedImage::from_url_encoded(&url).ok() {
-            let result = optimizer.create_image(&img).await;
+    async fn image_response(
+        &self,
+        url: &str,
+        accept: Option<&str>,
+    ) -> Result<ImageResponse, ImageRequestError> {
+        if let Some((image, svg)) = self.memory_image(url) {
+            return Ok(ImageResponse {
+                cache_control: self.cache_control_for(&image).map(str::to_string),
+                image,
+                body: ImageBody::Memory(svg),
+                vary_accept: false,
+                generation: None,
+            });
+        }
 
-            if let Ok(true) = result {
-                tracing::info!("Created Image: {}", img);
-            }
+        let file = self
+            .cached_file(url, accept)
+            .await?
+            .ok_or_else(|| ImageRequestError::InvalidUrl(url.to_string()))?;
+        Ok(ImageResponse {
+            cache_control: self.cache_control_for(&file.image).map(str::to_string),
+            image: file.image,
+            body: ImageBody::File {
+                root: file.root,
+                path: file.path,
+            },
+            vary_accept: file.negotiated,
+            generation: file.generation,
+        })
+    }
+}
 
-            result?;
+#[cfg(test)]
+mod routes_tests {
+    use super::*;
 
-            img
-        } else {
-            return Ok(None);
-        }
-    };
+    const TEST_IMAGE: &str = "./example/start-axum/public/cute_ferris.png";
 
-    let file_path = cache_image.get_file_path();
+    #[test]
+    fn handles_requests() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_control("public, max-age=60")
+            .build();
+        let image = CachedImage::resize(TEST_IMAGE, 48, 48, 75);
 
-    add_file_to_cache(optimizer, cache_image).await;
+        let response = runtime
+            .block_on(optimizer.handle_request(&image.get_path_encoded("/cache/image"), None))
+            .unwrap();
+        assert_eq!(response.content_type(), "image/webp");
+        assert_eq!(
+            response.cache_control.as_deref(),
+            Some("public, max-age=60")
+        );
+        assert!(response.body.file_path().unwrap().exists());
 
-    let uri_string = "/".to_string() + &file_path;
-    let maybe_uri = (uri_string).parse::<Uri>().ok();
+        let missing = CachedImage::resize("/missing.png", 48, 48, 75);
+        let error = runtime
+            .block_on(optimizer.handle_request(&missing.get_url_encoded("/cache/image"), None))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImageRequestError::Create(CreateImageError::SourceNotFound(_))
+        ));
+        assert_eq!((error.status(), error.message()), (404, "Image Not Found."));
 
-    if let Some(uri) = maybe_uri {
-        Ok(Some(uri))
-    } else {
-        tracing::error!("Failed to create uri: File path {file_path}");
-        Ok(None)
+        let error = runtime
+            .block_on(optimizer.handle_request("/cache/image/invalid", None))
+            .unwrap_err();
+        assert!(matches!(error, ImageRequestError::InvalidUrl(_)));
+        assert_eq!((error.status(), error.message()), (404, "Invalid Image."));
     }
-}
 
-// When the image is created, it will be added to the cache.
-// Mostly helpful for dev server startup.
-async fn add_file_to_cache(optimizer: &ImageOptimizer, image: CachedImage) {
-    if let CachedImageOption::Blur(_) = image.option {
-        if optimizer.cache.get(&image).is_none() {
-            let path = optimizer.get_file_path_from_root(&image);
-            match tokio::fs::read_to_string(path).await {
-                Ok(data) => {
-                    optimizer.cache.insert(image, data);
-                    tracing::debug!("Added image to cache (size {})", optimizer.cache.len())
-                }
-                Err(e) => {
-                    tracing::error!("Failed to read image [{}] with error: {:?}", image, e);
-                }
-            }
+    #[test]
+    fn ignores_requested_cache_control() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_control("public, max-age=60")
+            .build();
+        // Rooted like the sources of parsed URLs, so the rendered image is found.
+        let image = CachedImage::resize(TEST_IMAGE.trim_start_matches('.'), 44, 44, 75);
+        let cache_control = |url: String| {
+            runtime
+                .block_on(optimizer.handle_request(&url, None))
+                .unwrap()
+                .cache_control
+        };
+
+        // Clients can't pick the header, in either scheme.
+        let requested = image.clone().with_cache_control("no-store");
+        let query = format!("/cache/image?{}", serde_qs::to_string(&requested).unwrap());
+        let path = image
+            .get_path_encoded("/cache/image")
+            .replace("q_75", "q_75,cc_no-store");
+        for url in [query, path] {
+            assert_eq!(cache_control(url).as_deref(), Some("public, max-age=60"));
         }
+
+        // Only the server does, when it renders the image.
+        let banner = image.clone().with_cache_control("public, max-age=300");
+        optimizer.allow_image(&banner);
+        let url = banner.get_path_encoded("/cache/image");
+        assert_eq!(url, image.get_path_encoded("/cache/image"));
+        assert_eq!(cache_control(url).as_deref(), Some("public, max-age=300"));
     }
 }
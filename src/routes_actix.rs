@@ -0,0 +1,104 @@
+use crate::optimizer::ImageOptimizer;
+use crate::routes::ImageBody;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+/// This trait prevents using incorrect route for image cache handler with Actix Web.
+pub trait ImageCacheService {
+    /// Adds a route to the app for serving cached images, and the optimizer to the app data.
+    ///
+    /// ```no_run
+    /// use actix_web::{App, HttpServer};
+    /// use leptos_image::*;
+    ///
+    /// # async fn your_main_function() -> std::io::Result<()> {
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+    ///
+    /// HttpServer::new(move || {
+    ///     App::new()
+    ///         // Add a handler for serving the cached images.
+    ///         .image_cache_service(&optimizer)
+    ///     // Add your Leptos routes with `optimizer.provide_context()` ...
+    /// })
+    /// .bind(("127.0.0.1", 3000))?
+    /// .run()
+    /// .await
+    /// # }
+    /// ```
+    fn image_cache_service(self, optimizer: &ImageOptimizer) -> Self;
+}
+
+impl<T, B> ImageCacheService for actix_web::App<T>
+where
+    B: MessageBody,
+    T: ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<B>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+{
+    fn image_cache_service(self, optimizer: &ImageOptimizer) -> Self {
+        let path = optimizer.handler_path();
+        // Path encoded urls, see [`crate::UrlScheme::Path`].
+        let path_scheme = format!("{}/{{spec:.*}}", path.trim_end_matches('/'));
+
+        self.app_data(web::Data::new(optimizer.clone()))
+            .route(&path, web::get().to(actix_handler))
+            .route(&path_scheme, web::get().to(actix_handler))
+    }
+}
+
+/// Actix Web handler serving cached images, creating them on the first request.
+///
+/// Requires the [`ImageOptimizer`] in the app data, and must be registered for the optimizer's handler path.
+/// Prefer [`ImageCacheService::image_cache_service`], which does both.
+pub async fn actix_handler(optimizer: web::Data<ImageOptimizer>, req: HttpRequest) -> HttpResponse {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let image = match optimizer
+        .handle_request(&req.uri().to_string(), accept)
+        .await
+    {
+        Ok(image) => image,
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status()).unwrap();
+            return HttpResponse::build(status).body(e.message());
+        }
+    };
+
+    let content_type = image.content_type();
+    let mut response = match image.body {
+        ImageBody::Memory(svg) => {
+            let mut response = HttpResponse::Ok();
+            response.content_type(content_type);
+            response.body(svg)
+        }
+        ImageBody::File { root, path } => {
+            let path = std::path::Path::new(&root).join(path);
+            match actix_files::NamedFile::open_async(&path).await {
+                Ok(named) => named.into_response(&req),
+                Err(e) => {
+                    tracing::error!("Failed to open image [{}]: {:?}", path.display(), e);
+                    return HttpResponse::NotFound().body("Invalid Image.");
+                }
+            }
+        }
+    };
+    let cache_control = image.cache_control.as_deref();
+    if let Some(value) = cache_control.and_then(|v| HeaderValue::from_str(v).ok()) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+    if image.vary_accept {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+    response
+}
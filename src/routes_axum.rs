@@ -0,0 +1,638 @@
+use crate::optimizer::{constant_time_eq, CachedImage, ImageOptimizer};
+use crate::request_log::{CacheStatus, ImageRequestInfo};
+use crate::routes::ImageBody;
+use axum::extract::FromRef;
+use axum::response::Response as AxumResponse;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode, Uri},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::convert::Infallible;
+use tower::ServiceExt;
+use tower_http::services::fs::ServeFileSystemResponseBody;
+use tower_http::services::ServeDir;
+
+/// This trait prevents using incorrect route for image cache handler.
+pub trait ImageCacheRoute<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    /// Adds a route to the app for serving cached images.
+    /// Requires an axum State that contains the optimizer [`crate::ImageOptimizer`].
+    ///
+    /// ```
+    /// use leptos_image::*;
+    /// use leptos::*;
+    /// use axum::*;
+    /// use axum::routing::post;
+    /// use leptos_axum::{generate_route_list, handle_server_fns, LeptosRoutes};
+    ///
+    /// #[cfg(feature = "ssr")]
+    /// async fn your_main_function() {
+    ///
+    ///   let options = get_configuration(None).await.unwrap().leptos_options;
+    ///   let optimizer = ImageOptimizer::new("/__cache/image", options.site_root.clone(), 1);
+    ///   let state = AppState {leptos_options: options, optimizer: optimizer.clone() };
+    ///   let routes = generate_route_list(App);
+    ///
+    ///   let router: Router<()> = Router::new()
+    ///    .route("/api/*fn_name", post(leptos_axum::handle_server_fns))
+    ///    // Add a handler for serving the cached images.
+    ///    .image_cache_route(&state)
+    ///    .leptos_routes_with_context(&state, routes, optimizer.provide_context(), App)
+    ///    .with_state(state);
+    ///
+    ///   // Rest of your function ...
+    /// }
+    ///
+    /// // Composite App State with the optimizer and leptos options.
+    /// #[derive(Clone, axum::extract::FromRef)]
+    /// struct AppState {
+    ///   leptos_options: leptos::LeptosOptions,
+    ///   optimizer: leptos_image::ImageOptimizer,
+    /// }
+    ///
+    /// #[component]
+    /// fn App() -> impl IntoView {
+    ///   provide_image_context();
+    ///   ()
+    /// }
+    ///
+    /// ```
+    ///
+    ///
+    fn image_cache_route(self, state: &S) -> Self;
+
+    /// Adds a `POST {api_handler_path}/warm` route that generates images in the background.
+    ///
+    /// Requests must send the token configured with [`crate::ImageOptimizerBuilder::warmup_token`]
+    /// as an `Authorization: Bearer <token>` header. The route is not added if no token is configured.
+    ///
+    /// The JSON body lists the images to generate, either as optimized image URLs found in the rendered HTML,
+    /// or as serialized [`crate::CachedImage`] specs. With the `fetch` feature, absolute page URLs are
+    /// fetched and their images generated, like with [`ImageOptimizer::warm_from_urls`].
+    ///
+    /// ```json
+    /// {
+    ///   "images": [
+    ///     "/cache/image?src=/hero.jpg&option[r][w]=800&option[r][h]=600&option[r][q]=75",
+    ///     "https://example.com/blog/first-post"
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// Responds with `202 Accepted` and the number of images and pages queued.
+    fn image_cache_warm_route(self, state: &S) -> Self;
+
+    /// Adds a `GET {api_handler_path}/original` route that serves source images untouched,
+    /// for "download full resolution" links. Build the links with [`crate::use_original_image_url`].
+    ///
+    /// Sources are resolved like optimized images, against the tenant's root or [`crate::ImageSource`] and
+    /// the allowed sources, and only image files are served. With a signing key or in strict mode, only
+    /// links rendered on the server are served.
+    fn image_original_route(self, state: &S) -> Self;
+}
+
+impl<S> ImageCacheRoute<S> for axum::Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    ImageOptimizer: FromRef<S>,
+{
+    fn image_cache_route(self, state: &S) -> Self {
+        let optimizer = ImageOptimizer::from_ref(state);
+
+        let path = optimizer.handler_path();
+        // Path encoded urls, see [`crate::UrlScheme::Path`].
+        let path_scheme = format!("{}/*spec", path.trim_end_matches('/'));
+        let handler = move |req: Request<Body>| image_cache_handler(optimizer, req);
+
+        self.route(&path, axum::routing::get(handler.clone()))
+            .route(&path_scheme, axum::routing::get(handler))
+    }
+
+    fn image_cache_warm_route(self, state: &S) -> Self {
+        let optimizer = ImageOptimizer::from_ref(state);
+
+        if optimizer.warmup_token.is_none() {
+            tracing::warn!("No warm-up token configured, skipping image cache warm-up route.");
+            return self;
+        }
+
+        let path = format!("{}/warm", optimizer.handler_path().trim_end_matches('/'));
+        let handler = move |headers: HeaderMap, body: axum::body::Bytes| {
+            warm_cache_handler(optimizer, headers, body)
+        };
+
+        self.route(&path, axum::routing::post(handler))
+    }
+
+    fn image_original_route(self, state: &S) -> Self {
+        let optimizer = ImageOptimizer::from_ref(state);
+
+        let path = format!(
+            "{}/original",
+            optimizer.handler_path().trim_end_matches('/')
+        );
+        let handler = move |uri: Uri| original_handler(optimizer, uri);
+
+        self.route(&path, axum::routing::get(handler))
+    }
+}
+
+async fn original_handler(optimizer: ImageOptimizer, uri: Uri) -> AxumResponse {
+    let url = uri.to_string();
+    let (original, root, path) = match optimizer.handle_original_request(&url).await {
+        Ok((original, ImageBody::File { root, path })) => (original, root, path),
+        Ok((_, ImageBody::Memory(_))) => {
+            return (StatusCode::NOT_FOUND, "Invalid Image.").into_response();
+        }
+        Err(e) => {
+            return Response::builder()
+                .status(e.status())
+                .body(e.message().to_string())
+                .unwrap()
+                .into_response();
+        }
+    };
+
+    let path = percent_encoding::utf8_percent_encode(&path, crate::optimizer::PATH_ENCODE_SET);
+    let Ok(uri) = format!("/{path}").parse::<Uri>() else {
+        return (StatusCode::NOT_FOUND, "Invalid Image.").into_response();
+    };
+
+    let mut response = execute_file_handler(uri, &root, &HeaderMap::new())
+        .await
+        .unwrap();
+    if original.download && response.status().is_success() {
+        let file_name = std::path::Path::new(&original.src)
+            .file_name()
+            .map(|name| name.to_string_lossy().replace('"', ""))
+            .unwrap_or_default();
+        let disposition = format!("attachment; filename=\"{file_name}\"");
+        if let Ok(value) = HeaderValue::from_str(&disposition) {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+    response.into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct WarmRequest {
+    images: Vec<WarmImage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WarmImage {
+    Url(String),
+    Spec(CachedImage),
+}
+
+async fn warm_cache_handler(
+    optimizer: ImageOptimizer,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> AxumResponse {
+    let authorized = optimizer.warmup_token.as_deref().is_some_and(|token| {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    });
+
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, "Invalid warm-up token.").into_response();
+    }
+
+    // Parsed once authorized, so anonymous requests can't make the server parse their body.
+    let body: WarmRequest = match serde_json::from_slice(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::debug!("Invalid warm-up request: {e}");
+            return (StatusCode::BAD_REQUEST, "Invalid warm-up request.").into_response();
+        }
+    };
+
+    let mut images: Vec<CachedImage> = Vec::new();
+    let mut pages: Vec<String> = Vec::new();
+    for image in body.images {
+        match image {
+            WarmImage::Spec(spec) => images.push(spec),
+            WarmImage::Url(url) => match optimizer.parse_image_url(&url) {
+                Some(image) => images.push(image),
+                None if cfg!(feature = "fetch") && crate::optimizer::is_remote(&url) => {
+                    pages.push(url)
+                }
+                None => tracing::warn!("Skipping invalid image url {url}"),
+            },
+        }
+    }
+
+    let message = match pages.len() {
+        0 => format!("Queued {} images.", images.len()),
+        count => format!("Queued {} images and {count} pages.", images.len()),
+    };
+
+    tokio::spawn(async move {
+        let start = std::time::Instant::now();
+        let mut report = crate::CacheReport::default();
+        for image in images {
+            report.record(&optimizer, &image).await;
+        }
+        report.elapsed = start.elapsed();
+        tracing::info!("Warm-up finished, {report}");
+
+        #[cfg(feature = "fetch")]
+        if !pages.is_empty() {
+            match optimizer.warm_from_urls(pages).await {
+                Ok(report) => tracing::info!("Warm-up of pages finished, {report}"),
+                Err(e) => tracing::warn!("Warm-up of pages failed: {e}"),
+            }
+        }
+    });
+
+    (StatusCode::ACCEPTED, message).into_response()
+}
+
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(uri = %req.uri(), status = tracing::field::Empty, cache = tracing::field::Empty)
+)]
+async fn image_cache_handler(optimizer: ImageOptimizer, req: Request<Body>) -> AxumResponse {
+    let response = image_cache_handler_inner(optimizer, req).await;
+    let span = tracing::Span::current();
+    let status = response.status().as_u16();
+    let cache = response
+        .extensions()
+        .get::<ImageRequestInfo>()
+        .map_or("none", |info| info.cache.as_str());
+    span.record("status", status);
+    span.record("cache", cache);
+    let bytes = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse().ok());
+    crate::telemetry::served(cache, status, bytes);
+    response
+}
+
+async fn image_cache_handler_inner(optimizer: ImageOptimizer, req: Request<Body>) -> AxumResponse {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let image = match optimizer
+        .handle_request(&req.uri().to_string(), accept)
+        .await
+    {
+        Ok(image) => image,
+        Err(e) => {
+            return Response::builder()
+                .status(e.status())
+                .body(e.message().to_string())
+                .unwrap()
+                .into_response();
+        }
+    };
+    let cache_control = image
+        .cache_control
+        .as_deref()
+        .and_then(|value| HeaderValue::from_str(value).ok());
+
+    let content_type = image.content_type();
+    let (root, path) = match image.body {
+        ImageBody::Memory(svg) => {
+            return serve_from_memory(&req, image.image, svg, content_type, cache_control);
+        }
+        ImageBody::File { root, path } => (root, path),
+    };
+    // The file is served by path from its root. SVG sources keep their own name, which may need encoding.
+    let encoded = percent_encoding::utf8_percent_encode(&path, crate::optimizer::PATH_ENCODE_SET);
+    let Ok(uri) = format!("/{encoded}").parse::<Uri>() else {
+        tracing::error!("Failed to create uri: File path {}", path);
+        return Response::builder()
+            .status(404)
+            .body("Invalid Image.".to_string())
+            .unwrap()
+            .into_response();
+    };
+
+    let etag = file_etag(&root, &path).await;
+    let mut response = match &etag {
+        Some(etag) if etag_matches(req.headers(), etag) => StatusCode::NOT_MODIFIED.into_response(),
+        // Conditional and range headers are forwarded, so `Last-Modified` is honored too.
+        _ => execute_file_handler(uri, &root, req.headers())
+            .await
+            .unwrap()
+            .into_response(),
+    };
+    if let Some(value) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    if let Some(value) = cache_control {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+    if image.vary_accept {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+    response.extensions_mut().insert(ImageRequestInfo {
+        spec: image.image.to_string(),
+        cache: if image.generation.is_some() {
+            CacheStatus::Generated
+        } else {
+            CacheStatus::Disk
+        },
+        generation: image.generation,
+    });
+    response
+}
+
+// Responds with an image held in memory, skipping the filesystem.
+fn serve_from_memory(
+    req: &Request<Body>,
+    image: CachedImage,
+    svg: String,
+    content_type: &'static str,
+    cache_control: Option<HeaderValue>,
+) -> AxumResponse {
+    let etag = content_etag(svg.as_bytes());
+
+    let not_modified = etag_matches(req.headers(), &etag);
+
+    let mut response = if not_modified {
+        Response::builder().status(StatusCode::NOT_MODIFIED)
+    } else {
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, svg.len())
+    }
+    .header(header::ETAG, etag);
+    if let Some(value) = cache_control {
+        response = response.header(header::CACHE_CONTROL, value);
+    }
+    let body = if not_modified { String::new() } else { svg };
+    let mut response = response.body(body).unwrap().into_response();
+    response.extensions_mut().insert(ImageRequestInfo {
+        spec: image.to_string(),
+        cache: CacheStatus::Memory,
+        generation: None,
+    });
+    response
+}
+
+async fn execute_file_handler(
+    uri: Uri,
+    root: &str,
+    headers: &HeaderMap,
+) -> Result<Response<ServeFileSystemResponseBody>, Infallible> {
+    let mut req = Request::builder()
+        .uri(uri.clone())
+        .body(Body::empty())
+        .unwrap();
+    *req.headers_mut() = headers.clone();
+    ServeDir::new(root).oneshot(req).await
+}
+
+// Cached files never change once written, so their size and modification time identify the contents.
+async fn file_etag(root: &str, path: &str) -> Option<String> {
+    let path = std::path::Path::new(root).join(path);
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(format!("\"{:x}-{:x}\"", metadata.len(), modified.as_secs()))
+}
+
+fn content_etag(content: &[u8]) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("\"{:x}-{:x}\"", content.len(), hasher.finish())
+}
+
+// Whether the `If-None-Match` header of the request lists the ETag.
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod routes_axum_tests {
+    use super::*;
+    use crate::optimizer::OriginalImage;
+
+    #[test]
+    fn matches_etags() {
+        let etag = "\"1a-2b\"";
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(etag_matches(&headers("\"1a-2b\""), etag));
+        assert!(etag_matches(&headers("\"other\", W/\"1a-2b\""), etag));
+        assert!(etag_matches(&headers("*"), etag));
+        assert!(!etag_matches(&headers("\"1a-2c\""), etag));
+        assert!(!etag_matches(&HeaderMap::new(), etag));
+    }
+
+    #[test]
+    fn serves_rendered_originals() {
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_namespace("originals")
+            .image_source(crate::LocalSource::new("./example/start-axum/public"))
+            .signing_key("secret")
+            .strict(true)
+            .build();
+        let router: axum::Router = axum::Router::new()
+            .image_original_route(&optimizer)
+            .with_state(optimizer.clone());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let status = |url: &str| {
+            let request = Request::get(url).body(Body::empty()).unwrap();
+            runtime
+                .block_on(router.clone().oneshot(request))
+                .unwrap()
+                .status()
+        };
+
+        let original = OriginalImage {
+            src: "/cute_ferris.png".to_string(),
+            tenant: None,
+            download: true,
+        };
+        let url = original.get_url("/cache/image");
+        assert_eq!(status(&url), StatusCode::FORBIDDEN);
+
+        let rendered = optimizer.rendered_original_url(url.clone(), &original);
+        assert_eq!(status(&rendered), StatusCode::OK);
+        assert_eq!(
+            status(&rendered.replace("cute_ferris", "other")),
+            StatusCode::FORBIDDEN
+        );
+
+        // Signed but never rendered, e.g. after a restart.
+        optimizer.known_originals.clear();
+        assert_eq!(status(&rendered), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn authorizes_warm_up_before_parsing() {
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_namespace("warm-route")
+            .warmup_token("secret")
+            .build();
+        let router: axum::Router = axum::Router::new()
+            .image_cache_warm_route(&optimizer)
+            .with_state(optimizer);
+        let request = |token: &str, body: &'static str| {
+            Request::post("/cache/image/warm")
+                .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let status = |request: Request<Body>| {
+            runtime
+                .block_on(router.clone().oneshot(request))
+                .unwrap()
+                .status()
+        };
+        assert_eq!(
+            status(request("wrong", "not json")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(request("secret", "not json")),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(request("secret", r#"{"images": []}"#)),
+            StatusCode::ACCEPTED
+        );
+    }
+
+    #[test]
+    fn revalidates_cached_images() {
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_control("public, max-age=31536000, immutable")
+            .build();
+        let router: axum::Router = axum::Router::new()
+            .image_cache_route(&optimizer)
+            .with_state(optimizer);
+        let image =
+            crate::CachedImage::resize("./example/start-axum/public/cute_ferris.png", 48, 48, 75);
+        let url = image.get_path_encoded("/cache/image");
+        let request = |etag: Option<&str>| {
+            let mut request = Request::builder().uri(&url);
+            if let Some(etag) = etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime
+            .block_on(router.clone().oneshot(request(None)))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(header::LAST_MODIFIED));
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = runtime
+            .block_on(router.clone().oneshot(request(Some(&etag))))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+    }
+    #[test]
+    fn negotiates_formats() {
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let router: axum::Router = axum::Router::new()
+            .image_cache_route(&optimizer)
+            .with_state(optimizer.clone());
+        let image =
+            crate::CachedImage::resize("./example/start-axum/public/cute_ferris.png", 40, 40, 75);
+        let request = |accept: Option<&str>| {
+            let mut request = Request::builder().uri(image.get_path_encoded("/cache/image"));
+            if let Some(accept) = accept {
+                request = request.header(header::ACCEPT, accept);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let safari = "image/png,image/svg+xml,image/*;q=0.8,*/*;q=0.5";
+        for (accept, mime) in [
+            (None, "image/webp"),
+            (Some("image/webp,*/*"), "image/webp"),
+            (Some(safari), "image/png"),
+        ] {
+            let response = runtime
+                .block_on(router.clone().oneshot(request(accept)))
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], mime);
+            assert_eq!(response.headers()[header::VARY], "Accept");
+        }
+
+        optimizer.update_config(|config| config.negotiate_format = false);
+        let router: axum::Router = axum::Router::new()
+            .image_cache_route(&optimizer)
+            .with_state(optimizer);
+        let response = runtime
+            .block_on(router.oneshot(request(Some(safari))))
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/webp");
+        assert!(!response.headers().contains_key(header::VARY));
+    }
+
+    #[test]
+    fn serves_svgs_with_reserved_characters() {
+        let src = "cache/svg-route/my logo.svg";
+        std::fs::create_dir_all("cache/svg-route").unwrap();
+        std::fs::write(src, r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#).unwrap();
+        let optimizer = ImageOptimizer::new("/cache/image", ".", 1);
+        let router: axum::Router = axum::Router::new()
+            .image_cache_route(&optimizer)
+            .with_state(optimizer);
+        let image = crate::CachedImage::resize(format!("/{src}"), 100, 100, 75);
+        let request = Request::get(image.get_path_encoded("/cache/image"))
+            .body(Body::empty())
+            .unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime.block_on(router.oneshot(request)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+    }
+}
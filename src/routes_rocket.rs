@@ -0,0 +1,82 @@
+use crate::optimizer::ImageOptimizer;
+use crate::routes::ImageBody;
+use rocket::http::{Header, Method, Status};
+use rocket::route::{Handler, Outcome, Route};
+use rocket::{Build, Data, Request, Response, Rocket};
+
+/// This trait prevents using incorrect route for image cache handler with Rocket.
+pub trait ImageCacheMount {
+    /// Mounts a route at the optimizer's handler path for serving cached images.
+    ///
+    /// ```no_run
+    /// use leptos_image::*;
+    ///
+    /// # fn your_rocket() -> rocket::Rocket<rocket::Build> {
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+    ///
+    /// rocket::build()
+    ///     // Add a handler for serving the cached images.
+    ///     .image_cache_mount(&optimizer)
+    /// // Add your Leptos routes with `optimizer.provide_context()` ...
+    /// # }
+    /// ```
+    fn image_cache_mount(self, optimizer: &ImageOptimizer) -> Self;
+}
+
+impl ImageCacheMount for Rocket<Build> {
+    fn image_cache_mount(self, optimizer: &ImageOptimizer) -> Self {
+        let handler = ImageCacheHandler(optimizer.clone());
+        // Also matches no segment, so query encoded urls are served too, see [`crate::UrlScheme::Path`].
+        let route = Route::new(Method::Get, "/<spec..>", handler);
+        self.mount(optimizer.handler_path(), vec![route])
+    }
+}
+
+// Rocket handler serving cached images, creating them on the first request.
+#[derive(Clone)]
+struct ImageCacheHandler(ImageOptimizer);
+
+#[rocket::async_trait]
+impl Handler for ImageCacheHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, _data: Data<'r>) -> Outcome<'r> {
+        let accept = req.headers().get_one("Accept");
+        let image = match self.0.handle_request(&req.uri().to_string(), accept).await {
+            Ok(image) => image,
+            Err(e) => {
+                let response = Response::build()
+                    .status(Status::new(e.status()))
+                    .sized_body(e.message().len(), std::io::Cursor::new(e.message()))
+                    .finalize();
+                return Outcome::Success(response);
+            }
+        };
+
+        let content_type = image.content_type();
+        let mut response = Response::build();
+        match image.body {
+            ImageBody::Memory(svg) => {
+                response.sized_body(svg.len(), std::io::Cursor::new(svg));
+            }
+            ImageBody::File { root, path } => {
+                let path = std::path::Path::new(&root).join(path);
+                match rocket::tokio::fs::File::open(&path).await {
+                    Ok(file) => {
+                        response.sized_body(None, file);
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to open image [{}]: {:?}", path.display(), e);
+                        return Outcome::Error(Status::NotFound);
+                    }
+                }
+            }
+        }
+        response.raw_header("Content-Type", content_type);
+        if let Some(cache_control) = image.cache_control {
+            response.header(Header::new("Cache-Control", cache_control));
+        }
+        if image.vary_accept {
+            response.raw_header("Vary", "Accept");
+        }
+        Outcome::Success(response.finalize())
+    }
+}
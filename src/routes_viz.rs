@@ -0,0 +1,87 @@
+use crate::optimizer::ImageOptimizer;
+use crate::routes::ImageBody;
+use viz::header::{self, HeaderValue};
+use viz::{Request, Response, ResponseExt, StatusCode};
+
+/// This trait prevents using incorrect route for image cache handler with Viz.
+pub trait ImageCacheVizRoute {
+    /// Adds a route to the router for serving cached images.
+    ///
+    /// ```no_run
+    /// use leptos_image::*;
+    ///
+    /// # fn your_router() -> viz::Router {
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+    ///
+    /// viz::Router::new()
+    ///     // Add a handler for serving the cached images.
+    ///     .image_cache_route(&optimizer)
+    /// // Add your Leptos routes with `optimizer.provide_context()` ...
+    /// # }
+    /// ```
+    fn image_cache_route(self, optimizer: &ImageOptimizer) -> Self;
+}
+
+impl ImageCacheVizRoute for viz::Router {
+    fn image_cache_route(self, optimizer: &ImageOptimizer) -> Self {
+        let path = optimizer.handler_path();
+        // Path encoded urls, see [`crate::UrlScheme::Path`].
+        let path_scheme = format!("{}/*", path.trim_end_matches('/'));
+        let optimizer = optimizer.clone();
+        let handler = move |req: Request| viz_handler(optimizer.clone(), req);
+
+        self.get(&path, handler.clone()).get(&path_scheme, handler)
+    }
+}
+
+/// Viz handler serving cached images, creating them on the first request.
+///
+/// Must be registered for the optimizer's handler path.
+/// Prefer [`ImageCacheVizRoute::image_cache_route`], which registers both URL schemes.
+pub async fn viz_handler(optimizer: ImageOptimizer, req: Request) -> viz::Result<Response> {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let image = match optimizer
+        .handle_request(&req.uri().to_string(), accept)
+        .await
+    {
+        Ok(image) => image,
+        Err(e) => {
+            let mut response = Response::text(e.message());
+            *response.status_mut() = StatusCode::from_u16(e.status()).unwrap();
+            return Ok(response);
+        }
+    };
+
+    let content_type = image.content_type();
+    let mut response = match image.body {
+        ImageBody::Memory(svg) => Response::text(svg),
+        ImageBody::File { root, path } => {
+            let path = std::path::Path::new(&root).join(path);
+            match tokio::fs::File::open(&path).await {
+                Ok(file) => Response::stream(tokio_util::io::ReaderStream::new(file)),
+                Err(e) => {
+                    tracing::error!("Failed to open image [{}]: {:?}", path.display(), e);
+                    let mut response = Response::text("Invalid Image.");
+                    *response.status_mut() = StatusCode::NOT_FOUND;
+                    return Ok(response);
+                }
+            }
+        }
+    };
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    let cache_control = image.cache_control.as_deref();
+    if let Some(value) = cache_control.and_then(|v| HeaderValue::from_str(v).ok()) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+    if image.vary_accept {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+    Ok(response)
+}
@@ -0,0 +1,96 @@
+/// Common page layouts, used to infer the `sizes` attribute of responsive images.
+///
+/// Without a correct `sizes`, browsers assume the image spans the whole viewport
+/// and download a larger variant of the srcset than needed.
+///
+/// ```
+/// use leptos_image::*;
+///
+/// let sizes = sizes_from_layout(Layout::Grid { columns: 3, gap: 16 });
+/// assert_eq!(sizes, "calc((100vw - 32px) / 3)");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// The image spans the whole viewport.
+    FullWidth,
+    /// The image always has the same width, in pixels.
+    Fixed(u32),
+    /// The image spans the viewport up to a maximum width, in pixels.
+    Constrained(u32),
+    /// The image is one cell of a grid spanning the viewport, with a gap in pixels between columns.
+    Grid {
+        /// Number of columns.
+        columns: u32,
+        /// Gap between columns, in pixels.
+        gap: u32,
+    },
+    /// Like [`Layout::Grid`], in a container that stops growing at `max_width` pixels.
+    ConstrainedGrid {
+        /// Number of columns.
+        columns: u32,
+        /// Gap between columns, in pixels.
+        gap: u32,
+        /// Maximum width of the grid container, in pixels.
+        max_width: u32,
+    },
+}
+
+/// Produces the `sizes` attribute for an image rendered in the given layout.
+pub fn sizes_from_layout(layout: Layout) -> String {
+    match layout {
+        Layout::FullWidth => "100vw".to_string(),
+        Layout::Fixed(width) => format!("{width}px"),
+        Layout::Constrained(max_width) => {
+            format!("(min-width: {max_width}px) {max_width}px, 100vw")
+        }
+        Layout::Grid { columns, gap } => grid_cell(columns, gap),
+        Layout::ConstrainedGrid {
+            columns,
+            gap,
+            max_width,
+        } => {
+            let columns = columns.max(1);
+            let cell = max_width.saturating_sub(gap * (columns - 1)) / columns;
+            format!(
+                "(min-width: {max_width}px) {cell}px, {}",
+                grid_cell(columns, gap)
+            )
+        }
+    }
+}
+
+// Width of a grid cell when the grid spans the viewport.
+fn grid_cell(columns: u32, gap: u32) -> String {
+    match columns.max(1) {
+        1 => "100vw".to_string(),
+        columns => format!("calc((100vw - {}px) / {columns})", gap * (columns - 1)),
+    }
+}
+
+/// The `sizes` attribute of an `<Image/>`, either written by hand or inferred from a [`Layout`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Sizes(pub(crate) String);
+
+impl From<String> for Sizes {
+    fn from(sizes: String) -> Self {
+        Self(sizes)
+    }
+}
+
+impl From<&str> for Sizes {
+    fn from(sizes: &str) -> Self {
+        Self(sizes.to_string())
+    }
+}
+
+impl From<Layout> for Sizes {
+    fn from(layout: Layout) -> Self {
+        Self(sizes_from_layout(layout))
+    }
+}
+
+impl From<Layout> for String {
+    fn from(layout: Layout) -> Self {
+        sizes_from_layout(layout)
+    }
+}
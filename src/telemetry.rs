@@ -0,0 +1,54 @@
+// Metrics emitted through the `metrics` facade with the `metrics` feature, their names are listed in the README.
+// Without the feature every function is a no-op, so call sites don't need to be feature gated.
+
+use std::time::Duration;
+
+pub(crate) fn cache_lookup(hit: bool) {
+    #[cfg(feature = "metrics")]
+    if hit {
+        metrics::counter!("leptos_image_cache_hits_total").increment(1);
+    } else {
+        metrics::counter!("leptos_image_cache_misses_total").increment(1);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = hit;
+}
+
+pub(crate) fn encoded(elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("leptos_image_encode_seconds").record(elapsed.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = elapsed;
+}
+
+pub(crate) fn semaphore_waited(elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("leptos_image_semaphore_wait_seconds").record(elapsed.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = elapsed;
+}
+
+pub(crate) fn in_flight(count: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!("leptos_image_in_flight_encodes").set(count as f64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = count;
+}
+
+#[cfg_attr(not(feature = "axum-handler"), allow(dead_code))]
+pub(crate) fn served(cache: &'static str, status: u16, bytes: Option<u64>) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(
+            "leptos_image_requests_total",
+            "cache" => cache,
+            "status" => status.to_string()
+        )
+        .increment(1);
+        if let Some(bytes) = bytes {
+            metrics::counter!("leptos_image_served_bytes_total").increment(bytes);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (cache, status, bytes);
+}
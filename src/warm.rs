@@ -0,0 +1,587 @@
+use crate::cache::CacheReport;
+use crate::optimizer::{
+    path_from_segments, write_cache_file, CachedImage, CreateImageError, ImageOptimizer,
+};
+
+use leptos::*;
+
+impl ImageOptimizer {
+    /// Pre-generates the images of every page of the app in the background, typically right after the server starts.
+    ///
+    /// Each route without parameters is rendered with this optimizer in context, the same way it is served,
+    /// and the optimized images (and blur placeholders) found in the page are created.
+    /// Routes with parameters like `/post/:id` can't be enumerated, use `warm_from_urls` (`fetch` feature) for those.
+    /// Images that fail are recorded in the returned [`CacheReport`], so a broken image doesn't stop the rest.
+    ///
+    /// Must be called from within a Tokio runtime. The returned handle fails only if the app couldn't be rendered.
+    ///
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_image::*;
+    ///
+    /// #[component]
+    /// fn App() -> impl IntoView {
+    ///     view! { <Image src="/cute_ferris.png" width=100 height=100/> }
+    /// }
+    ///
+    /// # async fn start() {
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+    /// optimizer.warm_cache(App);
+    /// # }
+    /// ```
+    pub fn warm_cache<IV>(
+        &self,
+        app_fn: impl Fn() -> IV + Clone + Send + 'static,
+    ) -> tokio::task::JoinHandle<Result<CacheReport, CreateImageError>>
+    where
+        IV: IntoView + 'static,
+    {
+        let optimizer = self.clone();
+        tokio::spawn(async move {
+            let (report, _) = optimizer.create_app_images(app_fn).await?;
+            tracing::info!("Warmed the image cache, {report}");
+            Ok(report)
+        })
+    }
+
+    /// Creates the images of every page of the app for static hosting, e.g. before generating the site
+    /// with `leptos_axum` static routes or uploading it to Netlify or GitHub Pages.
+    ///
+    /// Pages are rendered like in [`ImageOptimizer::warm_cache`], and every image found is created in the
+    /// cache directory under the root. Render the site with [`crate::UrlScheme::Static`] afterwards,
+    /// so its images point to these files instead of the image handler.
+    ///
+    /// The files created are listed in `export.json` in the cache directory, as pairs of the image and the URL
+    /// of its file. Images that fail are recorded in the returned [`CacheReport`] and left out of the manifest.
+    ///
+    /// ```no_run
+    /// use leptos::*;
+    /// use leptos_image::*;
+    ///
+    /// #[component]
+    /// fn App() -> impl IntoView {
+    ///     view! { <Image src="/cute_ferris.png" width=100 height=100/> }
+    /// }
+    ///
+    /// # async fn export() -> Result<(), CreateImageError> {
+    /// let optimizer = ImageOptimizer::builder()
+    ///     .url_scheme(UrlScheme::Static)
+    ///     .build();
+    /// let report = optimizer.export(App).await?;
+    /// assert!(report.is_success(), "{report}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn export<IV>(
+        &self,
+        app_fn: impl Fn() -> IV + Clone + Send + 'static,
+    ) -> Result<CacheReport, CreateImageError>
+    where
+        IV: IntoView + 'static,
+    {
+        // Query URLs name the image they are for, as rendered, static file URLs only its digest.
+        let mut renderer = self.clone();
+        renderer.url_scheme = crate::UrlScheme::Query;
+        let (report, images) = renderer.create_app_images(app_fn).await?;
+
+        let static_dir = self.static_dir();
+        let settings = self.config().encode_digest();
+        let files: Vec<(CachedImage, String)> = images
+            .into_iter()
+            .filter(|image| !report.failed.iter().any(|(failed, _)| failed == image))
+            .map(|image| {
+                let url = image.get_static_url(&static_dir, &settings);
+                (image, url)
+            })
+            .collect();
+        let manifest = serde_json::to_vec(&files).map_err(std::io::Error::from)?;
+        write_cache_file(&self.export_manifest_path(), manifest).await?;
+        tracing::info!("Exported {} images, {report}", files.len());
+        Ok(report)
+    }
+
+    // Lists the files created by `ImageOptimizer::export`.
+    fn export_manifest_path(&self) -> std::path::PathBuf {
+        path_from_segments(vec![
+            &self.root_file_path,
+            &self.cache_dir,
+            &self.cache_namespace,
+            "export.json",
+        ])
+    }
+
+    // Creates the images of every static route, returned with the outcome.
+    async fn create_app_images<IV>(
+        &self,
+        app_fn: impl Fn() -> IV + Clone + Send + 'static,
+    ) -> Result<(CacheReport, Vec<CachedImage>), CreateImageError>
+    where
+        IV: IntoView + 'static,
+    {
+        let start = std::time::Instant::now();
+        // Views aren't `Send`, so the pages are rendered on a blocking thread driving a `LocalSet`.
+        // Tasks spawned while rendering, like blur placeholders, still run on the server's runtime.
+        let runtime = tokio::runtime::Handle::current();
+        let renderer = self.clone();
+        let urls = tokio::task::spawn_blocking(move || {
+            let local = tokio::task::LocalSet::new();
+            runtime.block_on(local.run_until(renderer.render_image_urls(app_fn)))
+        })
+        .await?;
+
+        let total = urls.len();
+        let mut report = CacheReport::default();
+        let mut images = Vec::with_capacity(total);
+        for (index, image_url) in urls.iter().enumerate() {
+            let Some(image) = self.parse_image_url(image_url) else {
+                tracing::warn!("Skipping invalid image url {image_url}");
+                continue;
+            };
+            report.record(self, &image).await;
+            tracing::info!("Warmed {}/{total} images", index + 1);
+            images.push(image);
+        }
+        report.elapsed = start.elapsed();
+        Ok((report, images))
+    }
+
+    // Optimized image URLs of every static route, rendered in order so suspended images are included.
+    async fn render_image_urls<IV>(&self, app_fn: impl Fn() -> IV + Clone + 'static) -> Vec<String>
+    where
+        IV: IntoView + 'static,
+    {
+        let (routes, _) = leptos_router::generate_route_list_inner_with_context(
+            app_fn.clone(),
+            self.provide_context(),
+        );
+        let mut urls = Vec::new();
+        for route in routes {
+            let path = route.path();
+            if path.contains(':') || path.contains('*') {
+                tracing::debug!("Skipping dynamic route {path} while warming the image cache");
+                continue;
+            }
+            let path = if path.is_empty() { "/" } else { path };
+            let url = format!("http://leptos{path}");
+            let app_fn = app_fn.clone();
+            let provide_optimizer = self.provide_context();
+            let html = leptos::ssr::render_to_string_async(move || {
+                provide_context(leptos_router::RouterIntegrationContext::new(
+                    leptos_router::ServerIntegration { path: url },
+                ));
+                leptos_meta::provide_meta_context();
+                provide_optimizer();
+                app_fn().into_view()
+            })
+            .await;
+            urls.extend(image_urls(&html, &self.handler_path()));
+        }
+        urls.sort_unstable();
+        urls.dedup();
+        urls
+    }
+
+    /// Fetches the given pages and pre-generates every optimized image they reference.
+    ///
+    /// Useful for content-driven pages that the route list can't enumerate.
+    /// A URL pointing to a `sitemap.xml` (or sitemap index) is expanded to the pages it lists.
+    /// The pages must be rendered by a server using this optimizer's handler path.
+    ///
+    /// Images that fail are recorded in the returned [`CacheReport`], only a page that can't be fetched is an error.
+    ///
+    /// ```no_run
+    /// use leptos_image::*;
+    ///
+    /// # async fn warm() -> Result<(), CreateImageError> {
+    /// let optimizer = ImageOptimizer::new("/__cache/image", "./target/site", 1);
+    /// let report = optimizer
+    ///     .warm_from_urls(["https://example.com/sitemap.xml"])
+    ///     .await?;
+    /// println!("{report}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "fetch")]
+    pub async fn warm_from_urls(
+        &self,
+        urls: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<CacheReport, CreateImageError> {
+        let start = std::time::Instant::now();
+        let client = reqwest::Client::new();
+        let mut pending: Vec<String> = urls.into_iter().map(Into::into).collect();
+        let mut visited = std::collections::HashSet::new();
+        let mut report = CacheReport::default();
+
+        while let Some(url) = pending.pop() {
+            if !visited.insert(url.clone()) {
+                continue;
+            }
+            let body = client
+                .get(&url)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())?
+                .text()
+                .await?;
+
+            if is_sitemap(&body) {
+                pending.extend(sitemap_locations(&body));
+                continue;
+            }
+
+            for image_url in image_urls(&body, &self.handler_path()) {
+                // Images shared by several pages are only counted once.
+                if !visited.insert(image_url.clone()) {
+                    continue;
+                }
+                let Some(image) = self.parse_image_url(&image_url) else {
+                    tracing::warn!("Skipping invalid image url {image_url}");
+                    continue;
+                };
+                report.record(self, &image).await;
+            }
+        }
+
+        report.elapsed = start.elapsed();
+        Ok(report)
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn is_sitemap(body: &str) -> bool {
+    body.contains("<urlset") || body.contains("<sitemapindex")
+}
+
+// Contents of every `<loc>` tag, the pages (or nested sitemaps) of a sitemap.
+#[cfg(feature = "fetch")]
+fn sitemap_locations(xml: &str) -> Vec<String> {
+    xml.split("<loc>")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("</loc>"))
+        .map(|(loc, _)| unescape(loc.trim()))
+        .collect()
+}
+
+// Optimized image URLs found anywhere in the page: `src`, `srcset`, preload links and inline styles.
+fn image_urls(html: &str, handler_path: &str) -> Vec<String> {
+    let handler_path = handler_path.trim_end_matches('/');
+    let mut urls: Vec<String> = html
+        .match_indices(handler_path)
+        .map(|(start, _)| {
+            let rest = &html[start..];
+            let end = rest
+                .find(|c: char| c == '"' || c == '\'' || c == ')' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            unescape(&rest[..end])
+        })
+        .filter(|url| url.len() > handler_path.len())
+        .collect();
+    urls.sort_unstable();
+    urls.dedup();
+    urls
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&amp;", "&")
+        .replace("&#x27;", "'")
+        .replace("&quot;", "\"")
+}
+
+#[cfg(test)]
+mod warm_tests {
+    use super::*;
+    use crate::optimizer::CachedImage;
+    use leptos_router::*;
+
+    const TEST_IMAGE: &str = "./example/start-axum/public/cute_ferris.png";
+
+    #[component]
+    fn App() -> impl IntoView {
+        crate::provide_image_context();
+        view! {
+            <Router>
+                <Routes>
+                    <Route path="/" view=|| view! { <crate::Image src=TEST_IMAGE width=48 height=48/> }/>
+                    <Route path="/post/:id" view=|| view! { <crate::Image src=TEST_IMAGE width=96 height=96/> }/>
+                    <Route path="/gallery" view=|| view! { <crate::ImageGallery images=[TEST_IMAGE] width=64 height=64 quality=60/> }/>
+                </Routes>
+            </Router>
+        }
+    }
+
+    #[test]
+    fn warm_cache_from_routes() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("warm-routes");
+
+        runtime
+            .block_on(async { optimizer.warm_cache(App).await })
+            .unwrap()
+            .unwrap();
+
+        let image =
+            |size| optimizer.get_file_path(&CachedImage::resize(TEST_IMAGE, size, size, 75));
+        assert!(std::path::Path::new(&image(48)).exists());
+        // The 2x variant of the `srcset`, at the quality of `OptimizerConfig::dpr_quality`.
+        let retina = CachedImage::resize(TEST_IMAGE, 96, 96, 55);
+        assert!(std::path::Path::new(&optimizer.get_file_path(&retina)).exists());
+        // Images of a gallery render with the config it shares.
+        assert!(std::path::Path::new(
+            &optimizer.get_file_path(&CachedImage::resize(TEST_IMAGE, 64, 64, 60))
+        )
+        .exists());
+        // Dynamic routes are skipped.
+        assert!(!std::path::Path::new(&image(96)).exists());
+    }
+
+    #[test]
+    fn exports_static_files() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/cache/image")
+            .root(".")
+            .cache_namespace("export")
+            .url_scheme(crate::UrlScheme::Static)
+            .build();
+
+        let report = runtime.block_on(optimizer.export(App)).unwrap();
+        assert!(report.is_success(), "{report}");
+
+        let manifest = std::fs::read(optimizer.export_manifest_path()).unwrap();
+        let files: Vec<(CachedImage, String)> = serde_json::from_slice(&manifest).unwrap();
+        let image = CachedImage::resize(TEST_IMAGE, 48, 48, 75);
+        let (_, url) = files
+            .iter()
+            .find(|(exported, _)| *exported == image)
+            .unwrap();
+        assert!(url.starts_with("/cache/image/export/"), "{url}");
+        // Served from the root as-is.
+        assert!(std::path::Path::new(&format!(".{url}")).exists());
+
+        // Pages rendered with the static scheme point to the exported files.
+        let provide_optimizer = optimizer.provide_context();
+        let html = runtime.block_on(tokio::task::LocalSet::new().run_until(
+            leptos::ssr::render_to_string_async(move || {
+                provide_context(RouterIntegrationContext::new(ServerIntegration {
+                    path: "http://leptos/".to_string(),
+                }));
+                leptos_meta::provide_meta_context();
+                provide_optimizer();
+                App().into_view()
+            }),
+        ));
+        assert!(html.contains(&format!("src=\"{url}\"")), "{html}");
+        assert!(!html.contains("/cache/image?"), "{html}");
+    }
+
+    #[test]
+    fn renders_custom_handler_path() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer = ImageOptimizer::builder()
+            .api_handler_path("/_images")
+            .root(".")
+            .base_path("app/")
+            .build();
+
+        let urls = runtime
+            .block_on(tokio::task::LocalSet::new().run_until(optimizer.render_image_urls(App)));
+
+        assert!(!urls.is_empty());
+        assert!(urls.iter().all(|url| url.starts_with("/app/_images")));
+    }
+
+    #[cfg(feature = "blur-placeholder")]
+    #[test]
+    fn sends_page_placeholders() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("page-placeholders");
+        let blur = CachedImage::blur(TEST_IMAGE);
+        runtime.block_on(async {
+            optimizer.create_image(&blur).await.unwrap();
+            optimizer.load_blur(&blur).await.unwrap();
+        });
+
+        let provide_optimizer = optimizer.provide_context();
+        let html = runtime.block_on(tokio::task::LocalSet::new().run_until(
+            leptos::ssr::render_to_string_async(move || {
+                leptos_meta::provide_meta_context();
+                provide_optimizer();
+                crate::provide_image_context();
+                view! { <crate::Image src=TEST_IMAGE width=48 height=48 blur=true/> }.into_view()
+            }),
+        ));
+        // The placeholder was requested by the image while the page rendered.
+        assert!(html.contains("data:image/svg+xml"), "{html}");
+
+        let _runtime = leptos::create_runtime();
+        optimizer.provide_context()();
+        let other = CachedImage::blur("/other.png");
+        let page_source = (None, TEST_IMAGE.to_string());
+        let info = optimizer.probe(TEST_IMAGE).unwrap();
+        optimizer
+            .source_dimensions
+            .insert((Some("acme".to_string()), "/private.png".to_string()), info);
+        let config = runtime
+            .block_on(crate::provider::get_image_config(
+                vec![other, blur.clone(), blur.clone()],
+                vec![page_source.clone(), (None, "/other.png".to_string())],
+            ))
+            .unwrap();
+        // Only cached placeholders and known dimensions of the page are sent, once.
+        assert_eq!(config.cache.len(), 1);
+        assert_eq!(config.cache[0].0, blur);
+        assert_eq!(
+            config.dimensions,
+            vec![(page_source, (info.width, info.height))]
+        );
+    }
+
+    #[cfg(feature = "blur-placeholder")]
+    #[test]
+    fn inlines_late_placeholders() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("late-placeholders");
+        let blur = CachedImage::blur(TEST_IMAGE);
+        runtime.block_on(async {
+            optimizer.create_image(&blur).await.unwrap();
+            optimizer.load_blur(&blur).await.unwrap();
+        });
+
+        let render = |provide_optimizer: Option<Box<dyn Fn()>>| {
+            runtime.block_on(tokio::task::LocalSet::new().run_until(
+                leptos::ssr::render_to_string_async(move || {
+                    leptos_meta::provide_meta_context();
+                    if let Some(provide_optimizer) = &provide_optimizer {
+                        provide_optimizer();
+                    }
+                    crate::provide_image_context();
+                    let original = crate::use_original_image_url(TEST_IMAGE, false);
+                    // Rendered once the image config has loaded, after the page's placeholders were sent.
+                    view! {
+                        <Suspense fallback=|| ()>
+                            {move || {
+                                original
+                                    .get()
+                                    .map(|_| {
+                                        view! {
+                                            <crate::Image src=TEST_IMAGE width=48 height=48 blur=true/>
+                                        }
+                                    })
+                            }}
+
+                        </Suspense>
+                    }
+                    .into_view()
+                }),
+            ))
+        };
+
+        let html = render(Some(Box::new(optimizer.provide_context())));
+        assert!(html.contains("data:image/svg+xml"), "{html}");
+        // Without the optimizer the config fails to load, and the image isn't rendered.
+        let html = render(None);
+        assert!(!html.contains("<img"), "{html}");
+    }
+
+    #[cfg(feature = "blur-placeholder")]
+    #[test]
+    fn renders_without_image_context() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("missing-context");
+        let blur = CachedImage::blur(TEST_IMAGE);
+        runtime.block_on(async {
+            optimizer.create_image(&blur).await.unwrap();
+            optimizer.load_blur(&blur).await.unwrap();
+        });
+
+        let provide_optimizer = optimizer.provide_context();
+        let html = runtime.block_on(tokio::task::LocalSet::new().run_until(
+            leptos::ssr::render_to_string_async(move || {
+                leptos_meta::provide_meta_context();
+                provide_optimizer();
+                let error = crate::use_image_context_error();
+                view! {
+                    <crate::Image src=TEST_IMAGE width=48 height=48 blur=true/>
+                    <p>{move || format!("{:?}", error.get())}</p>
+                }
+                .into_view()
+            }),
+        ));
+        // The image still renders, without its placeholder.
+        assert!(html.contains("<img"), "{html}");
+        assert!(!html.contains("data:image/svg+xml"), "{html}");
+        assert!(html.contains("Some(MissingContext)"), "{html}");
+    }
+
+    #[cfg(feature = "axum-handler")]
+    #[test]
+    fn reduces_data_from_page_request() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let optimizer =
+            ImageOptimizer::new("/cache/image", ".", 1).with_cache_namespace("reduced-data");
+
+        let render = |save_data: &'static str| {
+            let provide_optimizer = optimizer.provide_context();
+            runtime.block_on(tokio::task::LocalSet::new().run_until(
+                leptos::ssr::render_to_string_async(move || {
+                    leptos_meta::provide_meta_context();
+                    provide_optimizer();
+                    let request = axum::http::Request::builder()
+                        .header("Save-Data", save_data)
+                        .body(())
+                        .unwrap();
+                    provide_context(request.into_parts().0);
+                    crate::provide_image_context();
+                    view! { <crate::Image src=TEST_IMAGE width=48 height=48 reduced_data=true/> }
+                        .into_view()
+                }),
+            ))
+        };
+
+        let html = render("on");
+        assert!(html.contains("leptos-image-load"), "{html}");
+        // The decision is sent along with the image config, so hydration renders the same view.
+        assert!(html.contains(r#"\"reduced_data\":true"#), "{html}");
+        let html = render("off");
+        assert!(!html.contains("leptos-image-load"), "{html}");
+    }
+
+    #[test]
+    fn finds_image_urls() {
+        let html = r#"<link rel="preload" as="image" href="/cache/image?src=/a.png&amp;option[r][w]=100">
+            <img src="/cache/image/w_100,h_50,q_75/b.png" srcset="/cache/image/w_100,h_50,q_75/b.png 100w, /cache/image/w_200,h_100,q_75/b.png 200w">"#;
+
+        assert_eq!(
+            image_urls(html, "/cache/image"),
+            vec![
+                "/cache/image/w_100,h_50,q_75/b.png",
+                "/cache/image/w_200,h_100,q_75/b.png",
+                "/cache/image?src=/a.png&option[r][w]=100",
+            ]
+        );
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn reads_sitemap() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc>https://example.com/</loc></url>
+              <url><loc> https://example.com/blog?page=2&amp;tag=rust </loc></url>
+            </urlset>"#;
+
+        assert!(is_sitemap(xml));
+        assert_eq!(
+            sitemap_locations(xml),
+            vec![
+                "https://example.com/",
+                "https://example.com/blog?page=2&tag=rust"
+            ]
+        );
+    }
+}